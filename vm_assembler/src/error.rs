@@ -1,15 +1,68 @@
 use std::{fmt::Display, num::IntErrorKind};
 
 use super::Token;
+use crate::{parser::KeyWord, tokens::MAX_REPEAT};
 
 #[derive(Debug, PartialEq, Eq)]
 pub enum ParseError<'a> {
     InvalidKeyWord(String),
     InvalidIdent(String),
     InvalidToken(Token<'a>),
+    /// an operand that does not follow a keyword
+    ExpectedKeyWord(Token<'a>),
     InvalidRegister(String),
     InvalidNumber(IntErrorKind),
+    /// the inside of a character literal that is not one ascii character or a known escape
+    InvalidChar(String),
+    /// a string with no closing quote
+    UnterminatedString,
+    InvalidOperands(KeyWord),
+    /// a statement ended before all the operands its keyword needs
+    UnexpectedEndOfInput(KeyWord),
+    UnresolvedLabel(String),
+    /// `equ` was not followed by a number
+    InvalidConstant(String),
+    DuplicateConstant(String),
+    /// `.repeat` was not followed by a count and a statement
+    InvalidRepeat,
+    /// `.repeat` asked for more copies than fit in the memory a program is loaded into
+    RepeatTooLarge(u32),
+    /// `.start` was not followed by a label defined in the program
+    InvalidStart,
     EmptyFile,
+    /// `error` was found on `line`, counting from 1
+    AtLine {
+        line: usize,
+        error: Box<ParseError<'a>>,
+    },
+}
+
+impl<'a> ParseError<'a> {
+    fn at_line(self, line: usize) -> Self {
+        match self {
+            ParseError::AtLine { .. } => self,
+            error => ParseError::AtLine {
+                line,
+                error: Box::new(error),
+            },
+        }
+    }
+
+    /// the line the error is on, if it is known
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            ParseError::AtLine { line, .. } => Some(*line),
+            _ => None,
+        }
+    }
+}
+
+/// attaches `line` to the error of `result`
+pub(crate) fn at_line<'a, T>(
+    result: Result<T, ParseError<'a>>,
+    line: usize,
+) -> Result<T, ParseError<'a>> {
+    result.map_err(|error| error.at_line(line))
 }
 
 impl Display for ParseError<'_> {
@@ -19,8 +72,29 @@ impl Display for ParseError<'_> {
             ParseError::InvalidIdent(label) => write!(f, "invalid label {label}"),
             ParseError::InvalidRegister(register) => write!(f, "invalid register {register}"),
             ParseError::InvalidToken(token) => write!(f, "invalid token {token:?}"),
+            ParseError::ExpectedKeyWord(token) => write!(f, "expected keyword, found {token:?}"),
             ParseError::InvalidNumber(error) => write!(f, "invalid number {error:?}"),
+            ParseError::InvalidChar(inside) => write!(f, "invalid character literal '{inside}'"),
+            ParseError::UnterminatedString => write!(f, "string is missing its closing quote"),
+            ParseError::InvalidOperands(keyword) => {
+                write!(f, "invalid operands for keyword {keyword:?}")
+            }
+            ParseError::UnexpectedEndOfInput(keyword) => {
+                write!(f, "missing operands for keyword {keyword:?}")
+            }
+            ParseError::UnresolvedLabel(label) => write!(f, "unresolved label {label}"),
+            ParseError::InvalidConstant(name) => write!(f, "constant {name} needs a number"),
+            ParseError::DuplicateConstant(name) => write!(f, "constant {name} is already defined"),
+            ParseError::InvalidRepeat => write!(f, ".repeat needs a count and a statement"),
+            ParseError::RepeatTooLarge(count) => {
+                write!(
+                    f,
+                    ".repeat {count} is more than the {MAX_REPEAT} copies a program can hold"
+                )
+            }
+            ParseError::InvalidStart => write!(f, ".start needs a label defined in the program"),
             ParseError::EmptyFile => write!(f, "Attempted to parse empty file"),
+            ParseError::AtLine { line, error } => write!(f, "line {line}: {error}"),
         }
     }
 }
@@ -0,0 +1,226 @@
+use std::path::PathBuf;
+
+use clap::Parser as _;
+use tracing::info;
+use vm_assembler::Parser;
+use vm_cpu::image::Image;
+
+#[derive(clap::Parser, Debug, Default)]
+struct Args {
+    input: PathBuf,
+    /// file to write the assembled bytecode to
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// print the size of the assembled code and data
+    #[arg(long)]
+    stats: bool,
+    /// print each statement with its offset, bytes and source line
+    #[arg(long)]
+    listing: bool,
+    /// print each label with the offset it resolved to, and any that did not resolve
+    #[arg(long)]
+    symbols: bool,
+    /// write the output as an image, with a header holding the `.start` entry point
+    #[arg(long, requires = "output")]
+    image: bool,
+    /// fill the output with zeros up to this many bytes, for fixed size roms
+    #[arg(long, value_name = "BYTES", requires = "output")]
+    pad_to: Option<usize>,
+}
+
+fn run(args: &Args) -> Result<(), String> {
+    let data = std::fs::read_to_string(&args.input)
+        .map_err(|e| format!("failed to read {}: {e}", args.input.display()))?;
+
+    let parser = Parser::parse(&data).map_err(|e| e.to_string())?;
+
+    // printed before lowering so unresolved labels are listed rather than failing first
+    if args.symbols {
+        print!("{}", symbol_table(&parser));
+    }
+
+    let bytes = parser.bytes().map_err(|e| e.to_string())?;
+
+    info!(len = bytes.len(), "assembled");
+
+    if args.listing {
+        print!("{}", parser.listing().map_err(|e| e.to_string())?);
+    }
+
+    if args.stats {
+        println!("{}", parser.stats().map_err(|e| e.to_string())?);
+    }
+
+    if let Some(output) = &args.output {
+        let mut bytes = match args.image {
+            true => Image::new(parser.entry_point(), &bytes).encode(),
+            false => bytes,
+        };
+        if let Some(size) = args.pad_to {
+            if bytes.len() > size {
+                return Err(format!(
+                    "output is {} bytes, larger than --pad-to {size}",
+                    bytes.len()
+                ));
+            }
+            bytes.resize(size, 0);
+        }
+        std::fs::write(output, bytes)
+            .map_err(|e| format!("failed to write {}: {e}", output.display()))?;
+    }
+
+    Ok(())
+}
+
+/// one line per label ordered by offset, followed by the labels that were never defined
+fn symbol_table(parser: &Parser) -> String {
+    let mut symbols = parser.symbols().iter().collect::<Vec<_>>();
+    symbols.sort_by_key(|(name, addr)| (addr.0, **name));
+
+    let mut table = String::new();
+    for (name, addr) in symbols {
+        table.push_str(&format!("{:#06x}  {name}\n", addr.0));
+    }
+    for name in parser.unresolved() {
+        table.push_str(&format!("{:<6}  {name}\n", "??????"));
+    }
+
+    table
+}
+
+fn main() {
+    let args = Args::parse();
+
+    if let Err(e) = run(&args) {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use clap::{error::ErrorKind, Parser as _};
+    use vm_cpu::{cpu::Cpu, opcodes::OpCode, registers::Register};
+
+    use vm_assembler::Parser;
+
+    use super::{run, symbol_table, Args};
+
+    /// assembles `input` into `output` with every other flag left off
+    fn args(input: &Path, output: &Path) -> Args {
+        Args {
+            input: input.to_path_buf(),
+            output: Some(output.to_path_buf()),
+            ..Args::default()
+        }
+    }
+
+    #[test]
+    fn writes_output() {
+        let dir = std::env::temp_dir();
+        let input = dir.join(format!("vm_assembler_{}.asm", std::process::id()));
+        let output = dir.join(format!("vm_assembler_{}.bin", std::process::id()));
+
+        std::fs::write(&input, "mov r1, 5\ninc r1\nhalt").unwrap();
+
+        run(&args(&input, &output)).unwrap();
+
+        let bytes = std::fs::read(&output).unwrap();
+        let _ = std::fs::remove_file(input);
+        let _ = std::fs::remove_file(output);
+
+        assert_eq!(
+            bytes,
+            [
+                OpCode::MovRegU8 as u8,
+                Register::R1 as u8,
+                5,
+                OpCode::IncReg as u8,
+                Register::R1 as u8,
+                OpCode::Halt as u8,
+            ]
+        );
+    }
+
+    #[test]
+    fn writes_image() {
+        let dir = std::env::temp_dir();
+        let input = dir.join(format!("vm_assembler_image_{}.asm", std::process::id()));
+        let output = dir.join(format!("vm_assembler_image_{}.bin", std::process::id()));
+
+        std::fs::write(&input, "mov r1, 5\n.start main\nmain:\ninc r1\nhalt").unwrap();
+
+        run(&Args {
+            image: true,
+            ..args(&input, &output)
+        })
+        .unwrap();
+
+        let bytes = std::fs::read(&output).unwrap();
+        let _ = std::fs::remove_file(input);
+        let _ = std::fs::remove_file(output);
+
+        let mut cpu = Cpu::load_image(&bytes).unwrap();
+        assert_eq!(cpu.registers().get(Register::IP), 3);
+        cpu.execute();
+        assert_eq!(cpu.registers().get(Register::R1), 1);
+    }
+
+    #[test]
+    fn pads_output() {
+        let dir = std::env::temp_dir();
+        let input = dir.join(format!("vm_assembler_pad_{}.asm", std::process::id()));
+        let output = dir.join(format!("vm_assembler_pad_{}.bin", std::process::id()));
+
+        // 10 bytes of code
+        std::fs::write(&input, "mov r1, 5\nmov r2, 6\nadd r1, r2\nhalt").unwrap();
+
+        let mut args = Args {
+            pad_to: Some(64),
+            ..args(&input, &output)
+        };
+        run(&args).unwrap();
+        let bytes = std::fs::read(&output).unwrap();
+
+        args.pad_to = Some(8);
+        let err = run(&args).unwrap_err();
+
+        let _ = std::fs::remove_file(input);
+        let _ = std::fs::remove_file(output);
+
+        assert_eq!(bytes.len(), 64);
+        assert_eq!(bytes[9], OpCode::Halt as u8);
+        assert!(bytes[10..].iter().all(|byte| *byte == 0));
+        assert_eq!(err, "output is 10 bytes, larger than --pad-to 8");
+    }
+
+    #[test]
+    fn pad_to_needs_output() {
+        let err = Args::try_parse_from(["vm_assembler", "in.asm", "--pad-to", "64"]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::MissingRequiredArgument);
+
+        let args = Args::try_parse_from(["vm_assembler", "in.asm", "--pad-to", "64", "-o", "out"]);
+        assert_eq!(args.unwrap().pad_to, Some(64));
+    }
+
+    #[test]
+    fn image_needs_output() {
+        let err = Args::try_parse_from(["vm_assembler", "in.asm", "--image"]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::MissingRequiredArgument);
+
+        let args = Args::try_parse_from(["vm_assembler", "in.asm", "--image", "-o", "out"]);
+        assert!(args.unwrap().image);
+    }
+
+    #[test]
+    fn symbols() {
+        let parser = Parser::parse("start:\nmov r1, 5\nend:\njump missing\nhalt").unwrap();
+
+        assert_eq!(
+            symbol_table(&parser),
+            "0x0000  start\n0x0003  end\n??????  missing\n"
+        );
+    }
+}
@@ -1,10 +1,14 @@
+use crate::error::at_line;
 use crate::tokens::Lexer;
 use crate::ParseError;
 use crate::Token;
 
 use tracing::info;
 use tracing::instrument;
+use vm_cpu::cpu::DEFAULT_STACK_START;
 use vm_cpu::memory::Address;
+use vm_cpu::opcodes::{Instruction, Value};
+use vm_cpu::registers::RegisterMask;
 
 use std::collections::HashMap;
 use std::fmt::Display;
@@ -13,28 +17,19 @@ use std::str::FromStr;
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum AstNode<'a> {
     Token(Token<'a>),
+    /// the byte offset of a label from the start of the program. the cpu adds its program start
+    /// to every address operand, so a program runs the same wherever it is loaded
     Label(Address),
     Ident(&'a str),
     KeyWord(KeyWord),
 }
 
-impl AstNode<'_> {
-    fn byte_size(&self) -> Option<usize> {
+impl<'a> AstNode<'a> {
+    fn token(&self) -> Option<Token<'a>> {
         match self {
-            AstNode::Token(token) => match token {
-                Token::Register(_) => Some(1),
-                Token::Number(number) => match number {
-                    crate::tokens::Number::U8(_) => Some(1),
-                    crate::tokens::Number::U16(_) => Some(2),
-                    crate::tokens::Number::U32(_) => Some(4),
-                },
-                Token::Address(_addr) => Some(4),
-                Token::Identifier(_ident) => None,
-                Token::Comma => None,
-                Token::Space => None,
-            },
-            AstNode::Label(_) => None,
-            AstNode::Ident(_) => None,
+            AstNode::Token(token) => Some(token.clone()),
+            AstNode::Label(address) => Some(Token::Address(*address)),
+            AstNode::Ident(ident) => Some(Token::Identifier(ident)),
             AstNode::KeyWord(_) => None,
         }
     }
@@ -59,11 +54,20 @@ impl<'a> From<Token<'a>> for AstNode<'a> {
 #[derive(Debug, Default)]
 pub struct Ast<'a> {
     nodes: Vec<AstNode<'a>>,
+    /// the source line of each node, counting from 1
+    lines: Vec<usize>,
 }
 
 impl<'a> Ast<'a> {
+    /// adds `node` on the same line as the last one
     pub fn push(&mut self, node: AstNode<'a>) {
+        let line = self.lines.last().copied().unwrap_or(1);
+        self.push_at(node, line);
+    }
+
+    pub fn push_at(&mut self, node: AstNode<'a>, line: usize) {
         self.nodes.push(node);
+        self.lines.push(line);
     }
 
     pub fn get(&self, idx: usize) -> Option<&AstNode<'a>> {
@@ -80,58 +84,50 @@ impl<'a> Ast<'a> {
             *old_node = node;
         }
     }
+}
 
-    fn into_bytes(self) -> Vec<u8> {
-        let nodes = self.nodes;
-
-        let mut output = Vec::new();
-
-        for node in nodes {
-            match node {
-                AstNode::Token(token) => match token {
-                    Token::Register(register) => output.push(register as u8),
-                    Token::Number(number) => match number {
-                        crate::tokens::Number::U8(val) => output.push(val),
-                        crate::tokens::Number::U16(val) => {
-                            let bytes = val.to_le_bytes();
-
-                            for byte in bytes {
-                                output.push(byte);
-                            }
-                        }
-                        crate::tokens::Number::U32(val) => {
-                            let bytes = val.to_le_bytes();
+/// doc comments collected by [`Parser::parse_with_comments`]
+#[derive(Debug, Default, PartialEq)]
+pub struct Metadata<'a> {
+    /// comments keyed by the label they precede
+    pub labels: HashMap<&'a str, String>,
+    /// comments keyed by the index of the statement they precede
+    pub statements: HashMap<usize, String>,
+}
 
-                            for byte in bytes {
-                                output.push(byte);
-                            }
-                        }
-                    },
-                    Token::Address(address) => {
-                        let val = u32::from(address).to_le_bytes();
+/// how much memory an assembled program takes up. code and data are laid out in program order
+/// from the program start, so the program occupies `total()` bytes from there
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Stats {
+    pub code_bytes: usize,
+    pub data_bytes: usize,
+    /// the top of the stack when the program is loaded at 0 with the cli defaults
+    pub stack_start: usize,
+}
 
-                        for byte in val {
-                            output.push(byte);
-                        }
-                    }
-                    // these tokens can not be turned into bytes as they do not conform to what
-                    // the cpu expects
-                    Token::Comma | Token::Space | Token::Identifier(_) => {}
-                },
-                AstNode::Label(address) => {
-                    let bytes = u32::from(address).to_le_bytes();
+impl Stats {
+    pub fn total(&self) -> usize {
+        self.code_bytes + self.data_bytes
+    }
 
-                    for byte in bytes {
-                        output.push(byte)
-                    }
-                }
-                // these nodes can not be turned into bytes as they do not conform to what
-                // the cpu expects
-                AstNode::Ident(_) | AstNode::KeyWord(_) => {}
-            }
-        }
+    /// how far the stack can grow down from `stack_start` before it reaches the program
+    pub fn stack_bytes(&self) -> usize {
+        self.stack_start.saturating_sub(self.total())
+    }
+}
 
-        output
+impl Display for Stats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "code:  {} bytes", self.code_bytes)?;
+        writeln!(f, "data:  {} bytes", self.data_bytes)?;
+        writeln!(f, "total: {} bytes", self.total())?;
+        write!(
+            f,
+            "stack: {} bytes, {:#06x} down to {:#06x}",
+            self.stack_bytes(),
+            self.stack_start,
+            self.total()
+        )
     }
 }
 
@@ -139,6 +135,13 @@ impl<'a> Ast<'a> {
 pub struct Parser<'a> {
     ast: Ast<'a>,
     resolved_labels: HashMap<&'a str, Address>,
+    /// values named with `NAME equ value`
+    constants: HashMap<&'a str, crate::tokens::Number>,
+    metadata: Metadata<'a>,
+    /// the text the program was parsed from
+    source: &'a str,
+    /// where execution begins, set with `.start label`
+    entry: Address,
 }
 
 impl Display for Parser<'_> {
@@ -166,61 +169,182 @@ impl<'a> Parser<'a> {
         self.ast.push(token.into());
     }
 
-    #[instrument]
     pub fn parse(data: &'a str) -> Result<Parser<'a>, ParseError<'a>> {
-        if data.is_empty() {
-            return Err(ParseError::EmptyFile);
+        Self::parse_inner(data, false)
+    }
+
+    /// like [`Parser::parse`] but keeps the comments preceding a label or statement as its
+    /// doc string, see [`Parser::metadata`]
+    pub fn parse_with_comments(data: &'a str) -> Result<Parser<'a>, ParseError<'a>> {
+        Self::parse_inner(data, true)
+    }
+
+    pub fn metadata(&self) -> &Metadata<'a> {
+        &self.metadata
+    }
+
+    /// the value of a constant defined with `equ`
+    pub fn constant(&self, name: &str) -> Option<Value> {
+        self.constants.get(name).cloned().map(Value::from)
+    }
+
+    /// every label defined in the program and the offset it resolved to
+    pub fn symbols(&self) -> &HashMap<&'a str, Address> {
+        &self.resolved_labels
+    }
+
+    /// operands naming neither a label nor a constant, in the order they first appear. lowering
+    /// fails with [`ParseError::UnresolvedLabel`] on the first of these
+    pub fn unresolved(&self) -> Vec<&'a str> {
+        let mut unresolved = Vec::new();
+        for node in &self.ast.nodes {
+            if let AstNode::Ident(ident) = node {
+                if !unresolved.contains(ident) {
+                    unresolved.push(*ident);
+                }
+            }
         }
 
-        let lexer = Lexer::lex(data);
+        unresolved
+    }
 
-        let mut parser = Parser::default();
+    /// the offset of the label named by `.start`, or 0 if there is none. IP should start at
+    /// the program start plus this
+    pub fn entry_point(&self) -> Address {
+        self.entry
+    }
 
-        let mut addr: usize = 0;
+    #[instrument]
+    fn parse_inner(data: &'a str, keep_comments: bool) -> Result<Parser<'a>, ParseError<'a>> {
+        if data.is_empty() {
+            return Err(ParseError::EmptyFile);
+        }
 
-        for token in lexer.tokens.iter() {
-            match token {
+        let lexer = Lexer::lex(data)?.expand_repeats()?;
+
+        let mut parser = Parser {
+            source: data,
+            ..Default::default()
+        };
+
+        let mut statements = 0;
+        // each label and the index of the statement it points at, resolved to offsets once the
+        // size of every statement is known
+        let mut labels = Vec::new();
+        // comment lines waiting for the label or statement they document
+        let mut doc: Option<String> = None;
+        // tokens up to here were already consumed by a constant definition or `.start`
+        let mut skip_to = 0;
+        // the label named by `.start` and the line it was named on
+        let mut start = None;
+
+        for (idx, token) in lexer.tokens.iter().enumerate() {
+            if idx < skip_to {
+                continue;
+            }
+
+            let node = match token {
+                Token::Comment(comment) => {
+                    if keep_comments {
+                        match &mut doc {
+                            Some(doc) => {
+                                doc.push('\n');
+                                doc.push_str(comment);
+                            }
+                            None => doc = Some(comment.to_string()),
+                        }
+                    }
+                    continue;
+                }
                 Token::Identifier(s) => match KeyWord::from_str(s) {
-                    Ok(keyword) => parser.push(AstNode::KeyWord(keyword)),
-                    Err(e) => {
-                        match e {
-                            ParseError::InvalidKeyWord(_) => {}
-                            _ => panic!("{e:?}"),
+                    Ok(kw) => {
+                        if let Some(doc) = doc.take() {
+                            parser.metadata.statements.insert(statements, doc);
+                        }
+                        statements += 1;
+                        AstNode::KeyWord(kw)
+                    }
+                    Err(_) if *s == ".start" => {
+                        let Some((label, Token::Identifier(name))) = next_word(&lexer.tokens, idx)
+                        else {
+                            return at_line(Err(ParseError::InvalidStart), lexer.lines[idx]);
                         };
 
+                        start = Some((*name, lexer.lines[idx]));
+                        skip_to = label + 1;
+                        continue;
+                    }
+                    Err(_) => {
+                        // `NAME equ value` defines a constant, which has to come before its uses
+                        if let Some((equ, Token::Identifier("equ"))) = next_word(&lexer.tokens, idx)
+                        {
+                            let Some((value, Token::Number(num))) = next_word(&lexer.tokens, equ)
+                            else {
+                                let error = ParseError::InvalidConstant(s.to_string());
+                                return at_line(Err(error), lexer.lines[idx]);
+                            };
+
+                            if parser.constants.insert(s, num.clone()).is_some() {
+                                let error = ParseError::DuplicateConstant(s.to_string());
+                                return at_line(Err(error), lexer.lines[idx]);
+                            }
+
+                            skip_to = value + 1;
+                            continue;
+                        }
+
                         if s.ends_with(":") {
-                            parser
-                                .resolved_labels
-                                .insert(&s[0..s.len().saturating_sub(1)], Address::from(addr));
-                            addr += 4;
-                        } else {
-                            parser.push(AstNode::Ident(s));
+                            let label = &s[0..s.len().saturating_sub(1)];
+                            labels.push((label, statements));
+                            if let Some(doc) = doc.take() {
+                                parser.metadata.labels.insert(label, doc);
+                            }
+                            continue;
+                        }
+
+                        match parser.constants.get(s) {
+                            Some(num) => AstNode::Token(Token::Number(num.clone())),
+                            None => AstNode::Ident(s),
                         }
                     }
                 },
-                _ => {
-                    let tok = AstNode::from(token.clone());
-                    if let Some(amount) = tok.byte_size() {
-                        addr += amount;
-                    }
+                _ => AstNode::from(token.clone()),
+            };
 
-                    parser.push(tok);
-                }
+            parser.ast.push_at(node, lexer.lines[idx]);
+        }
+
+        let mut offsets = vec![0];
+        for size in statement_sizes(&parser.ast.nodes) {
+            offsets.push(offsets.last().unwrap() + size);
+        }
+        for (label, statement) in labels {
+            let offset = offsets.get(statement).copied().unwrap_or_default();
+            parser.resolved_labels.insert(label, Address::from(offset));
+        }
+
+        if let Some((label, line)) = start {
+            match parser.resolved_labels.get(label) {
+                Some(addr) => parser.entry = *addr,
+                None => return at_line(Err(ParseError::InvalidStart), line),
             }
         }
 
+        let mut keyword = None;
+
         for idx in 0..parser.ast.nodes.len() {
             let Some(token) = parser.ast.get(idx) else {
                 break;
             };
 
             match token {
+                AstNode::KeyWord(kw) => keyword = Some(*kw),
                 AstNode::Label(address) => info!(?address),
                 AstNode::Ident(ident) => {
                     if let Some(addr) = parser.resolved_labels.get(ident) {
                         info!(?ident, ?addr);
                         info!(?token);
-                        parser.ast.set(idx, AstNode::Label(*addr));
+                        parser.ast.set(idx, resolve_label(keyword, *addr));
                     }
                 }
                 _ => {}
@@ -231,6 +355,574 @@ impl<'a> Parser<'a> {
 
         Ok(parser)
     }
+
+    /// lowers the parsed program into instructions and data, in program order
+    pub fn statements(&self) -> Result<Vec<Statement>, ParseError<'a>> {
+        tokens_to_statements(&self.ast.nodes, &self.ast.lines)
+    }
+
+    /// lowers the parsed program into the instructions the cpu executes, skipping any data
+    pub fn instructions(&self) -> Result<Vec<Instruction>, ParseError<'a>> {
+        Ok(self
+            .statements()?
+            .into_iter()
+            .filter_map(|statement| match statement {
+                Statement::Instruction(inst) => Some(inst),
+                Statement::Data(_) => None,
+            })
+            .collect())
+    }
+
+    /// lowers `data` one line at a time instead of parsing the whole program up front. labels
+    /// are resolved in a single pass, so a label can only be used after the line defining it;
+    /// forward references yield [`ParseError::UnresolvedLabel`]
+    pub fn stream(data: &'a str) -> InstructionStream<'a> {
+        InstructionStream {
+            lines: data.lines(),
+            labels: HashMap::new(),
+            addr: 0,
+            pending: Vec::new().into_iter(),
+            failed: false,
+            line: 0,
+        }
+    }
+
+    /// the assembled program, one statement per line with its byte offset, the bytes it
+    /// encodes to and the source line it came from
+    pub fn listing(&self) -> Result<String, ParseError<'a>> {
+        let source = self.source.lines().collect::<Vec<_>>();
+        // every keyword starts a statement
+        let lines = self
+            .ast
+            .nodes
+            .iter()
+            .zip(&self.ast.lines)
+            .filter(|(node, _)| matches!(node, AstNode::KeyWord(_)))
+            .map(|(_, line)| *line);
+
+        let mut listing = String::new();
+        let mut offset = 0;
+
+        for (statement, line) in self.statements()?.iter().zip(lines) {
+            let hex = statement
+                .encode()
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect::<Vec<_>>()
+                .join(" ");
+            let text = source.get(line - 1).map_or("", |text| text.trim());
+            listing.push_str(&format!("{offset:#06x}  {hex:<26}  {line:>4}  {text}\n"));
+
+            offset += statement.size();
+        }
+
+        Ok(listing)
+    }
+
+    pub fn stats(&self) -> Result<Stats, ParseError<'a>> {
+        let mut stats = Stats {
+            stack_start: DEFAULT_STACK_START as usize,
+            ..Stats::default()
+        };
+
+        for statement in self.statements()? {
+            match statement {
+                Statement::Instruction(_) => stats.code_bytes += statement.size(),
+                Statement::Data(_) => stats.data_bytes += statement.size(),
+            }
+        }
+
+        Ok(stats)
+    }
+
+    /// encodes the program into the bytecode the cpu loads
+    pub fn bytes(&self) -> Result<Vec<u8>, ParseError<'a>> {
+        Ok(self
+            .statements()?
+            .iter()
+            .flat_map(Statement::encode)
+            .collect())
+    }
+}
+
+/// a lowered line of assembly
+#[derive(Debug, Clone, PartialEq)]
+pub enum Statement {
+    Instruction(Instruction),
+    /// bytes emitted as is by db, dw and dd
+    Data(Vec<u8>),
+}
+
+impl Statement {
+    pub fn encode(&self) -> Vec<u8> {
+        match self {
+            Statement::Instruction(inst) => inst.encode(),
+            Statement::Data(bytes) => bytes.clone(),
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        match self {
+            Statement::Instruction(inst) => inst.size(),
+            Statement::Data(bytes) => bytes.len(),
+        }
+    }
+}
+
+/// `lines` holds the source line of each node, errors are reported on the line of the keyword
+/// they belong to
+fn tokens_to_statements<'a>(
+    nodes: &[AstNode<'a>],
+    lines: &[usize],
+) -> Result<Vec<Statement>, ParseError<'a>> {
+    split_statements(nodes, lines)?
+        .into_iter()
+        .map(|(keyword, line, operands)| at_line(lower(keyword, &operands), line))
+        .collect()
+}
+
+/// the keyword of a statement, the line it is on and its operands
+type Unlowered<'a> = (KeyWord, usize, Vec<AstNode<'a>>);
+
+/// groups `nodes` into statements, with spaces, commas and comments removed
+fn split_statements<'a>(
+    nodes: &[AstNode<'a>],
+    lines: &[usize],
+) -> Result<Vec<Unlowered<'a>>, ParseError<'a>> {
+    let mut statements = Vec::new();
+    let mut keyword = None;
+    let mut operands = Vec::new();
+
+    for (node, line) in nodes.iter().zip(lines) {
+        match node {
+            AstNode::Token(Token::Space | Token::Comma | Token::Comment(_)) => {}
+            AstNode::KeyWord(next) => {
+                if let Some((keyword, line)) = keyword.replace((*next, *line)) {
+                    statements.push((keyword, line, std::mem::take(&mut operands)));
+                }
+            }
+            _ => {
+                if keyword.is_none() {
+                    let token = node.token().unwrap();
+                    return at_line(Err(ParseError::ExpectedKeyWord(token)), *line);
+                }
+                operands.push(node.clone());
+            }
+        }
+    }
+
+    if let Some((keyword, line)) = keyword {
+        statements.push((keyword, line, operands));
+    }
+
+    Ok(statements)
+}
+
+/// the encoded size of each statement, which is what label offsets are summed from. a label
+/// operand encodes the same wherever it points, so every identifier is lowered as a label at 0.
+/// statements that do not lower take up no space, lowering them again reports the error
+fn statement_sizes(nodes: &[AstNode<'_>]) -> Vec<usize> {
+    let mut keyword = None;
+    let nodes = nodes
+        .iter()
+        .map(|node| match node {
+            AstNode::KeyWord(kw) => {
+                keyword = Some(*kw);
+                node.clone()
+            }
+            AstNode::Ident(_) => resolve_label(keyword, Address::from(0)),
+            _ => node.clone(),
+        })
+        .collect::<Vec<_>>();
+
+    split_statements(&nodes, &vec![0; nodes.len()])
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(keyword, _, operands)| lower(keyword, &operands).map_or(0, |s| s.size()))
+        .collect()
+}
+
+/// the index and token of the first word after `idx`, skipping spaces
+fn next_word<'t, 'a>(tokens: &'t [Token<'a>], idx: usize) -> Option<(usize, &'t Token<'a>)> {
+    tokens
+        .iter()
+        .enumerate()
+        .skip(idx + 1)
+        .find(|(_, token)| !matches!(token, Token::Space))
+}
+
+/// the node a label operand of `keyword` becomes. push takes the label's address as a value so
+/// code addresses can be computed with, everything else uses it as an address
+fn resolve_label<'a>(keyword: Option<KeyWord>, addr: Address) -> AstNode<'a> {
+    match keyword {
+        Some(KeyWord::Push) => AstNode::Token(Token::Number(crate::tokens::Number::U32(addr.0))),
+        _ => AstNode::Label(addr),
+    }
+}
+
+/// `value` as an unsigned value `width` bytes wide, `value` has to fit in it
+fn widen(value: Value, width: usize) -> Value {
+    match width {
+        1 => Value::U8(value.as_u32() as u8),
+        2 => Value::U16(value.as_u32() as u16),
+        _ => Value::U32(value.as_u32()),
+    }
+}
+
+/// builds a single statement from a keyword and its operands, with spaces and commas removed
+fn lower<'a>(keyword: KeyWord, operands: &[AstNode<'a>]) -> Result<Statement, ParseError<'a>> {
+    use crate::tokens::Number::{U16, U8};
+    use AstNode::Label;
+    use Token::{IndexedAddress, Number, Register as Reg};
+
+    if let Some(AstNode::Ident(ident)) = operands
+        .iter()
+        .find(|node| matches!(node, AstNode::Ident(_)))
+    {
+        return Err(ParseError::UnresolvedLabel(ident.to_string()));
+    }
+
+    // the operands that are there are only checked against the forms of the keyword below
+    if operands.len() < keyword.min_operands() {
+        return Err(ParseError::UnexpectedEndOfInput(keyword));
+    }
+
+    if let Some(width) = keyword.data_width() {
+        return data(keyword, width, operands).map(Statement::Data);
+    }
+
+    let inst = match (keyword, operands) {
+        (KeyWord::Mov, [AstNode::Token(Reg(dst)), AstNode::Token(Reg(src))]) => {
+            Instruction::MovRegReg(*dst, *src)
+        }
+        (KeyWord::Mov, [AstNode::Token(Reg(reg)), AstNode::Token(Number(num))]) => {
+            Instruction::MovRegNum(*reg, Value::from(num.clone()))
+        }
+        (KeyWord::Mov, [AstNode::Token(Reg(reg)), Label(addr)]) => {
+            Instruction::MovMemReg(*addr, *reg)
+        }
+        (KeyWord::Mov, [Label(addr), AstNode::Token(Reg(reg))]) => {
+            Instruction::MovRegMem(*reg, *addr)
+        }
+        (KeyWord::Mov, [Label(addr), AstNode::Token(Number(num))]) => {
+            Instruction::MovMemNum(*addr, Value::from(num.clone()))
+        }
+
+        (KeyWord::MovL, [AstNode::Token(Reg(reg)), AstNode::Token(Number(U8(val)))]) => {
+            Instruction::MovRegLowU16(*reg, *val as u16)
+        }
+        (KeyWord::MovL, [AstNode::Token(Reg(reg)), AstNode::Token(Number(U16(val)))]) => {
+            Instruction::MovRegLowU16(*reg, *val)
+        }
+        (KeyWord::MovH, [AstNode::Token(Reg(reg)), AstNode::Token(Number(U8(val)))]) => {
+            Instruction::MovRegHighU16(*reg, *val as u16)
+        }
+        (KeyWord::MovH, [AstNode::Token(Reg(reg)), AstNode::Token(Number(U16(val)))]) => {
+            Instruction::MovRegHighU16(*reg, *val)
+        }
+
+        (KeyWord::SetB, [AstNode::Token(Reg(reg)), AstNode::Token(Number(U8(byte)))]) => {
+            Instruction::SetByte(*reg, *byte)
+        }
+
+        (KeyWord::Add, [AstNode::Token(Reg(dst)), AstNode::Token(Reg(src))]) => {
+            Instruction::AddRegReg(*dst, *src)
+        }
+        (KeyWord::Add, [AstNode::Token(Reg(reg)), AstNode::Token(Number(num))]) => {
+            Instruction::AddRegNum(*reg, Value::from(num.clone()))
+        }
+        (KeyWord::Add, [AstNode::Token(Reg(reg)), Label(addr)]) => {
+            Instruction::AddRegMem(*reg, *addr)
+        }
+        (KeyWord::Add, [Label(addr), AstNode::Token(Reg(reg))]) => {
+            Instruction::AddMemReg(*addr, *reg)
+        }
+
+        (KeyWord::And, [AstNode::Token(Reg(dst)), AstNode::Token(Reg(src))]) => {
+            Instruction::AndRegReg(*dst, *src)
+        }
+        (KeyWord::And, [AstNode::Token(Reg(reg)), AstNode::Token(Number(num))]) => {
+            Instruction::AndRegNum(*reg, Value::from(num.clone()))
+        }
+        (KeyWord::Or, [AstNode::Token(Reg(dst)), AstNode::Token(Reg(src))]) => {
+            Instruction::OrRegReg(*dst, *src)
+        }
+        (KeyWord::Or, [AstNode::Token(Reg(reg)), AstNode::Token(Number(num))]) => {
+            Instruction::OrRegNum(*reg, Value::from(num.clone()))
+        }
+        (KeyWord::Xor, [AstNode::Token(Reg(dst)), AstNode::Token(Reg(src))]) => {
+            Instruction::XorRegReg(*dst, *src)
+        }
+        (KeyWord::Xor, [AstNode::Token(Reg(reg)), AstNode::Token(Number(num))]) => {
+            Instruction::XorRegNum(*reg, Value::from(num.clone()))
+        }
+        (KeyWord::Not, [AstNode::Token(Reg(reg))]) => Instruction::NotReg(*reg),
+
+        (KeyWord::Shl, [AstNode::Token(Reg(dst)), AstNode::Token(Reg(src))]) => {
+            Instruction::ShlRegReg(*dst, *src)
+        }
+        (KeyWord::Shl, [AstNode::Token(Reg(reg)), AstNode::Token(Number(U8(count)))]) => {
+            Instruction::ShlRegNum(*reg, *count)
+        }
+        (KeyWord::Shr, [AstNode::Token(Reg(dst)), AstNode::Token(Reg(src))]) => {
+            Instruction::ShrRegReg(*dst, *src)
+        }
+        (KeyWord::Shr, [AstNode::Token(Reg(reg)), AstNode::Token(Number(U8(count)))]) => {
+            Instruction::ShrRegNum(*reg, *count)
+        }
+
+        (
+            KeyWord::AddPair,
+            [AstNode::Token(Reg(lo)), AstNode::Token(Reg(hi)), AstNode::Token(Reg(src_lo)), AstNode::Token(Reg(src_hi))],
+        ) => Instruction::AddPair {
+            lo: *lo,
+            hi: *hi,
+            src_lo: *src_lo,
+            src_hi: *src_hi,
+        },
+        (KeyWord::Sub, [AstNode::Token(Reg(dst)), AstNode::Token(Reg(src))]) => {
+            Instruction::SubRegReg(*dst, *src)
+        }
+        (KeyWord::Mul, [AstNode::Token(Reg(dst)), AstNode::Token(Reg(src))]) => {
+            Instruction::MulRegReg(*dst, *src)
+        }
+        (KeyWord::Div, [AstNode::Token(Reg(dst)), AstNode::Token(Reg(src))]) => {
+            Instruction::DivRegReg(*dst, *src)
+        }
+
+        (KeyWord::AddF, [AstNode::Token(Reg(dst)), AstNode::Token(Reg(src))]) => {
+            Instruction::AddF32RegReg(*dst, *src)
+        }
+        (KeyWord::SubF, [AstNode::Token(Reg(dst)), AstNode::Token(Reg(src))]) => {
+            Instruction::SubF32RegReg(*dst, *src)
+        }
+        (KeyWord::MulF, [AstNode::Token(Reg(dst)), AstNode::Token(Reg(src))]) => {
+            Instruction::MulF32RegReg(*dst, *src)
+        }
+        (KeyWord::DivF, [AstNode::Token(Reg(dst)), AstNode::Token(Reg(src))]) => {
+            Instruction::DivF32RegReg(*dst, *src)
+        }
+
+        (KeyWord::Load, [AstNode::Token(Reg(reg)), Label(addr)]) => Instruction::Load(*reg, *addr),
+        (KeyWord::Load, [AstNode::Token(Reg(dest)), AstNode::Token(Reg(addr))]) => {
+            Instruction::LoadIndirect(*dest, *addr)
+        }
+
+        (KeyWord::Jump, [Label(addr)]) => Instruction::Jump(*addr),
+        (KeyWord::JumpEq, [Label(addr)]) => Instruction::JumpEq(*addr),
+        (KeyWord::JumpNe, [Label(addr)]) => Instruction::JumpNe(*addr),
+        (KeyWord::JumpGe, [Label(addr)]) => Instruction::JumpGe(*addr),
+        (KeyWord::JumpGte, [Label(addr)]) => Instruction::JumpGte(*addr),
+        (KeyWord::JumpLt, [Label(addr)]) => Instruction::JumpLt(*addr),
+        (KeyWord::JumpLte, [Label(addr)]) => Instruction::JumpLte(*addr),
+        (KeyWord::JumpReg, [AstNode::Token(Reg(reg))]) => Instruction::JumpReg(*reg),
+
+        (KeyWord::Push, [AstNode::Token(Reg(reg))]) => Instruction::PushReg(*reg),
+        (KeyWord::Push, [AstNode::Token(Number(num))]) => {
+            Instruction::PushVal(Value::from(num.clone()))
+        }
+        (KeyWord::Push, [Label(addr)]) => Instruction::PushMem(*addr),
+
+        (KeyWord::Pop, [AstNode::Token(Reg(reg))]) => Instruction::PopReg(*reg),
+        (KeyWord::PushAll, []) => Instruction::PushAll,
+        (KeyWord::PopAll, []) => Instruction::PopAll,
+        (KeyWord::StackDepth, [AstNode::Token(Reg(reg))]) => Instruction::StackDepthReg(*reg),
+
+        (KeyWord::Call, [Label(addr)]) => Instruction::Call(*addr),
+        (KeyWord::Call, [Label(addr), AstNode::Token(Number(U8(mask)))]) => {
+            Instruction::CallSaving(*addr, RegisterMask(*mask))
+        }
+        (KeyWord::Ret, []) => Instruction::Ret,
+        (KeyWord::IRet, []) => Instruction::IRet,
+        (KeyWord::Nop, []) => Instruction::Nop,
+        (KeyWord::Halt, []) => Instruction::Halt,
+
+        (KeyWord::Cmp, [AstNode::Token(Reg(left)), AstNode::Token(Reg(right))]) => {
+            Instruction::CmpReg(*left, *right)
+        }
+        // a float operand makes the whole compare a float one
+        (KeyWord::Cmp, [AstNode::Token(Number(left)), AstNode::Token(Number(right))])
+            if left.is_float() || right.is_float() =>
+        {
+            Instruction::CmpVal(Value::F32(left.as_f32()), Value::F32(right.as_f32()))
+        }
+        // a negative operand makes the whole compare signed
+        (KeyWord::Cmp, [AstNode::Token(Number(left)), AstNode::Token(Number(right))])
+            if left.is_signed() || right.is_signed() =>
+        {
+            Instruction::CmpVal(Value::I32(left.as_i32()), Value::I32(right.as_i32()))
+        }
+        // the opcode gives both operands one width, so the narrower one is widened to match
+        (KeyWord::Cmp, [AstNode::Token(Number(left)), AstNode::Token(Number(right))]) => {
+            let (left, right) = (Value::from(left.clone()), Value::from(right.clone()));
+            let width = left.width().max(right.width());
+            Instruction::CmpVal(widen(left, width), widen(right, width))
+        }
+        (KeyWord::Cmp, [AstNode::Token(Reg(reg)), AstNode::Token(Number(num))])
+            if num.is_float() =>
+        {
+            Instruction::CmpRegNum(*reg, Value::F32(num.as_f32()))
+        }
+        (KeyWord::Cmp, [AstNode::Token(Reg(reg)), AstNode::Token(Number(num))])
+            if num.is_signed() =>
+        {
+            Instruction::CmpRegNum(*reg, Value::I32(num.as_i32()))
+        }
+        (KeyWord::Cmp, [AstNode::Token(Reg(reg)), AstNode::Token(Number(num))]) => {
+            Instruction::CmpRegNum(*reg, Value::from(num.clone()))
+        }
+        // a lone register is compared against zero
+        (KeyWord::Cmp, [AstNode::Token(Reg(reg))]) => Instruction::CmpRegNum(*reg, Value::U8(0)),
+
+        (KeyWord::Inc, [AstNode::Token(Reg(reg))]) => Instruction::IncReg(*reg),
+        (KeyWord::Inc, [Label(addr)]) => Instruction::IncMem(*addr),
+
+        (KeyWord::Store, [Label(addr), AstNode::Token(Reg(reg))]) => {
+            Instruction::StoreReg(*addr, *reg)
+        }
+        (KeyWord::Store, [Label(addr), AstNode::Token(Number(num))]) => {
+            Instruction::StoreVal(*addr, Value::from(num.clone()))
+        }
+        (KeyWord::Store, [AstNode::Token(Reg(addr)), AstNode::Token(Reg(src))]) => {
+            Instruction::StoreIndirect(*addr, *src)
+        }
+        (
+            KeyWord::Store,
+            [AstNode::Token(IndexedAddress(base, index)), AstNode::Token(Reg(src))],
+        ) => Instruction::StoreRegIndexed(*base, *index, *src),
+
+        (KeyWord::MemCpy, [Label(dest), Label(src), AstNode::Token(Reg(len))]) => {
+            Instruction::MemCopy(*dest, *src, *len)
+        }
+        (
+            KeyWord::MemSet,
+            [Label(dest), AstNode::Token(Number(U8(byte))), AstNode::Token(Reg(len))],
+        ) => Instruction::MemSet(*dest, *byte, *len),
+
+        (KeyWord::Interrupt, [AstNode::Token(Number(num))]) => Instruction::Interrupt(num.as_u32()),
+        (KeyWord::Interrupt, [AstNode::Token(Reg(reg))]) => Instruction::InterruptReg(*reg),
+
+        _ => return Err(ParseError::InvalidOperands(keyword)),
+    };
+
+    Ok(Statement::Instruction(inst))
+}
+
+/// encodes every operand of a data directive as a little endian value `width` bytes wide
+fn data<'a>(
+    keyword: KeyWord,
+    width: usize,
+    operands: &[AstNode<'a>],
+) -> Result<Vec<u8>, ParseError<'a>> {
+    let mut bytes = Vec::with_capacity(operands.len() * width);
+
+    for operand in operands {
+        let num = match operand {
+            AstNode::Token(Token::Number(num)) => num,
+            // strings are only allowed where each value is a byte
+            AstNode::Token(Token::String(s)) if width == 1 => {
+                bytes.extend_from_slice(s.as_bytes());
+                continue;
+            }
+            _ => return Err(ParseError::InvalidOperands(keyword)),
+        };
+
+        // a negative value is stored as its two's complement, so it has to fit the signed range
+        let val = num.as_u32();
+        let fits = match num {
+            _ if width == 4 => true,
+            crate::tokens::Number::I32(val) => *val >= -(1 << (width * 8 - 1)),
+            _ => val >> (width * 8) == 0,
+        };
+        if !fits {
+            return Err(ParseError::InvalidOperands(keyword));
+        }
+
+        bytes.extend_from_slice(&val.to_le_bytes()[..width]);
+    }
+
+    Ok(bytes)
+}
+
+#[derive(Debug)]
+pub struct InstructionStream<'a> {
+    lines: std::str::Lines<'a>,
+    labels: HashMap<&'a str, Address>,
+    addr: u32,
+    pending: std::vec::IntoIter<Statement>,
+    failed: bool,
+    /// the line last read, counting from 1
+    line: usize,
+}
+
+impl<'a> InstructionStream<'a> {
+    fn line_to_nodes(&mut self, line: &'a str) -> Result<Vec<AstNode<'a>>, ParseError<'a>> {
+        let mut nodes = Vec::new();
+        let mut keyword = None;
+
+        let lexer = Lexer::lex_from(line, self.line)?.expand_repeats()?;
+        for token in lexer.tokens {
+            let node = match token {
+                Token::Comment(_) => continue,
+                Token::Identifier(s) => match KeyWord::from_str(s) {
+                    Ok(kw) => {
+                        keyword = Some(kw);
+                        AstNode::KeyWord(kw)
+                    }
+                    Err(_) if s.ends_with(':') => {
+                        self.labels
+                            .insert(&s[0..s.len() - 1], Address::from(self.addr));
+                        continue;
+                    }
+                    Err(_) => match self.labels.get(s) {
+                        Some(addr) => resolve_label(keyword, *addr),
+                        None => AstNode::Ident(s),
+                    },
+                },
+                _ => AstNode::from(token),
+            };
+
+            nodes.push(node);
+        }
+
+        Ok(nodes)
+    }
+}
+
+impl<'a> Iterator for InstructionStream<'a> {
+    type Item = Result<Instruction, ParseError<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            // data is not an instruction, it only moves the following labels
+            if let Some(statement) = self.pending.next() {
+                self.addr += statement.size() as u32;
+                match statement {
+                    Statement::Instruction(inst) => return Some(Ok(inst)),
+                    Statement::Data(_) => continue,
+                }
+            }
+
+            // nothing is yielded after the first error
+            if self.failed {
+                return None;
+            }
+
+            let line = self.lines.next()?;
+            self.line += 1;
+            let statements = self
+                .line_to_nodes(line)
+                .and_then(|nodes| tokens_to_statements(&nodes, &vec![self.line; nodes.len()]));
+
+            match statements {
+                Ok(statements) => self.pending = statements.into_iter(),
+                Err(e) => {
+                    self.failed = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -264,11 +956,18 @@ impl<'a> IntoIterator for Parser<'a> {
 
 #[cfg(test)]
 mod test {
+    use std::num::IntErrorKind;
+
     use tracing::level_filters::LevelFilter;
     use tracing_subscriber::util::SubscriberInitExt;
-    use vm_cpu::{memory::Address, registers::Register};
+    use vm_cpu::{
+        cpu::Cpu,
+        memory::{Address, CpuMemory, Memory},
+        opcodes::{Comparison, Instruction, OpCode, Value},
+        registers::Register,
+    };
 
-    use crate::{parser::KeyWord, tokens::Number, Token};
+    use crate::{parser::KeyWord, tokens::Number, ParseError, Token};
 
     use super::{AstNode, Parser};
 
@@ -442,15 +1141,890 @@ mod test {
 
         let ast = Parser::parse(src).unwrap().into_iter().collect::<Vec<_>>();
 
+        // labels take up no space, so consecutive labels share an address
         let expected = [
             AstNode::Label(vm_cpu::memory::Address::from(0)),
-            AstNode::Label(vm_cpu::memory::Address::from(4)),
-            AstNode::Label(vm_cpu::memory::Address::from(8)),
+            AstNode::Label(vm_cpu::memory::Address::from(0)),
+            AstNode::Label(vm_cpu::memory::Address::from(0)),
         ];
 
         assert_eq!(ast, expected);
     }
 
+    #[test]
+    fn call_label_instruction() {
+        let src = "call foo\nfoo:\nhalt";
+
+        let insts = Parser::parse(src).unwrap().instructions().unwrap();
+
+        assert_eq!(
+            insts,
+            [Instruction::Call(Address::from(5)), Instruction::Halt]
+        );
+    }
+
+    #[test]
+    fn push_label_instruction() {
+        // the label's address is pushed, not the memory at it
+        let src = "push foo\nfoo:\nhalt";
+
+        let parser = Parser::parse(src).unwrap();
+
+        assert_eq!(
+            parser.instructions().unwrap(),
+            [Instruction::PushVal(Value::U32(5)), Instruction::Halt]
+        );
+        assert_eq!(
+            Parser::stream("foo:\npush foo").collect::<Result<Vec<_>, _>>(),
+            Ok(vec![Instruction::PushVal(Value::U32(0))])
+        );
+    }
+
+    #[test]
+    fn jump_label_instruction() {
+        let src = "foo:\nadd r1, 10\njump foo";
+
+        let insts = Parser::parse(src).unwrap().instructions().unwrap();
+
+        assert_eq!(insts[1], Instruction::Jump(Address::from(0)));
+    }
+
+    #[test]
+    fn negative_immediates() {
+        let src = "mov r1, -1\nadd r1, -5";
+
+        let insts = Parser::parse(src).unwrap().instructions().unwrap();
+
+        assert_eq!(
+            insts,
+            [
+                Instruction::MovRegNum(Register::R1, Value::U32(0xFFFF_FFFF)),
+                Instruction::AddRegNum(Register::R1, Value::U32(0xFFFF_FFFB)),
+            ]
+        );
+    }
+
+    fn at(line: usize, error: ParseError) -> ParseError {
+        ParseError::AtLine {
+            line,
+            error: Box::new(error),
+        }
+    }
+
+    #[test]
+    fn error_lines() {
+        let err = Parser::parse("mov r1, 5\ninc r1\n\nadd r1\nhalt")
+            .unwrap()
+            .instructions()
+            .unwrap_err();
+        assert_eq!(err.line(), Some(4));
+        assert_eq!(err.to_string(), "line 4: missing operands for keyword Add");
+
+        let err = Parser::parse("; setup\nr1\nhalt")
+            .unwrap()
+            .instructions()
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 2: expected keyword, found Register(R1)"
+        );
+    }
+
+    #[test]
+    fn malformed_statements() {
+        let err = Parser::parse("mov r1").unwrap().instructions().unwrap_err();
+        assert_eq!(err, at(1, ParseError::UnexpectedEndOfInput(KeyWord::Mov)));
+
+        // a truncated last statement is not dropped
+        let err = Parser::parse("halt\nstore [4]")
+            .unwrap()
+            .bytes()
+            .unwrap_err();
+        assert_eq!(err, at(2, ParseError::UnexpectedEndOfInput(KeyWord::Store)));
+
+        let mut stream = Parser::stream("halt\naddpair r1, r2, r3");
+        assert_eq!(stream.next(), Some(Ok(Instruction::Halt)));
+        assert_eq!(
+            stream.next(),
+            Some(Err(at(
+                2,
+                ParseError::UnexpectedEndOfInput(KeyWord::AddPair)
+            )))
+        );
+
+        let err = Parser::parse("halt\nmov 5, 5")
+            .unwrap()
+            .instructions()
+            .unwrap_err();
+        assert_eq!(err, at(2, ParseError::InvalidOperands(KeyWord::Mov)));
+
+        let err = Parser::parse("halt\nmov r1, [abc]").unwrap_err();
+        assert_eq!(
+            err,
+            at(2, ParseError::InvalidNumber(IntErrorKind::InvalidDigit))
+        );
+
+        let mut stream = Parser::stream("halt\nmov r1, 5]");
+        assert_eq!(stream.next(), Some(Ok(Instruction::Halt)));
+        assert_eq!(
+            stream.next(),
+            Some(Err(at(
+                2,
+                ParseError::InvalidNumber(IntErrorKind::InvalidDigit)
+            )))
+        );
+    }
+
+    #[test]
+    fn call_unresolved_label() {
+        let src = "call foo";
+
+        let err = Parser::parse(src).unwrap().instructions().unwrap_err();
+
+        assert_eq!(err, at(1, ParseError::UnresolvedLabel("foo".to_string())));
+    }
+
+    /// decodes `bytes` one instruction at a time
+    fn disassemble(bytes: &[u8]) -> Vec<Instruction> {
+        let mut insts = Vec::new();
+        let mut offset = 0;
+
+        while offset < bytes.len() {
+            let (inst, len) = Instruction::decode(&bytes[offset..]).unwrap();
+            offset += len;
+            insts.push(inst);
+        }
+
+        insts
+    }
+
+    /// assembles `src`, decodes the bytes back and checks they match, then prints the decoded
+    /// program and checks it assembles to the same instructions
+    fn round_trip(src: &str) {
+        let insts = Parser::parse(src).unwrap().instructions().unwrap();
+        let bytes = insts
+            .iter()
+            .flat_map(Instruction::encode)
+            .collect::<Vec<_>>();
+
+        let decoded = disassemble(&bytes);
+        assert_eq!(decoded, insts, "{src}");
+
+        let text = decoded
+            .iter()
+            .map(Instruction::to_string)
+            .collect::<Vec<_>>()
+            .join("\n");
+        let reassembled = Parser::parse(&text).unwrap().instructions().unwrap();
+        assert_eq!(reassembled, insts, "{text}");
+    }
+
+    #[test]
+    fn round_trip_programs() {
+        let programs = [
+            "mov r1, r2\nmov r1, 10\nmov r1, 300\nmov r1, 70000\nmov r1, -1",
+            "movl r1, 5\nmovl r1, 65535\nmovh r2, 300\nsetb r3, 255",
+            "mov r1, [8]\nmov [8], r1\nmov [8], 5\nadd r1, [8]\nadd [8], r1",
+            "add r1, r2\nadd r1, 5\nsub r1, r2\nmul r1, r2\ndiv r1, r2",
+            "addpair r1, r2, r3, r4",
+            "addf r1, r2\nsubf r1, r2\nmulf r1, r2\ndivf r1, r2",
+            "and r1, r2\nor r1, 300\nxor r1, r1\nnot r1\nshl r1, r2\nshr r1, 3",
+            "push r1\npush 70000\npush [4]\npop r2\nstackdepth r3\npushall\npopall",
+            "cmp r1, r2\ncmp 1, 2\ncmp 300, 400\ncmp 5, 300\ncmp 70000, 1\ncmp -1, 1\ncmp r1, 10\ncmp r1, -1\ncmp r1, 1.5\ncmp -1.5, 2.0\ninc r1\ninc [4]",
+            "start:\njump start\njumpeq start\njumpne start\njumpreg r1\ncall start\nret",
+            "start:\njumpge start\njumpgte start\njumplt start\njumplte start\nhalt",
+            "start:\ncall start, 1\ncall start, 255\nret",
+            "load r1, [4]\nload r1, r2\nstore [4], r1\nstore [4], 9\nstore r2, r1\nstore [4 + r2], r1",
+            "memcpy [8], [4], r1\nmemcpy [0], [100], r3",
+            "memset [8], 0, r1\nmemset [8], 255, r2",
+            "interrupt 3\ninterrupt r1\niret\nnop\nhalt",
+        ];
+
+        for src in programs {
+            round_trip(src);
+        }
+    }
+
+    #[test]
+    fn store_cmp_inc_interrupt() {
+        let cases = [
+            (
+                "store [8], r1",
+                Instruction::StoreReg(Address::from(8), Register::R1),
+            ),
+            (
+                "store [8], 300",
+                Instruction::StoreVal(Address::from(8), Value::U16(300)),
+            ),
+            (
+                "cmp r1, r2",
+                Instruction::CmpReg(Register::R1, Register::R2),
+            ),
+            ("cmp 1, 2", Instruction::CmpVal(Value::U8(1), Value::U8(2))),
+            ("inc r3", Instruction::IncReg(Register::R3)),
+            ("interrupt 2", Instruction::Interrupt(2)),
+        ];
+
+        for (src, expected) in cases {
+            let insts = Parser::parse(src).unwrap().instructions().unwrap();
+            assert_eq!(insts, vec![expected], "{src}");
+        }
+    }
+
+    #[test]
+    fn forward_jump_away_from_zero() {
+        let src = "mov r1, 1\njump end\nmov r1, 2\nend:\ninc r1\nhalt";
+        let bytes = Parser::parse(src).unwrap().bytes().unwrap();
+
+        let program_start = 200;
+        let mut cpu = Cpu::new(
+            CpuMemory::default(),
+            program_start,
+            u16::MAX as u32,
+            0.into(),
+        );
+        cpu.load_program(&bytes).unwrap();
+
+        // mov, then the jump lands on the inc past the skipped mov
+        let _ = cpu.step().unwrap();
+        let _ = cpu.step().unwrap();
+        let end = Instruction::MovRegNum(Register::R1, Value::U8(1)).size()
+            + Instruction::Jump(Address::from(0)).size()
+            + Instruction::MovRegNum(Register::R1, Value::U8(2)).size();
+        assert_eq!(cpu.registers()[Register::IP], program_start + end as u32);
+
+        cpu.execute();
+        assert_eq!(cpu.registers()[Register::R1], 2);
+    }
+
+    #[test]
+    fn mixed_width_cmp_runs() {
+        let bytes = Parser::parse("cmp 5, 300\ncmp 300, 5\nhalt")
+            .unwrap()
+            .bytes()
+            .unwrap();
+        let mut cpu = Cpu::new(CpuMemory::default(), 0, u16::MAX as u32, 0.into());
+        cpu.load_program(&bytes).unwrap();
+
+        let _ = cpu.step().unwrap();
+        assert_eq!(cpu.flags().comparison(), Comparison::Lt);
+        let _ = cpu.step().unwrap();
+        assert_eq!(cpu.flags().comparison(), Comparison::Gt);
+        assert!(cpu.step().unwrap().is_break());
+    }
+
+    #[test]
+    fn float_cmp_runs() {
+        let src = "mov r1, -1.5\ncmp r1, 1.5\ncmp -1.5, 2.0\ncmp 3, 2.5\nhalt";
+        let parser = Parser::parse(src).unwrap();
+        assert_eq!(
+            parser.instructions().unwrap()[1],
+            Instruction::CmpRegNum(Register::R1, Value::F32(1.5))
+        );
+
+        let mut cpu = Cpu::new(CpuMemory::default(), 0, u16::MAX as u32, 0.into());
+        cpu.load_program(&parser.bytes().unwrap()).unwrap();
+
+        let _ = cpu.step().unwrap();
+        assert_eq!(cpu.registers().get(Register::R1), (-1.5_f32).to_bits());
+        let _ = cpu.step().unwrap();
+        assert_eq!(cpu.flags().comparison(), Comparison::Lt);
+        let _ = cpu.step().unwrap();
+        assert_eq!(cpu.flags().comparison(), Comparison::Lt);
+        let _ = cpu.step().unwrap();
+        assert_eq!(cpu.flags().comparison(), Comparison::Gt);
+        assert!(cpu.step().unwrap().is_break());
+    }
+
+    /// assembles a jump over `statement` to a label right after it and runs it, the label has
+    /// to point at the halt that follows
+    fn jump_over(statement: &str) {
+        let src = format!("jump end\n{statement}\nend:\nhalt");
+        let parser = Parser::parse(&src).unwrap();
+        let bytes = parser.bytes().unwrap();
+        assert_eq!(
+            parser.symbols()["end"],
+            Address::from(bytes.len() - 1),
+            "{src}"
+        );
+
+        let mut cpu = Cpu::new(CpuMemory::default(), 0, u16::MAX as u32, 0.into());
+        cpu.load_program(&bytes).unwrap();
+        assert!(cpu.step().is_ok());
+        assert!(cpu.step().unwrap().is_break(), "{src}");
+    }
+
+    #[test]
+    fn label_after_signed_cmp() {
+        jump_over("cmp -1, 1");
+        jump_over("cmp r1, -1");
+    }
+
+    #[test]
+    fn label_after_lone_register_cmp() {
+        jump_over("cmp r1");
+    }
+
+    #[test]
+    fn label_after_movl() {
+        jump_over("movl r1, 5");
+        jump_over("movh r1, 5");
+    }
+
+    #[test]
+    fn symbols() {
+        let parser = Parser::parse("start:\nmov r1, 5\nend:\njump missing\nhalt").unwrap();
+
+        assert_eq!(parser.symbols().len(), 2);
+        assert_eq!(parser.symbols().get("start"), Some(&Address::from(0)));
+        assert_eq!(parser.symbols().get("end"), Some(&Address::from(3)));
+        assert_eq!(parser.unresolved(), vec!["missing"]);
+    }
+
+    #[test]
+    fn store_round_trip() {
+        let src = "store [10], r1";
+
+        let parser = Parser::parse(src).unwrap();
+        let bytes = parser.bytes().unwrap();
+
+        let mut memory = CpuMemory::default();
+        memory.write_bytes(Address::from(0), &bytes).unwrap();
+        let cpu = Cpu::new(memory, 0, u16::MAX as u32, 0.into());
+
+        assert_eq!(
+            cpu.current_instruction().unwrap(),
+            Instruction::StoreReg(Address::from(10), Register::R1)
+        );
+    }
+
+    #[test]
+    fn constants() {
+        let src = "COUNT equ 10\nBIG equ 70000\nmov r1, COUNT\nmov r2, BIG\nend:\njump end";
+        let parser = Parser::parse(src).unwrap();
+
+        assert_eq!(parser.constant("COUNT"), Some(Value::U8(10)));
+        assert_eq!(
+            parser.instructions().unwrap(),
+            vec![
+                Instruction::MovRegNum(Register::R1, Value::U8(10)),
+                Instruction::MovRegNum(Register::R2, Value::U32(70000)),
+                // constants take up no space, so labels after them are unaffected
+                Instruction::Jump(Address::from(3 + 6)),
+            ]
+        );
+    }
+
+    #[test]
+    fn redefined_constant() {
+        assert_eq!(
+            Parser::parse("COUNT equ 10\nCOUNT equ 11").unwrap_err(),
+            at(2, ParseError::DuplicateConstant("COUNT".to_string()))
+        );
+        assert_eq!(
+            Parser::parse("COUNT equ r1").unwrap_err(),
+            at(1, ParseError::InvalidConstant("COUNT".to_string()))
+        );
+    }
+
+    #[test]
+    fn signed_cmp() {
+        let parser = Parser::parse("cmp -1, 1\ncmp 5, -2\nmov r1, -1").unwrap();
+
+        assert_eq!(
+            parser.instructions().unwrap(),
+            vec![
+                Instruction::CmpVal(Value::I32(-1), Value::I32(1)),
+                Instruction::CmpVal(Value::I32(5), Value::I32(-2)),
+                Instruction::MovRegNum(Register::R1, Value::U32(u32::MAX)),
+            ]
+        );
+    }
+
+    #[test]
+    fn floats() {
+        let insts = Parser::parse("mov r1, 1.5\nmov r2, 2.5\naddf r1, r2\ndd 4.0")
+            .unwrap()
+            .instructions()
+            .unwrap();
+
+        assert_eq!(
+            insts,
+            [
+                Instruction::MovRegNum(Register::R1, Value::U32(1.5_f32.to_bits())),
+                Instruction::MovRegNum(Register::R2, Value::U32(2.5_f32.to_bits())),
+                Instruction::AddF32RegReg(Register::R1, Register::R2),
+            ]
+        );
+
+        let bytes = Parser::parse("dd 4.0").unwrap().bytes().unwrap();
+        assert_eq!(bytes, 4.0_f32.to_bits().to_le_bytes());
+    }
+
+    #[test]
+    fn cmp_register_number() {
+        let parser = Parser::parse("cmp r1, 10\ncmp r2, -1\ncmp r3").unwrap();
+
+        assert_eq!(
+            parser.instructions().unwrap(),
+            vec![
+                Instruction::CmpRegNum(Register::R1, Value::U8(10)),
+                Instruction::CmpRegNum(Register::R2, Value::I32(-1)),
+                Instruction::CmpRegNum(Register::R3, Value::U8(0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn entry_point() {
+        let src = "jump main\nmsg:\n.string \"hello\"\n.start main\nmain:\nload r1, msg\nhalt";
+        let parser = Parser::parse(src).unwrap();
+        assert_eq!(parser.entry_point(), Address::from(10));
+        assert_eq!(
+            parser.instructions().unwrap(),
+            [
+                Instruction::Jump(Address::from(10)),
+                Instruction::Load(Register::R1, Address::from(5)),
+                Instruction::Halt,
+            ]
+        );
+
+        assert_eq!(
+            Parser::parse("halt").unwrap().entry_point(),
+            Address::from(0)
+        );
+
+        let err = Parser::parse("halt\n.start nowhere").unwrap_err();
+        assert_eq!(err, at(2, ParseError::InvalidStart));
+        let err = Parser::parse("halt\n.start").unwrap_err();
+        assert_eq!(err, at(2, ParseError::InvalidStart));
+    }
+
+    #[test]
+    fn mov_halves() {
+        let insts = Parser::parse("movl r1, 0xEEEE\nmovh r1, 0xAAAA")
+            .unwrap()
+            .instructions()
+            .unwrap();
+        assert_eq!(
+            insts,
+            [
+                Instruction::MovRegLowU16(Register::R1, 0xEEEE),
+                Instruction::MovRegHighU16(Register::R1, 0xAAAA),
+            ]
+        );
+
+        let err = Parser::parse("halt\nmovh r1, 70000")
+            .unwrap()
+            .instructions()
+            .unwrap_err();
+        assert_eq!(err, at(2, ParseError::InvalidOperands(KeyWord::MovH)));
+    }
+
+    #[test]
+    fn set_byte() {
+        let insts = Parser::parse("setb r1, 0xFF")
+            .unwrap()
+            .instructions()
+            .unwrap();
+        assert_eq!(insts, [Instruction::SetByte(Register::R1, 0xff)]);
+
+        let err = Parser::parse("halt\nsetb r1, 256")
+            .unwrap()
+            .instructions()
+            .unwrap_err();
+        assert_eq!(err, at(2, ParseError::InvalidOperands(KeyWord::SetB)));
+    }
+
+    #[test]
+    fn char_operand() {
+        let insts = Parser::parse("mov r1, 'A'\npush '\\n'")
+            .unwrap()
+            .instructions()
+            .unwrap();
+        assert_eq!(
+            insts,
+            [
+                Instruction::MovRegNum(Register::R1, Value::U8(65)),
+                Instruction::PushVal(Value::U8(10)),
+            ]
+        );
+    }
+
+    #[test]
+    fn mem_copy() {
+        let insts = Parser::parse("memcpy [200], [100], r1")
+            .unwrap()
+            .instructions()
+            .unwrap();
+        assert_eq!(
+            insts,
+            [Instruction::MemCopy(
+                Address::from(200),
+                Address::from(100),
+                Register::R1
+            )]
+        );
+    }
+
+    #[test]
+    fn mem_set() {
+        let insts = Parser::parse("memset [200], 0, r1")
+            .unwrap()
+            .instructions()
+            .unwrap();
+        assert_eq!(
+            insts,
+            [Instruction::MemSet(Address::from(200), 0, Register::R1)]
+        );
+
+        let err = Parser::parse("memset [200], 256, r1")
+            .unwrap()
+            .instructions()
+            .unwrap_err();
+        assert_eq!(err, at(1, ParseError::InvalidOperands(KeyWord::MemSet)));
+    }
+
+    #[test]
+    fn mov_widths() {
+        let parser =
+            Parser::parse("mov r1, 255\nmov r1, 256\nmov r1, 65535\nmov r1, 70000").unwrap();
+        let insts = parser.instructions().unwrap();
+        assert_eq!(
+            insts,
+            [
+                Instruction::MovRegNum(Register::R1, Value::U8(255)),
+                Instruction::MovRegNum(Register::R1, Value::U16(256)),
+                Instruction::MovRegNum(Register::R1, Value::U16(65535)),
+                Instruction::MovRegNum(Register::R1, Value::U32(70000)),
+            ]
+        );
+
+        let ops = insts.iter().map(OpCode::from).collect::<Vec<_>>();
+        assert_eq!(
+            ops,
+            [
+                OpCode::MovRegU8,
+                OpCode::MovRegU16,
+                OpCode::MovRegU16,
+                OpCode::MovRegU32
+            ]
+        );
+    }
+
+    #[test]
+    fn store_indexed() {
+        let src = "store [100 + r1], r2\nstore [4+r3], r1\nend:\njump end";
+        let insts = Parser::parse(src).unwrap().instructions().unwrap();
+        assert_eq!(
+            insts,
+            [
+                Instruction::StoreRegIndexed(Address::from(100), Register::R1, Register::R2),
+                Instruction::StoreRegIndexed(Address::from(4), Register::R3, Register::R1),
+                Instruction::Jump(Address::from(14)),
+            ]
+        );
+
+        let err = Parser::parse("halt\nstore [100 + r9], r2").unwrap_err();
+        assert_eq!(err, at(2, ParseError::InvalidRegister("r9".to_string())));
+
+        let err = Parser::parse("halt\nmov r1, [100 + r1]")
+            .unwrap()
+            .instructions()
+            .unwrap_err();
+        assert_eq!(err, at(2, ParseError::InvalidOperands(KeyWord::Mov)));
+    }
+
+    #[test]
+    fn nop_padding() {
+        let parser = Parser::parse(".repeat 2 nop\nhalt").unwrap();
+        assert_eq!(
+            parser.bytes().unwrap(),
+            [OpCode::Nop as u8, OpCode::Nop as u8, OpCode::Halt as u8]
+        );
+    }
+
+    #[test]
+    fn repeat() {
+        let insts = Parser::parse(".repeat 3 inc r1\nhalt")
+            .unwrap()
+            .instructions()
+            .unwrap();
+        assert_eq!(
+            insts,
+            [
+                Instruction::IncReg(Register::R1),
+                Instruction::IncReg(Register::R1),
+                Instruction::IncReg(Register::R1),
+                Instruction::Halt,
+            ]
+        );
+
+        // labels after the block account for every copy
+        let insts = Parser::parse(".repeat 2 add r1, r2 ; twice\nend:\njump end")
+            .unwrap()
+            .instructions()
+            .unwrap();
+        assert_eq!(
+            insts,
+            [
+                Instruction::AddRegReg(Register::R1, Register::R2),
+                Instruction::AddRegReg(Register::R1, Register::R2),
+                Instruction::Jump(Address::from(6)),
+            ]
+        );
+
+        let err = Parser::parse("halt\n.repeat inc r1").unwrap_err();
+        assert_eq!(err, at(2, ParseError::InvalidRepeat));
+
+        let err = Parser::parse(".repeat 4000000000 nop").unwrap_err();
+        assert_eq!(err, at(1, ParseError::RepeatTooLarge(4_000_000_000)));
+    }
+
+    #[test]
+    fn indirect_instructions() {
+        // a register in place of an address is used as a pointer
+        let src = "store r2, r1\nload r3, r2";
+
+        let insts = Parser::parse(src).unwrap().instructions().unwrap();
+
+        assert_eq!(
+            insts,
+            [
+                Instruction::StoreIndirect(Register::R2, Register::R1),
+                Instruction::LoadIndirect(Register::R3, Register::R2),
+            ]
+        );
+    }
+
+    #[test]
+    fn bitwise_instructions() {
+        let src = "xor r1, r1\nand r2, 255\nor r2, r3\nnot r1";
+
+        let insts = Parser::parse(src).unwrap().instructions().unwrap();
+
+        assert_eq!(
+            insts,
+            [
+                Instruction::XorRegReg(Register::R1, Register::R1),
+                Instruction::AndRegNum(Register::R2, Value::U8(255)),
+                Instruction::OrRegReg(Register::R2, Register::R3),
+                Instruction::NotReg(Register::R1),
+            ]
+        );
+    }
+
+    #[test]
+    fn shift_instructions() {
+        let src = "shl r1, 8\nshr r1, r2";
+
+        let insts = Parser::parse(src).unwrap().instructions().unwrap();
+
+        assert_eq!(
+            insts,
+            [
+                Instruction::ShlRegNum(Register::R1, 8),
+                Instruction::ShrRegReg(Register::R1, Register::R2),
+            ]
+        );
+    }
+
+    #[test]
+    fn data_directives() {
+        let src = "db 1, 2\ndw 256\ndd 65536\ndw 0x1234";
+
+        let bytes = Parser::parse(src).unwrap().bytes().unwrap();
+
+        assert_eq!(bytes, [1, 2, 0, 1, 0, 0, 1, 0, 0x34, 0x12]);
+    }
+
+    #[test]
+    fn negative_data() {
+        let src = "db -1, -128\ndw -2\ndd -1";
+
+        let bytes = Parser::parse(src).unwrap().bytes().unwrap();
+
+        assert_eq!(bytes, [0xff, 0x80, 0xfe, 0xff, 0xff, 0xff, 0xff, 0xff]);
+
+        for (src, keyword) in [("db -129", KeyWord::Db), ("dw -32769", KeyWord::Dw)] {
+            let err = Parser::parse(src).unwrap().bytes().unwrap_err();
+            assert_eq!(err, at(1, ParseError::InvalidOperands(keyword)));
+        }
+    }
+
+    #[test]
+    fn data_moves_labels() {
+        let src = "jump end\ndb 1, 2\ndw 256\ndd 65536\nend:\nhalt";
+
+        let insts = Parser::parse(src).unwrap().instructions().unwrap();
+
+        assert_eq!(
+            insts,
+            [
+                Instruction::Jump(Address::from(5 + 2 + 2 + 4)),
+                Instruction::Halt
+            ]
+        );
+    }
+
+    #[test]
+    fn directives() {
+        let src = ".byte 1, 2, 3\n.word 258\n.string \"hi\"";
+
+        let bytes = Parser::parse(src).unwrap().bytes().unwrap();
+
+        assert_eq!(bytes, [1, 2, 3, 2, 1, 0, 0, b'h', b'i']);
+    }
+
+    #[test]
+    fn string_label() {
+        let src = "jump start\nmsg:\n.string \"ab\"\nstart:\nload r1, msg\nhalt";
+
+        let insts = Parser::parse(src).unwrap().instructions().unwrap();
+
+        assert_eq!(
+            insts,
+            [
+                Instruction::Jump(Address::from(7)),
+                Instruction::Load(Register::R1, Address::from(5)),
+                Instruction::Halt
+            ]
+        );
+    }
+
+    #[test]
+    fn stats() {
+        let src = "mov r1, 40\nadd r1, 300\njump end\n.byte 1, 2\nend:\nhalt";
+
+        let parser = Parser::parse(src).unwrap();
+        let stats = parser.stats().unwrap();
+
+        let code = parser
+            .instructions()
+            .unwrap()
+            .iter()
+            .map(Instruction::size)
+            .sum::<usize>();
+
+        assert_eq!(stats.code_bytes, code);
+        assert_eq!(stats.data_bytes, 2);
+        assert_eq!(stats.total(), parser.bytes().unwrap().len());
+        assert_eq!(stats.stack_start, 0xffff);
+        assert_eq!(stats.stack_bytes(), 0xffff - stats.total());
+        assert_eq!(
+            stats.to_string().lines().last(),
+            Some("stack: 65520 bytes, 0xffff down to 0x000f")
+        );
+    }
+
+    #[test]
+    fn data_too_wide() {
+        let err = Parser::parse("db 256").unwrap().bytes().unwrap_err();
+
+        assert_eq!(err, at(1, ParseError::InvalidOperands(KeyWord::Db)));
+    }
+
+    #[test]
+    fn stack_depth_instruction() {
+        let insts = Parser::parse("stackdepth r4")
+            .unwrap()
+            .instructions()
+            .unwrap();
+
+        assert_eq!(insts, [Instruction::StackDepthReg(Register::R4)]);
+    }
+
+    #[test]
+    fn label_doc_comment() {
+        let src = "; entry point\nmain:\n; stop\n; right away\nhalt ; trailing";
+
+        let parser = Parser::parse_with_comments(src).unwrap();
+
+        assert_eq!(
+            parser.metadata().labels.get("main").map(String::as_str),
+            Some("entry point")
+        );
+        assert_eq!(
+            parser.metadata().statements.get(&0).map(String::as_str),
+            Some("stop\nright away")
+        );
+        assert_eq!(parser.instructions().unwrap(), [Instruction::Halt]);
+
+        let parser = Parser::parse(src).unwrap();
+
+        assert_eq!(parser.metadata(), &super::Metadata::default());
+        assert_eq!(parser.instructions().unwrap(), [Instruction::Halt]);
+    }
+
+    #[test]
+    fn listing() {
+        let src = "start:\n  mov r1, 300 ; load\n\n  add r1, r2\n  jump start";
+
+        let listing = Parser::parse(src).unwrap().listing().unwrap();
+        let lines = listing.lines().collect::<Vec<_>>();
+
+        let mov = OpCode::MovRegU16 as u8;
+        let add = OpCode::AddRegReg as u8;
+        let jump = OpCode::Jump as u8;
+        assert_eq!(
+            lines,
+            [
+                format!(
+                    "0x0000  {mov:02x} 03 2c 01{:15}     2  mov r1, 300 ; load",
+                    ""
+                ),
+                format!("0x0004  {add:02x} 03 04{:18}     4  add r1, r2", ""),
+                format!("0x0007  {jump:02x} 00 00 00 00{:12}     5  jump start", ""),
+            ]
+        );
+
+        // each offset moves on by the size of the instruction before it
+        let offsets = lines
+            .iter()
+            .map(|line| usize::from_str_radix(&line[2..6], 16).unwrap())
+            .collect::<Vec<_>>();
+        for (offset, op) in offsets
+            .windows(2)
+            .zip([OpCode::MovRegU16, OpCode::AddRegReg])
+        {
+            assert_eq!(offset[1] - offset[0], op.increment_amount() as usize);
+        }
+    }
+
+    #[test]
+    fn stream_lazily() {
+        let mut src = String::from("start:\n");
+        for _ in 0..98 {
+            src.push_str("inc r1\n");
+        }
+        src.push_str("jump start\nhalt");
+
+        let mut stream = Parser::stream(&src);
+
+        assert_eq!(stream.next(), Some(Ok(Instruction::IncReg(Register::R1))));
+
+        let rest = stream.collect::<Result<Vec<_>, _>>().unwrap();
+
+        assert_eq!(rest.len(), 99);
+        assert_eq!(rest[97], Instruction::Jump(Address::from(0)));
+        assert_eq!(rest[98], Instruction::Halt);
+    }
+
+    #[test]
+    fn stream_stops_at_first_error() {
+        let src = "inc r1\njump end\ninc r1\nend:\nhalt";
+
+        let stream = Parser::stream(src).collect::<Vec<_>>();
+
+        assert_eq!(
+            stream,
+            [
+                Ok(Instruction::IncReg(Register::R1)),
+                Err(at(2, ParseError::UnresolvedLabel("end".to_string())))
+            ]
+        );
+    }
+
     #[test]
     fn unresolved_label() {
         let src = "foo";
@@ -463,8 +2037,17 @@ mod test {
     }
 }
 
+macro_rules! keyword_name {
+    ($variant:ident) => {
+        stringify!($variant).to_lowercase()
+    };
+    ($variant:ident, $name:literal) => {
+        $name
+    };
+}
+
 macro_rules! keywords {
-    ($($variant:ident),* $(,)?) => {
+    ($($variant:ident $(= $name:literal)?),* $(,)?) => {
         #[derive(Debug, Clone, Copy, PartialEq, Eq)]
         #[repr(u8)]
         #[rustfmt::skip]
@@ -477,7 +2060,7 @@ macro_rules! keywords {
 
             fn from_str(value: &str) -> Result<Self, Self::Err> {
                 $(
-                    if value == stringify!($variant).to_lowercase() {
+                    if value == keyword_name!($variant $(, $name)?) {
                         return Ok(KeyWord::$variant);
                     }
                 )*
@@ -489,16 +2072,81 @@ macro_rules! keywords {
 
 keywords! {
     Mov,
+    MovL,
+    MovH,
+    SetB,
     Add,
+    AddPair,
+    Sub,
+    Mul,
+    Div,
+    AddF,
+    SubF,
+    MulF,
+    DivF,
+    And,
+    Or,
+    Xor,
+    Not,
+    Shl,
+    Shr,
+    Db,
+    Dw,
+    Dd,
+    Byte = ".byte",
+    Word = ".word",
+    Str = ".string",
     Load,
     Jump,
+    JumpEq,
+    JumpNe,
+    JumpGe,
+    JumpGte,
+    JumpLt,
+    JumpLte,
+    JumpReg,
     Push,
     Pop,
+    PushAll,
+    PopAll,
+    StackDepth,
     Call,
     Ret,
+    IRet,
+    Nop,
     Halt,
     Cmp,
     Inc,
     Store,
+    MemCpy,
+    MemSet,
     Interrupt,
 }
+
+impl KeyWord {
+    /// how many operands the shortest form of the keyword takes
+    fn min_operands(&self) -> usize {
+        use KeyWord::*;
+
+        match self {
+            Ret | IRet | Nop | Halt | PushAll | PopAll => 0,
+            Not | Inc | Push | Pop | StackDepth | Jump | JumpEq | JumpNe | JumpGe | JumpGte
+            | JumpLt | JumpLte | JumpReg | Call | Interrupt | Cmp => 1,
+            Db | Dw | Dd | Byte | Word | Str => 1,
+            MemCpy | MemSet => 3,
+            AddPair => 4,
+            Mov | MovL | MovH | SetB | Add | Sub | Mul | Div | AddF | SubF | MulF | DivF | And
+            | Or | Xor | Shl | Shr | Load | Store => 2,
+        }
+    }
+
+    /// the width of each value emitted by a data directive
+    fn data_width(&self) -> Option<usize> {
+        match self {
+            KeyWord::Db | KeyWord::Byte | KeyWord::Str => Some(1),
+            KeyWord::Dw => Some(2),
+            KeyWord::Dd | KeyWord::Word => Some(4),
+            _ => None,
+        }
+    }
+}
@@ -1,47 +1,131 @@
-use core::panic;
 use std::{num::IntErrorKind, str::FromStr};
 
-use crate::ParseError;
+use crate::{error::at_line, ParseError};
 use tracing::trace;
-use vm_cpu::{memory::Address, registers::Register};
+use vm_cpu::{
+    memory::{Address, MAX_PAGES, PAGE_SIZE},
+    opcodes::Value,
+    registers::Register,
+};
+
+/// the most copies `.repeat` makes, every statement is at least a byte so more than this can not
+/// be loaded
+pub(crate) const MAX_REPEAT: u32 = MAX_PAGES * PAGE_SIZE;
 
 #[derive(Debug, Default, Clone)]
 pub(crate) struct Lexer<'a> {
     pub(crate) tokens: Vec<Token<'a>>,
+    /// the line each token is on, counting from 1
+    pub(crate) lines: Vec<usize>,
 }
 
 impl<'a> Lexer<'a> {
-    pub fn lex(data: &'a str) -> Lexer<'a> {
+    /// splits `data` into tokens, failing on a malformed address
+    pub fn lex(data: &'a str) -> Result<Lexer<'a>, ParseError<'a>> {
+        Self::lex_from(data, 1)
+    }
+
+    /// lexes `data` as if its first line were `first_line`
+    pub(crate) fn lex_from(data: &'a str, first_line: usize) -> Result<Lexer<'a>, ParseError<'a>> {
         let mut tokenizer = Lexer::default();
         let mut start: Option<usize> = None;
-        for (i, c) in data.chars().enumerate() {
+        // a comment runs from a ';' to the end of the line
+        let mut comment: Option<usize> = None;
+        // the start of the quoted string being lexed, just past its opening quote
+        let mut string: Option<usize> = None;
+        // the start of the character literal being lexed, just past its opening quote
+        let mut character: Option<usize> = None;
+        // whether the last character of the literal was an unescaped `\`
+        let mut escaped = false;
+        // spaces inside brackets do not end the word, so `[100 + r1]` is lexed as one
+        let mut bracket = false;
+        // the line being lexed, counting from 1
+        let mut line = first_line;
+        for (i, c) in data.char_indices() {
+            // a token ended by this newline belongs to the line it ends
+            let current = line;
+            if c == '\n' {
+                line += 1;
+            }
+
+            if let Some(s) = comment {
+                if c == '\n' {
+                    tokenizer.push(Token::Comment(data[s..i].trim()), current);
+                    comment = None;
+                }
+                continue;
+            }
+
+            if let Some(s) = string {
+                if c == '"' {
+                    tokenizer.push(Token::String(&data[s..i]), current);
+                    string = None;
+                }
+                continue;
+            }
+
+            if let Some(s) = character {
+                match c {
+                    '\n' => {
+                        return at_line(
+                            Err(ParseError::InvalidChar(data[s..i].to_string())),
+                            current,
+                        )
+                    }
+                    _ if escaped => escaped = false,
+                    '\\' => escaped = true,
+                    '\'' => {
+                        tokenizer.push(at_line(parse_char(&data[s..i]), current)?, current);
+                        character = None;
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+
             match c {
+                '"' if start.is_none() => string = Some(i + 1),
+                '\'' if start.is_none() => character = Some(i + 1),
+                '[' | ']' => {
+                    bracket = c == '[';
+                    start.get_or_insert(i);
+                }
+                ' ' if bracket => {}
+                ';' => {
+                    if let Some(s) = start.take() {
+                        tokenizer.push(at_line(lex_word(&data[s..i]), current)?, current);
+                    }
+                    comment = Some(i + 1);
+                }
                 '\n' => {
                     if start.is_some() {
-                        tokenizer
-                            .tokens
-                            .push(lex_word(&data[start.unwrap()..i]).unwrap());
+                        tokenizer.push(
+                            at_line(lex_word(&data[start.unwrap()..i]), current)?,
+                            current,
+                        );
                         start = None;
                     }
                 }
                 ',' => {
                     if start.is_some() {
-                        tokenizer
-                            .tokens
-                            .push(lex_word(&data[start.unwrap()..i]).unwrap());
+                        tokenizer.push(
+                            at_line(lex_word(&data[start.unwrap()..i]), current)?,
+                            current,
+                        );
                         start = None;
                     }
-                    tokenizer.tokens.push(Token::Comma);
+                    tokenizer.push(Token::Comma, current);
                 }
                 ' ' => {
                     if start.is_none() {
-                        tokenizer.tokens.push(Token::Space);
+                        tokenizer.push(Token::Space, current);
                     } else {
-                        tokenizer
-                            .tokens
-                            .push(lex_word(&data[start.unwrap()..i]).unwrap());
+                        tokenizer.push(
+                            at_line(lex_word(&data[start.unwrap()..i]), current)?,
+                            current,
+                        );
                         start = None;
-                        tokenizer.tokens.push(Token::Space);
+                        tokenizer.push(Token::Space, current);
                     }
                 }
                 _ => {
@@ -52,10 +136,77 @@ impl<'a> Lexer<'a> {
             }
         }
         if let Some(start1) = start {
-            tokenizer.tokens.push(lex_word(&data[start1..]).unwrap());
+            tokenizer.push(at_line(lex_word(&data[start1..]), line)?, line);
+        }
+        if let Some(s) = comment {
+            tokenizer.push(Token::Comment(data[s..].trim()), line);
+        }
+        // reported on the line the string starts on, it may have run over many more
+        if let Some(s) = string {
+            let line = first_line + data[..s].matches('\n').count();
+            return at_line(Err(ParseError::UnterminatedString), line);
+        }
+        if let Some(s) = character {
+            return at_line(Err(ParseError::InvalidChar(data[s..].to_string())), line);
+        }
+
+        Ok(tokenizer)
+    }
+
+    /// expands `.repeat N statement` into `N` copies of the rest of its line, so labels and
+    /// constants in the copies are resolved like any other statement
+    pub(crate) fn expand_repeats(self) -> Result<Lexer<'a>, ParseError<'a>> {
+        let mut expanded = Lexer::default();
+        let mut idx = 0;
+
+        while idx < self.tokens.len() {
+            let (token, line) = (&self.tokens[idx], self.lines[idx]);
+            idx += 1;
+
+            if token != &Token::Identifier(".repeat") {
+                expanded.push(token.clone(), line);
+                continue;
+            }
+
+            let end = self.lines[idx..]
+                .iter()
+                .position(|other| *other != line)
+                .map_or(self.tokens.len(), |len| idx + len);
+            let mut words =
+                (idx..end).filter(|i| !matches!(self.tokens[*i], Token::Space | Token::Comment(_)));
+
+            // the count is the first word, everything after it is the statement
+            let (count, body) = match (words.next(), words.next()) {
+                (Some(i), Some(body)) => match &self.tokens[i] {
+                    Token::Number(num) if num.is_signed() => {
+                        return at_line(Err(ParseError::InvalidRepeat), line)
+                    }
+                    Token::Number(num) if num.as_u32() > MAX_REPEAT => {
+                        return at_line(Err(ParseError::RepeatTooLarge(num.as_u32())), line)
+                    }
+                    Token::Number(num) => (num.as_u32(), body),
+                    _ => return at_line(Err(ParseError::InvalidRepeat), line),
+                },
+                _ => return at_line(Err(ParseError::InvalidRepeat), line),
+            };
+
+            for _ in 0..count {
+                for token in &self.tokens[body..end] {
+                    if !matches!(token, Token::Comment(_)) {
+                        expanded.push(token.clone(), line);
+                    }
+                }
+                expanded.push(Token::Space, line);
+            }
+            idx = end;
         }
 
-        tokenizer
+        Ok(expanded)
+    }
+
+    fn push(&mut self, token: Token<'a>, line: usize) {
+        self.tokens.push(token);
+        self.lines.push(line);
     }
 
     #[allow(unused)]
@@ -83,97 +234,152 @@ impl<'a> Iterator for LexerIterator<'a> {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, Clone)]
 pub enum Number {
     U8(u8),
     U16(u16),
     U32(u32),
+    /// any number written with a leading `-`
+    I32(i32),
+    /// any number written with a `.`
+    F32(f32),
+}
+
+/// floats compare by their bits so a number is always equal to itself, which lets the tokens
+/// keep their `Eq`
+impl PartialEq for Number {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Number::U8(l), Number::U8(r)) => l == r,
+            (Number::U16(l), Number::U16(r)) => l == r,
+            (Number::U32(l), Number::U32(r)) => l == r,
+            (Number::I32(l), Number::I32(r)) => l == r,
+            (Number::F32(l), Number::F32(r)) => l.to_bits() == r.to_bits(),
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Number {}
+
+impl From<Number> for Value {
+    /// registers are 32 bits wide, so outside of compares a negative number is its u32 two's
+    /// complement and arithmetic on it wraps correctly
+    fn from(value: Number) -> Self {
+        match value {
+            Number::U8(val) => Value::U8(val),
+            Number::U16(val) => Value::U16(val),
+            Number::U32(val) => Value::U32(val),
+            Number::I32(val) => Value::U32(val as u32),
+            Number::F32(val) => Value::U32(val.to_bits()),
+        }
+    }
 }
 
 impl<'a> Number {
+    pub(crate) fn as_u32(&self) -> u32 {
+        match self {
+            Number::U8(val) => *val as u32,
+            Number::U16(val) => *val as u32,
+            Number::U32(val) => *val,
+            Number::I32(val) => *val as u32,
+            Number::F32(val) => val.to_bits(),
+        }
+    }
+
+    pub(crate) fn is_signed(&self) -> bool {
+        matches!(self, Number::I32(_))
+    }
+
+    pub(crate) fn is_float(&self) -> bool {
+        matches!(self, Number::F32(_))
+    }
+
+    /// the number as a float, integers are converted rather than reinterpreted
+    pub(crate) fn as_f32(&self) -> f32 {
+        match self {
+            Number::F32(val) => *val,
+            Number::I32(val) => *val as f32,
+            num => num.as_u32() as f32,
+        }
+    }
+
+    /// the number as a signed value, large unsigned numbers wrap around to negative
+    pub(crate) fn as_i32(&self) -> i32 {
+        self.as_u32() as i32
+    }
+
     fn parse(s: &'a str) -> Result<Self, ParseError<'a>> {
         let s = s.trim();
 
-        match s.parse::<u8>() {
-            Ok(num) => Ok(Number::U8(num)),
-            Err(e) => match e.kind() {
-                IntErrorKind::Empty => panic!(),
-                IntErrorKind::InvalidDigit => Err(ParseError::InvalidNumber(e.kind().clone())),
-                IntErrorKind::PosOverflow => match s.parse::<u16>() {
-                    Ok(num) => Ok(Number::U16(num)),
-                    Err(e) => match e.kind() {
-                        IntErrorKind::Empty => panic!(),
-                        IntErrorKind::InvalidDigit => {
-                            Err(ParseError::InvalidNumber(e.kind().clone()))
-                        }
-
-                        IntErrorKind::PosOverflow => match s.parse::<u32>() {
-                            Ok(num) => Ok(Number::U32(num)),
-                            Err(e) => match e.kind() {
-                                IntErrorKind::Empty => panic!(),
-                                IntErrorKind::InvalidDigit => {
-                                    Err(ParseError::InvalidNumber(IntErrorKind::InvalidDigit))
-                                }
-                                IntErrorKind::PosOverflow => panic!(),
-                                IntErrorKind::NegOverflow => todo!(),
-                                IntErrorKind::Zero => Ok(Number::U32(0)),
-                                _ => todo!(),
-                            },
-                        },
-                        IntErrorKind::Zero => Ok(Number::U16(0)),
-                        _ => todo!(),
-                    },
-                },
-                IntErrorKind::Zero => Ok(Number::U8(0)),
-                _ => todo!(),
-            },
+        if s.contains('.') {
+            return s
+                .parse::<f32>()
+                .map(Number::F32)
+                .map_err(|_| ParseError::InvalidNumber(IntErrorKind::InvalidDigit));
+        }
+
+        if s.starts_with('-') {
+            return match s.parse::<i32>() {
+                Ok(num) => Ok(Number::I32(num)),
+                Err(e) => Err(ParseError::InvalidNumber(*e.kind())),
+            };
         }
+
+        let num = match s.strip_prefix("0x") {
+            Some(hex) => u32::from_str_radix(hex, 16),
+            None => s.parse::<u32>(),
+        }
+        .map_err(|e| ParseError::InvalidNumber(*e.kind()))?;
+
+        // numbers take the smallest width they fit in
+        Ok(match num {
+            0..=0xff => Number::U8(num as u8),
+            0x100..=0xffff => Number::U16(num as u16),
+            _ => Number::U32(num),
+        })
     }
 }
 
-// #[derive(Debug, PartialEq, Eq, Clone)]
-// pub struct Address(pub u32);
-//
-// impl From<u8> for Address {
-//     fn from(value: u8) -> Self {
-//         Self(value as u32)
-//     }
-// }
-//
-// impl From<u16> for Address {
-//     fn from(value: u16) -> Self {
-//         Self(value as u32)
-//     }
-// }
-//
-// impl From<u32> for Address {
-//     fn from(value: u32) -> Self {
-//         Self(value)
-//     }
-// }
-//
-// impl From<i32> for Address {
-//     fn from(value: i32) -> Self {
-//         Self(value as u32)
-//     }
-// }
+/// parses the inside of a character literal like `'A'` or `'\n'` into the byte it stands for
+fn parse_char(s: &str) -> Result<Token<'_>, ParseError<'_>> {
+    let mut chars = s.chars();
+    let byte = match (chars.next(), chars.next(), chars.next()) {
+        (Some('\\'), Some(escape), None) => match escape {
+            'n' => b'\n',
+            't' => b'\t',
+            'r' => b'\r',
+            '0' => b'\0',
+            '\\' => b'\\',
+            '\'' => b'\'',
+            '"' => b'"',
+            _ => return Err(ParseError::InvalidChar(s.to_string())),
+        },
+        (Some(c), None, None) if c.is_ascii() => c as u8,
+        _ => return Err(ParseError::InvalidChar(s.to_string())),
+    };
+
+    Ok(Token::Number(Number::U8(byte)))
+}
 
 fn parse_address(s: &str) -> Result<Address, ParseError<'_>> {
     let s = s.trim();
 
     let mut start = None;
 
-    for (i, ch) in s.chars().enumerate() {
+    for (i, ch) in s.char_indices() {
         match ch {
             '[' => {
                 start = Some(i + 1);
             }
             ']' => {
-                assert!(start.is_some());
-                trace!("{:?}", &s[start.unwrap()..i]);
-                return match s[start.unwrap()..i].parse::<u32>() {
+                let Some(start) = start else {
+                    break;
+                };
+                trace!("{:?}", &s[start..i]);
+                return match s[start..i].parse::<u32>() {
                     Ok(val) => Ok(Address::from(val)),
-                    Err(e) => return Err(ParseError::InvalidNumber(e.kind().clone())),
+                    Err(e) => return Err(ParseError::InvalidNumber(*e.kind())),
                 };
             }
             _ => {}
@@ -188,15 +394,40 @@ pub enum Token<'a> {
     Register(Register),
     Number(Number),
     Address(Address),
+    /// an address offset by the value of a register, written `[base + register]`
+    IndexedAddress(Address, Register),
     Identifier(&'a str),
     Comma,
     Space,
+    Comment(&'a str),
+    /// the contents of a quoted string, without the quotes
+    String(&'a str),
+}
+
+/// parses the `base + register` inside an indexed address like `[100 + r1]`
+fn parse_indexed(base: &str, index: &str) -> Result<Token<'static>, ParseError<'static>> {
+    let base = base
+        .trim()
+        .parse::<u32>()
+        .map_err(|e| ParseError::InvalidNumber(*e.kind()))?;
+    let index = Register::from_str(index.trim())
+        .map_err(|_| ParseError::InvalidRegister(index.trim().to_string()))?;
+
+    Ok(Token::IndexedAddress(Address::from(base), index))
 }
 
-fn lex_word(word: &str) -> Result<Token, ParseError> {
+fn lex_word(word: &str) -> Result<Token<'_>, ParseError<'_>> {
+    if let Some((base, index)) = word
+        .strip_prefix('[')
+        .and_then(|word| word.strip_suffix(']'))
+        .and_then(|word| word.split_once('+'))
+    {
+        return parse_indexed(base, index);
+    }
+
     let mut start = None;
 
-    for (i, c) in word.chars().enumerate() {
+    for (i, c) in word.char_indices() {
         match c {
             ',' => {
                 if let Some(s) = start {
@@ -207,15 +438,11 @@ fn lex_word(word: &str) -> Result<Token, ParseError> {
             ' ' => return Ok(Token::Space),
             '[' => start = Some(i),
             ']' => {
-                return Ok(Token::Address(
-                    parse_address(&word[start.unwrap()..i + 1]).unwrap(),
-                ))
-            }
-            'a'..='z' => {
-                if start.is_none() {
-                    start = Some(i);
-                }
+                return Ok(Token::Address(parse_address(
+                    &word[start.unwrap_or(0)..i + 1],
+                )?))
             }
+            'a'..='z' if start.is_none() => start = Some(i),
             _ => {}
         }
     }
@@ -240,11 +467,11 @@ fn lex_word(word: &str) -> Result<Token, ParseError> {
 mod lexer_test {
     use std::str::FromStr;
 
-    use vm_cpu::registers::Register;
+    use vm_cpu::{memory::Address, registers::Register};
 
     use crate::{
-        tokens::{Address, Lexer, Number},
-        Token,
+        tokens::{Lexer, Number},
+        ParseError, Token,
     };
 
     #[test]
@@ -255,7 +482,7 @@ mod lexer_test {
 
         for reg in regs {
             assert!(
-                Lexer::lex(reg).tokens.first().unwrap()
+                Lexer::lex(reg).unwrap().tokens.first().unwrap()
                     == &Token::Register(Register::from_str(reg).unwrap())
             );
         }
@@ -265,7 +492,7 @@ mod lexer_test {
     fn numbers() {
         let nums = "0 1 100000 val";
 
-        let lexer = Lexer::lex(nums);
+        let lexer = Lexer::lex(nums).unwrap();
         let ast = lexer.iter().collect::<Vec<_>>();
 
         let expected = [
@@ -281,11 +508,134 @@ mod lexer_test {
         assert_eq!(ast, expected)
     }
 
+    #[test]
+    fn negative_numbers() {
+        let nums = "-1 -5";
+
+        let lexer = Lexer::lex(nums).unwrap();
+        let ast = lexer.iter().collect::<Vec<_>>();
+
+        let expected = [
+            &Token::Number(Number::I32(-1)),
+            &Token::Space,
+            &Token::Number(Number::I32(-5)),
+        ];
+
+        assert_eq!(ast, expected)
+    }
+
+    #[test]
+    fn float_numbers() {
+        let tokens = Lexer::lex("2.75 -1.5 .string").unwrap().tokens;
+
+        assert_eq!(
+            tokens,
+            [
+                Token::Number(Number::F32(2.75)),
+                Token::Space,
+                Token::Number(Number::F32(-1.5)),
+                Token::Space,
+                Token::Identifier(".string"),
+            ]
+        );
+    }
+
+    #[test]
+    fn comment() {
+        let tokens = Lexer::lex("halt ; stop here\n; next").unwrap().tokens;
+
+        assert_eq!(
+            tokens,
+            [
+                Token::Identifier("halt"),
+                Token::Space,
+                Token::Comment("stop here"),
+                Token::Comment("next"),
+            ]
+        );
+    }
+
+    #[test]
+    fn string() {
+        let tokens = Lexer::lex(".string \"a b, c\"").unwrap().tokens;
+
+        assert_eq!(
+            tokens,
+            [
+                Token::Identifier(".string"),
+                Token::Space,
+                Token::String("a b, c"),
+            ]
+        );
+    }
+
+    #[test]
+    fn unterminated_string() {
+        assert_eq!(
+            Lexer::lex("halt\n.string \"abc\nhalt").unwrap_err(),
+            ParseError::AtLine {
+                line: 2,
+                error: Box::new(ParseError::UnterminatedString),
+            }
+        );
+    }
+
+    #[test]
+    fn char_literals() {
+        let tokens = Lexer::lex(r"'A' '\n' '\0' '\\' '\'' ' '").unwrap().tokens;
+
+        assert_eq!(
+            tokens,
+            [
+                Token::Number(Number::U8(65)),
+                Token::Space,
+                Token::Number(Number::U8(10)),
+                Token::Space,
+                Token::Number(Number::U8(0)),
+                Token::Space,
+                Token::Number(Number::U8(b'\\')),
+                Token::Space,
+                Token::Number(Number::U8(b'\'')),
+                Token::Space,
+                Token::Number(Number::U8(b' ')),
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_char_literals() {
+        for (src, inside) in [("'AB'", "AB"), ("''", ""), (r"'\q'", r"\q"), ("'A", "A")] {
+            assert_eq!(
+                Lexer::lex(src).unwrap_err(),
+                ParseError::AtLine {
+                    line: 1,
+                    error: Box::new(ParseError::InvalidChar(inside.to_string())),
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn hex_numbers() {
+        let tokens = Lexer::lex("0x12 0x1234 0x11223344").unwrap().tokens;
+
+        assert_eq!(
+            tokens,
+            [
+                Token::Number(Number::U8(0x12)),
+                Token::Space,
+                Token::Number(Number::U16(0x1234)),
+                Token::Space,
+                Token::Number(Number::U32(0x11223344)),
+            ]
+        );
+    }
+
     #[test]
     fn address() {
         let addrs = "[1] [2] 1";
 
-        let lexer = Lexer::lex(addrs);
+        let lexer = Lexer::lex(addrs).unwrap();
         let ast = lexer.iter().collect::<Vec<_>>();
 
         let expected = [
@@ -303,7 +653,7 @@ mod lexer_test {
     fn comma() {
         let comma = ",";
 
-        let lexer = Lexer::lex(comma);
+        let lexer = Lexer::lex(comma).unwrap();
         let ast = lexer.iter().collect::<Vec<_>>();
 
         assert!(*ast.first().unwrap() == &Token::Comma);
@@ -313,7 +663,7 @@ mod lexer_test {
     fn space() {
         let space = " ";
 
-        let lexer = Lexer::lex(space);
+        let lexer = Lexer::lex(space).unwrap();
         let ast = lexer.iter().collect::<Vec<_>>();
 
         assert!(*ast.first().unwrap() == &Token::Space);
@@ -323,7 +673,7 @@ mod lexer_test {
     fn ident() {
         let ident = "val foo foo:";
 
-        let lexer = Lexer::lex(ident);
+        let lexer = Lexer::lex(ident).unwrap();
         let ast = lexer.iter().collect::<Vec<_>>();
 
         let expected = [
@@ -0,0 +1,65 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use tracing::level_filters::LevelFilter;
+use vm_cpu::{
+    cpu::Cpu,
+    memory::{Address, CpuMemory},
+    opcodes::{Instruction, Value},
+    registers::Register,
+};
+
+const ITERATIONS: u32 = 10_000;
+
+/// counts r1 up to `ITERATIONS`, four instructions a lap
+fn tight_loop() -> Vec<u8> {
+    let program = [
+        Instruction::MovRegNum(Register::R1, Value::U8(0)),
+        Instruction::IncReg(Register::R1),
+        Instruction::CmpRegNum(Register::R1, Value::U32(ITERATIONS)),
+        Instruction::JumpNe(Address::from(3)),
+        Instruction::Halt,
+    ];
+
+    program.iter().flat_map(Instruction::encode).collect()
+}
+
+fn interpreter(c: &mut Criterion) {
+    let mut cpu = Cpu::new(CpuMemory::default(), 0, u16::MAX as u32, 0.into());
+    cpu.load_program(&tight_loop()).unwrap();
+
+    c.bench_function("tight loop", |b| {
+        b.iter(|| {
+            // the program stays in memory, only the registers and flags start over
+            cpu.reset(0, u16::MAX as u32, false);
+            cpu.execute();
+        })
+    });
+}
+
+/// the cost of a single cheap instruction under a subscriber that only takes info and above,
+/// like an application logging at its default level. the per step event is trace so it is
+/// filtered out, compare with `--features trace` to see what entering a span per step adds
+fn step(c: &mut Criterion) {
+    let program = [
+        Instruction::IncReg(Register::R1),
+        Instruction::Jump(Address::from(0)),
+    ];
+    let bytes = program
+        .iter()
+        .flat_map(Instruction::encode)
+        .collect::<Vec<_>>();
+
+    let mut cpu = Cpu::new(CpuMemory::default(), 0, u16::MAX as u32, 0.into());
+    cpu.load_program(&bytes).unwrap();
+
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(LevelFilter::INFO)
+        .with_writer(std::io::sink)
+        .finish();
+
+    tracing::subscriber::with_default(subscriber, || {
+        c.bench_function("step", |b| b.iter(|| cpu.step().unwrap()));
+    });
+}
+
+criterion_group!(benches, interpreter, step);
+criterion_main!(benches);
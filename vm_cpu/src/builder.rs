@@ -0,0 +1,238 @@
+//! builds programs from rust without going through the assembler
+
+use alloc::vec::Vec;
+
+use crate::{
+    cpu::Cpu,
+    error::Error,
+    memory::{Address, Memory},
+    opcodes::{Instruction, Value},
+    registers::Register,
+};
+
+/// the right hand side of an instruction that takes either a register or a number
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Source {
+    Reg(Register),
+    Val(Value),
+}
+
+impl From<Register> for Source {
+    fn from(value: Register) -> Self {
+        Self::Reg(value)
+    }
+}
+
+impl From<Value> for Source {
+    fn from(value: Value) -> Self {
+        Self::Val(value)
+    }
+}
+
+impl From<i32> for Source {
+    /// picks the narrowest width the number fits in, like the assembler does for literals
+    fn from(value: i32) -> Self {
+        Self::Val(match value {
+            0..=0xff => Value::U8(value as u8),
+            0x100..=0xffff => Value::U16(value as u16),
+            0x10000.. => Value::U32(value as u32),
+            _ => Value::I32(value),
+        })
+    }
+}
+
+/// collects instructions one call at a time, e.g. `ProgramBuilder::new().mov(R1, 10).halt()`
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ProgramBuilder {
+    insts: Vec<Instruction>,
+}
+
+impl ProgramBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn inst(mut self, inst: Instruction) -> Self {
+        self.insts.push(inst);
+        self
+    }
+
+    /// the offset the next instruction will be at, for jumping back to it
+    pub fn offset(&self) -> Address {
+        Address::from(self.insts.iter().map(Instruction::size).sum::<usize>())
+    }
+
+    pub fn mov(self, dest: Register, src: impl Into<Source>) -> Self {
+        self.inst(match src.into() {
+            Source::Reg(src) => Instruction::MovRegReg(dest, src),
+            Source::Val(val) => Instruction::MovRegNum(dest, val),
+        })
+    }
+
+    pub fn add(self, dest: Register, src: impl Into<Source>) -> Self {
+        self.inst(match src.into() {
+            Source::Reg(src) => Instruction::AddRegReg(dest, src),
+            Source::Val(val) => Instruction::AddRegNum(dest, val),
+        })
+    }
+
+    pub fn sub(self, dest: Register, src: Register) -> Self {
+        self.inst(Instruction::SubRegReg(dest, src))
+    }
+
+    pub fn mul(self, dest: Register, src: Register) -> Self {
+        self.inst(Instruction::MulRegReg(dest, src))
+    }
+
+    pub fn div(self, dest: Register, src: Register) -> Self {
+        self.inst(Instruction::DivRegReg(dest, src))
+    }
+
+    pub fn inc(self, reg: Register) -> Self {
+        self.inst(Instruction::IncReg(reg))
+    }
+
+    pub fn cmp(self, left: Register, right: impl Into<Source>) -> Self {
+        self.inst(match right.into() {
+            Source::Reg(right) => Instruction::CmpReg(left, right),
+            Source::Val(val) => Instruction::CmpRegNum(left, val),
+        })
+    }
+
+    pub fn push(self, src: impl Into<Source>) -> Self {
+        self.inst(match src.into() {
+            Source::Reg(src) => Instruction::PushReg(src),
+            Source::Val(val) => Instruction::PushVal(val),
+        })
+    }
+
+    pub fn pop(self, dest: Register) -> Self {
+        self.inst(Instruction::PopReg(dest))
+    }
+
+    pub fn load(self, dest: Register, address: impl Into<Address>) -> Self {
+        self.inst(Instruction::Load(dest, address.into()))
+    }
+
+    pub fn store(self, address: impl Into<Address>, src: Register) -> Self {
+        self.inst(Instruction::StoreReg(address.into(), src))
+    }
+
+    pub fn jump(self, address: impl Into<Address>) -> Self {
+        self.inst(Instruction::Jump(address.into()))
+    }
+
+    pub fn jump_eq(self, address: impl Into<Address>) -> Self {
+        self.inst(Instruction::JumpEq(address.into()))
+    }
+
+    pub fn jump_ne(self, address: impl Into<Address>) -> Self {
+        self.inst(Instruction::JumpNe(address.into()))
+    }
+
+    pub fn call(self, address: impl Into<Address>) -> Self {
+        self.inst(Instruction::Call(address.into()))
+    }
+
+    pub fn ret(self) -> Self {
+        self.inst(Instruction::Ret)
+    }
+
+    pub fn nop(self) -> Self {
+        self.inst(Instruction::Nop)
+    }
+
+    pub fn halt(self) -> Self {
+        self.inst(Instruction::Halt)
+    }
+
+    pub fn instructions(&self) -> &[Instruction] {
+        &self.insts
+    }
+
+    /// the bytecode of every instruction, in order
+    pub fn assemble(&self) -> Vec<u8> {
+        self.insts.iter().flat_map(Instruction::encode).collect()
+    }
+
+    /// writes the program at the program start of `cpu`, see [`Cpu::load_program`]
+    pub fn load_into<M: Memory>(&self, cpu: &mut Cpu<M>) -> Result<(), Error> {
+        cpu.load_program(&self.assemble())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        cpu::Cpu,
+        memory::CpuMemory,
+        opcodes::{Instruction, OpCode, Value},
+        registers::Register::{R1, R2, R3},
+    };
+
+    use super::{ProgramBuilder, Source};
+
+    #[test]
+    fn matches_hand_assembled() {
+        let program = ProgramBuilder::new()
+            .mov(R1, 10)
+            .mov(R2, R1)
+            .add(R1, R2)
+            .halt();
+
+        let bytes = [
+            OpCode::MovRegU8 as u8,
+            R1 as u8,
+            10,
+            OpCode::MovRegReg as u8,
+            R2 as u8,
+            R1 as u8,
+            OpCode::AddRegReg as u8,
+            R1 as u8,
+            R2 as u8,
+            OpCode::Halt as u8,
+        ];
+        assert_eq!(program.assemble(), bytes);
+
+        let mut cpu = Cpu::new(CpuMemory::default(), 0, u16::MAX as u32, 0.into());
+        program.load_into(&mut cpu).unwrap();
+        cpu.execute();
+
+        assert_eq!(cpu.registers()[R1], 20);
+        assert_eq!(cpu.registers()[R2], 10);
+    }
+
+    #[test]
+    fn counts_down() {
+        let program = ProgramBuilder::new().mov(R1, 5).mov(R2, 0);
+        let top = program.offset();
+        let program = program
+            .inc(R2)
+            .mov(R3, 1)
+            .sub(R1, R3)
+            .cmp(R1, 0)
+            .jump_ne(top)
+            .halt();
+
+        let mut cpu = Cpu::new(CpuMemory::default(), 0, u16::MAX as u32, 0.into());
+        program.load_into(&mut cpu).unwrap();
+        cpu.execute();
+
+        assert_eq!(cpu.registers()[R1], 0);
+        assert_eq!(cpu.registers()[R2], 5);
+    }
+
+    #[test]
+    fn literal_widths() {
+        assert_eq!(Source::from(255), Source::Val(Value::U8(255)));
+        assert_eq!(Source::from(256), Source::Val(Value::U16(256)));
+        assert_eq!(Source::from(70000), Source::Val(Value::U32(70000)));
+        assert_eq!(Source::from(-1), Source::Val(Value::I32(-1)));
+
+        let program = ProgramBuilder::new().push(-1);
+        assert_eq!(
+            program.instructions(),
+            [Instruction::PushVal(Value::I32(-1))]
+        );
+    }
+}
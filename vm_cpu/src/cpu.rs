@@ -1,65 +1,370 @@
-use std::{fmt::Debug, ops::ControlFlow};
-use tracing::{info, instrument, trace, warn};
+use alloc::{
+    boxed::Box,
+    collections::{BTreeMap, BTreeSet},
+    format,
+    string::String,
+    vec,
+    vec::Vec,
+};
+use core::{
+    cmp::Ordering,
+    fmt::{Debug, Write},
+    ops::{ControlFlow, Range},
+};
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
+#[cfg(feature = "trace")]
+use tracing::instrument;
+use tracing::trace;
 
 use crate::{
     error::Error,
-    memory::{self, Address, CpuMemory},
+    image::Image,
+    memory::{self, Address, CpuMemory, Memory},
+    mmio::MmioDevice,
     opcodes::{Comparison, Instruction, OpCode, Value},
-    registers::{Register, Registers},
+    registers::{Register, RegisterMask, Registers},
 };
 
+/// the condition bits, more than one can hold at once so jumps can test combinations of them
 #[derive(Debug)]
 pub struct Flags {
-    cmp: Comparison,
+    bits: u8,
 }
 
 impl Default for Flags {
     fn default() -> Self {
         Self {
-            cmp: Comparison::Eq,
+            bits: Self::EQ | Self::ZERO,
+        }
+    }
+}
+
+impl Flags {
+    pub const EQ: u8 = 1;
+    pub const NE: u8 = 1 << 1;
+    pub const LT: u8 = 1 << 2;
+    pub const GT: u8 = 1 << 3;
+    /// the difference of the last comparison was zero
+    pub const ZERO: u8 = 1 << 4;
+    /// set when the last multi word add carried out of its high word
+    pub const CARRY: u8 = 1 << 5;
+
+    /// whether every bit in `flags` is set
+    pub fn is_set(&self, flags: u8) -> bool {
+        self.bits & flags == flags
+    }
+
+    /// whether any bit in `flags` is set
+    pub fn any(&self, flags: u8) -> bool {
+        self.bits & flags != 0
+    }
+
+    pub fn bits(&self) -> u8 {
+        self.bits
+    }
+
+    /// the last comparison as a single [`Comparison`], for code written before the flags
+    /// were a bitset
+    pub fn comparison(&self) -> Comparison {
+        if self.is_set(Self::GT) {
+            Comparison::Gt
+        } else if self.is_set(Self::LT) {
+            Comparison::Lt
+        } else {
+            Comparison::Eq
+        }
+    }
+
+    /// replaces the comparison bits, leaving carry alone
+    fn set_ordering(&mut self, ordering: Ordering) {
+        self.bits = (self.bits & Self::CARRY)
+            | match ordering {
+                Ordering::Equal => Self::EQ | Self::ZERO,
+                Ordering::Greater => Self::NE | Self::GT,
+                Ordering::Less => Self::NE | Self::LT,
+            };
+    }
+
+    /// a float compare against NaN is neither equal, less nor greater, so only NE is set
+    fn set_unordered(&mut self) {
+        self.bits = (self.bits & Self::CARRY) | Self::NE;
+    }
+
+    fn set_carry(&mut self, carry: bool) {
+        if carry {
+            self.bits |= Self::CARRY;
+        } else {
+            self.bits &= !Self::CARRY;
+        }
+    }
+}
+
+/// what [`Cpu::run_benchmark`] measured, the halt counts as an instruction
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy)]
+pub struct BenchStats {
+    pub instructions: u64,
+    pub elapsed: Duration,
+}
+
+#[cfg(feature = "std")]
+impl BenchStats {
+    pub fn instructions_per_second(&self) -> f64 {
+        self.instructions as f64 / self.elapsed.as_secs_f64()
+    }
+}
+
+/// one instruction of a [`Cpu::run_traced`] run, the registers are the ones it left behind
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TraceEntry {
+    pub address: Address,
+    pub inst: Instruction,
+    pub registers: [u32; Register::len()],
+}
+
+impl core::fmt::Display for TraceEntry {
+    /// the address and instruction followed by every register, in [`Register`] order
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:#06x}  {}", self.address.0, self.inst)?;
+        for (idx, val) in self.registers.iter().enumerate() {
+            let register = Register::try_from(idx as u8).map_err(|_| core::fmt::Error)?;
+            write!(f, " {register}={val}")?;
         }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum StopReason {
+    Breakpoint(Address),
+    Halted,
+}
+
+/// how add, sub, inc and mul behave when the result does not fit in a register
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum ArithmeticMode {
+    #[default]
+    Wrapping,
+    Saturating,
+    /// overflowing is an [`Error::Overflow`]
+    Checked,
+}
+
+/// what happens to IP once an instruction has run
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Ip {
+    Advance,
+    /// the instruction set IP itself
+    Set,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ArithOp {
+    Add,
+    Sub,
+    Mul,
+}
+
+pub type InterruptHandler<M = CpuMemory> = Box<dyn FnMut(&mut Cpu<M>)>;
+
+/// interrupts handled by rust code instead of a handler in the program
+struct InterruptHandlers<M>(BTreeMap<u32, InterruptHandler<M>>);
+
+impl<M> Default for InterruptHandlers<M> {
+    fn default() -> Self {
+        Self(BTreeMap::new())
+    }
+}
+
+impl<M> Debug for InterruptHandlers<M> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_set().entries(self.0.keys()).finish()
+    }
+}
+
+pub type StepHook = Box<dyn FnMut(&Instruction, &Registers)>;
+
+/// called with every instruction right before it runs
+#[derive(Default)]
+struct StepHookSlot(Option<StepHook>);
+
+impl Debug for StepHookSlot {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(if self.0.is_some() { "Some(..)" } else { "None" })
     }
 }
 
+/// where the cli and [`Cpu::load_image`] put the top of the stack, it grows down towards the
+/// end of the program
+pub const DEFAULT_STACK_START: u32 = u16::MAX as u32;
+
+/// runs programs out of any [`Memory`], a sparse [`CpuMemory`] unless told otherwise
 #[derive(Default, Debug)]
-pub struct Cpu {
+pub struct Cpu<M = CpuMemory> {
     flags: Flags,
     registers: Registers,
     interrupt_table: Address,
-    in_interrupt: bool,
+    /// how many interrupts are being handled, each one has a frame saved on the stack
+    interrupt_depth: u32,
+    interrupt_handlers: InterruptHandlers<M>,
+    step_hook: StepHookSlot,
     program_start: Address,
-    memory: CpuMemory,
+    /// the end of the loaded program, the stack may not grow into it
+    program_end: Address,
+    stack_start: Address,
+    memory: M,
+    breakpoints: BTreeSet<Address>,
+    arithmetic_mode: ArithmeticMode,
+    /// the summed [`OpCode::cost`] of every instruction run so far
+    cycles: u64,
+    /// how many calls have not returned yet
+    call_depth: u32,
+    /// the deepest `call_depth` may go, unlimited when `None`
+    max_call_depth: Option<u32>,
+    /// whether instructions may write into the loaded program
+    code_writable: bool,
+    /// the address of every instruction run since coverage was turned on or the cpu was last
+    /// reset, `None` while it is off
+    coverage: Option<BTreeSet<Address>>,
 }
 
-impl Cpu {
-    pub fn new(
-        memory: CpuMemory,
-        program_start: u32,
-        stack_start: u32,
-        interrupt_table: Address,
-    ) -> Self {
+impl<M: Memory> Cpu<M> {
+    pub fn new(memory: M, program_start: u32, stack_start: u32, interrupt_table: Address) -> Self {
         Self {
             memory,
             registers: Registers::new(program_start, stack_start),
             interrupt_table,
-            in_interrupt: false,
+            interrupt_depth: 0,
+            interrupt_handlers: InterruptHandlers::default(),
+            step_hook: StepHookSlot::default(),
             program_start: program_start.into(),
+            program_end: program_start.into(),
+            stack_start: stack_start.into(),
             flags: Flags::default(),
+            breakpoints: BTreeSet::new(),
+            arithmetic_mode: ArithmeticMode::default(),
+            cycles: 0,
+            call_depth: 0,
+            max_call_depth: None,
+            code_writable: false,
+            coverage: None,
+        }
+    }
+
+    /// puts the cpu back in the state [`Cpu::new`] leaves it in, reusing its memory. the
+    /// contents of memory are only cleared with `reset_memory`. breakpoints, interrupt
+    /// handlers and devices stay registered
+    pub fn reset(&mut self, program_start: u32, stack_start: u32, reset_memory: bool) {
+        self.registers = Registers::new(program_start, stack_start);
+        self.flags = Flags::default();
+        self.interrupt_depth = 0;
+        self.cycles = 0;
+        self.call_depth = 0;
+        if let Some(coverage) = &mut self.coverage {
+            coverage.clear();
+        }
+        self.program_start = program_start.into();
+        self.program_end = program_start.into();
+        self.stack_start = stack_start.into();
+
+        if reset_memory {
+            self.memory.zero();
+        }
+    }
+
+    /// writes `program` at the program start, an empty program is rejected since running it
+    /// would execute whatever is already in memory. a program placed below the stack has to
+    /// end before the stack starts, otherwise pushes would overwrite it
+    pub fn load_program(&mut self, program: &[u8]) -> Result<(), Error> {
+        if program.is_empty() {
+            return Err(Error::EmptyProgram);
+        }
+
+        let start = self.program_start.0;
+        let end = u32::try_from(program.len())
+            .ok()
+            .and_then(|len| start.checked_add(len))
+            .ok_or(memory::Error::InvalidAddress(start))?;
+
+        if start <= self.stack_start.0 && end > self.stack_start.0 {
+            return Err(memory::Error::InvalidAddress(self.stack_start.0).into());
+        }
+        // checked up front so a program that does not fit leaves memory as it was
+        if end as usize > self.memory.capacity() {
+            return Err(memory::Error::InvalidAddress(self.memory.capacity() as u32).into());
         }
+
+        self.memory.write_bytes(self.program_start, program)?;
+        self.program_end = end.into();
+
+        Ok(())
+    }
+
+    /// the cycles spent since the cpu was created or last reset
+    pub fn cycles(&self) -> u64 {
+        self.cycles
     }
 
     pub fn registers(&self) -> &Registers {
         &self.registers
     }
 
-    pub fn memory(&self) -> &CpuMemory {
+    /// the address of every instruction run since [`Cpu::set_coverage`] turned recording on
+    /// or the cpu was last reset, `None` while it is off
+    pub fn coverage(&self) -> Option<&BTreeSet<Address>> {
+        self.coverage.as_ref()
+    }
+
+    /// the `offsets` of instructions, relative to the program start like the assembler's
+    /// labels, that have not been run. `None` while coverage is off
+    pub fn unreached(&self, offsets: impl IntoIterator<Item = Address>) -> Option<Vec<Address>> {
+        let coverage = self.coverage.as_ref()?;
+
+        Some(
+            offsets
+                .into_iter()
+                .filter(|offset| {
+                    (self.program_start + *offset).map_or(true, |addr| !coverage.contains(&addr))
+                })
+                .collect(),
+        )
+    }
+
+    /// the flags set by the last comparison and multi word add
+    pub fn flags(&self) -> &Flags {
+        &self.flags
+    }
+
+    pub fn memory(&self) -> &M {
         &self.memory
     }
 
-    pub fn memory_mut(&mut self) -> &mut CpuMemory {
+    pub fn memory_mut(&mut self) -> &mut M {
         &mut self.memory
     }
 
+    pub fn get_register(&self, register: Register) -> u32 {
+        self.registers[register]
+    }
+
+    /// sets `register` from the host, e.g. to pass arguments in before running
+    pub fn set_register(&mut self, register: Register, val: u32) {
+        self.registers[register] = val;
+    }
+
+    /// reads the u32 at `address`, which is absolute rather than relative to the program
+    pub fn peek_u32(&self, address: Address) -> Result<u32, Error> {
+        Ok(self.memory.read_u32(address)?)
+    }
+
+    /// writes `val` to `address`, which is absolute rather than relative to the program
+    pub fn poke_u32(&mut self, address: Address, val: u32) -> Result<(), Error> {
+        Ok(self.memory.write_u32(address, val)?)
+    }
+
     pub fn execute(&mut self) {
         loop {
             match self.step() {
@@ -72,468 +377,643 @@ impl Cpu {
         }
     }
 
-    fn fetch_instruction(&mut self) -> Result<Instruction, Error> {
-        let ip = self.registers[Register::IP];
-        let byte = self.memory.read(ip)?;
-        let op = OpCode::try_from(byte)?;
-
-        let ip = ip + 1; // to skip the opcode and only deal with bytecodes
-        let bytecode = self
-            .memory
-            .get(ip.into()..(ip + op.increment_amount() as u32).into())?;
-
-        Ok(match op {
-            OpCode::MovRegMem => {
-                let reg = Register::try_from(bytecode[0])?;
-                let addr = u32::from_le_bytes([bytecode[1], bytecode[2], bytecode[3], bytecode[4]]);
-
-                Instruction::MovRegMem(reg, addr.into())
-            }
-            OpCode::MovRegReg => {
-                let left = Register::try_from(bytecode[0])?;
-
-                let right = Register::try_from(bytecode[1])?;
+    /// runs until halt like [`Cpu::execute`], timing the run
+    #[cfg(feature = "std")]
+    pub fn run_benchmark(&mut self) -> Result<BenchStats, Error> {
+        let mut instructions = 0;
+        let start = Instant::now();
 
-                Instruction::MovRegReg(left, right)
+        loop {
+            instructions += 1;
+            if self.step()?.is_break() {
+                break;
             }
-            OpCode::MovRegU8 => {
-                let left = Register::try_from(bytecode[0])?;
-
-                let right = bytecode[1];
+        }
 
-                Instruction::MovRegNum(left, Value::U8(right))
-            }
-            OpCode::MovRegU16 => {
-                let left = Register::try_from(bytecode[0])?;
+        Ok(BenchStats {
+            instructions,
+            elapsed: start.elapsed(),
+        })
+    }
 
-                let right = u16::from_le_bytes([bytecode[1], bytecode[2]]);
+    /// runs until halt like [`Cpu::execute`], recording every instruction run along with the
+    /// registers after it. the halt is the last entry
+    pub fn run_traced(&mut self) -> Result<Vec<TraceEntry>, Error> {
+        let mut trace = Vec::new();
 
-                Instruction::MovRegNum(left, Value::U16(right))
+        loop {
+            let address = Address::from(self.registers[Register::IP]);
+            let inst = self.fetch_instruction()?;
+            let flow = self.step()?;
+
+            trace.push(TraceEntry {
+                address,
+                inst,
+                registers: self.registers_snapshot(),
+            });
+
+            if flow.is_break() {
+                return Ok(trace);
             }
-            OpCode::MovRegU32 => {
-                let left = Register::try_from(bytecode[0])?;
+        }
+    }
 
-                let right =
-                    u32::from_le_bytes([bytecode[1], bytecode[2], bytecode[3], bytecode[4]]);
+    /// decodes the instruction at IP without advancing it
+    pub fn current_instruction(&self) -> Result<Instruction, Error> {
+        self.fetch_instruction()
+    }
 
-                Instruction::MovRegNum(left, Value::U32(right))
-            }
+    pub fn registers_snapshot(&self) -> [u32; Register::len()] {
+        *self.registers.as_slice()
+    }
 
-            OpCode::MovMemReg => {
-                let addr = u32::from_le_bytes([bytecode[0], bytecode[1], bytecode[2], bytecode[3]]);
-                let reg = Register::try_from(bytecode[4])?;
+    pub fn read_range(&self, range: Range<Address>) -> Result<Vec<u8>, Error> {
+        Ok(self.memory.get(range)?)
+    }
 
-                Instruction::MovMemReg(addr.into(), reg)
+    /// renders `range` like `hexdump -C`, 16 bytes a line with the address of the first in front
+    /// and the printable ones in the gutter. bytes that can not be read show up as `??`
+    pub fn dump_memory(&self, range: Range<Address>) -> String {
+        let mut out = String::new();
+        let mut addr = range.start.0;
+
+        while addr < range.end.0 {
+            let len = (range.end.0 - addr).min(16);
+            let bytes = (addr..addr + len)
+                .map(|addr| self.memory.read(addr.into()).ok())
+                .collect::<Vec<_>>();
+
+            let _ = write!(out, "{addr:08x} ");
+            for (i, byte) in bytes.iter().enumerate() {
+                if i == 8 {
+                    out.push(' ');
+                }
+                match byte {
+                    Some(byte) => {
+                        let _ = write!(out, " {byte:02x}");
+                    }
+                    None => out.push_str(" ??"),
+                }
             }
-            OpCode::MovMemU8 => {
-                let addr = u32::from_le_bytes([bytecode[0], bytecode[1], bytecode[2], bytecode[3]]);
 
-                Instruction::MovMemNum(addr.into(), Value::U8(bytecode[4]))
+            // line the gutter up with full rows
+            for i in bytes.len()..16 {
+                if i == 8 {
+                    out.push(' ');
+                }
+                out.push_str("   ");
             }
-            OpCode::MovMemU16 => {
-                let addr = u32::from_le_bytes([bytecode[0], bytecode[1], bytecode[2], bytecode[3]]);
-                let val = u16::from_le_bytes([bytecode[4], bytecode[5]]);
 
-                Instruction::MovMemNum(addr.into(), Value::U16(val))
+            out.push_str("  |");
+            for byte in &bytes {
+                out.push(match byte {
+                    Some(byte) if byte.is_ascii_graphic() || *byte == b' ' => *byte as char,
+                    _ => '.',
+                });
             }
-            OpCode::MovMemU32 => {
-                let addr = u32::from_le_bytes([bytecode[0], bytecode[1], bytecode[2], bytecode[3]]);
-                let val = u32::from_le_bytes([bytecode[4], bytecode[5], bytecode[6], bytecode[7]]);
+            out.push_str("|\n");
 
-                Instruction::MovMemNum(addr.into(), Value::U32(val))
-            }
+            addr += len;
+        }
 
-            OpCode::AddRegReg => {
-                let left = Register::try_from(bytecode[0])?;
-                let right = Register::try_from(bytecode[1])?;
+        out
+    }
 
-                Instruction::AddRegReg(left, right)
-            }
-            OpCode::AddRegMem => {
-                let reg = Register::try_from(bytecode[0])?;
-                let addr = u32::from_le_bytes([bytecode[1], bytecode[2], bytecode[3], bytecode[4]]);
+    /// every register followed by the flags
+    pub fn dump_registers(&self) -> String {
+        let mut out = format!("{}", self.registers);
+        let _ = writeln!(out, "CMP {:?}", self.flags.comparison());
+        let _ = writeln!(out, "CARRY {}", self.flags.is_set(Flags::CARRY));
 
-                Instruction::AddRegMem(reg, addr.into())
-            }
-            OpCode::AddMemReg => {
-                let addr = u32::from_le_bytes([bytecode[0], bytecode[1], bytecode[2], bytecode[3]]);
-                let reg = Register::try_from(bytecode[4])?;
+        out
+    }
 
-                Instruction::AddMemReg(addr.into(), reg)
-            }
-            OpCode::AddRegU8 => {
-                let left = Register::try_from(bytecode[0])?;
+    /// runs `handler` for interrupt `idx` instead of jumping through the interrupt table. the
+    /// program carries on with the next instruction once it returns
+    pub fn register_interrupt(&mut self, idx: u32, handler: InterruptHandler<M>) {
+        self.interrupt_handlers.0.insert(idx, handler);
+    }
 
-                let right = bytecode[1];
+    /// calls `hook` with each instruction after it is decoded and before it runs, along with
+    /// the registers as they are at that point
+    pub fn set_step_hook(&mut self, hook: StepHook) {
+        self.step_hook.0 = Some(hook);
+    }
 
-                Instruction::AddRegNum(left, Value::U8(right))
-            }
-            OpCode::AddRegU16 => {
-                let left = Register::try_from(bytecode[0])?;
+    pub fn clear_step_hook(&mut self) {
+        self.step_hook.0 = None;
+    }
 
-                let right = u16::from_le_bytes([bytecode[1], bytecode[2]]);
+    pub fn set_arithmetic_mode(&mut self, mode: ArithmeticMode) {
+        self.arithmetic_mode = mode;
+    }
 
-                Instruction::AddRegNum(left, Value::U16(right))
-            }
-            OpCode::AddRegU32 => {
-                let left = Register::try_from(bytecode[0])?;
+    /// a call made while `max` calls have not returned is an [`Error::CallDepthExceeded`],
+    /// `None` lets calls nest until the stack runs out
+    pub fn set_max_call_depth(&mut self, max: Option<u32>) {
+        self.max_call_depth = max;
+    }
 
-                let right =
-                    u32::from_le_bytes([bytecode[1], bytecode[2], bytecode[3], bytecode[4]]);
+    /// lets instructions write into the loaded program, writes there are an
+    /// [`Error::WriteProtected`] otherwise
+    pub fn set_code_writable(&mut self, writable: bool) {
+        self.code_writable = writable;
+    }
 
-                Instruction::AddRegNum(left, Value::U32(right))
+    /// records the address of every instruction run from here on, see [`Cpu::coverage`]. off by
+    /// default since it costs a set insert per step
+    pub fn set_coverage(&mut self, enabled: bool) {
+        match enabled {
+            true => {
+                self.coverage.get_or_insert_with(BTreeSet::new);
             }
+            false => self.coverage = None,
+        }
+    }
 
-            OpCode::IncReg => {
-                let reg = Register::try_from(bytecode[0])?;
+    pub fn call_depth(&self) -> u32 {
+        self.call_depth
+    }
 
-                Instruction::IncReg(reg)
-            }
-            OpCode::IncMem => {
-                let addr = u32::from_le_bytes([bytecode[0], bytecode[1], bytecode[2], bytecode[3]]);
+    pub fn add_breakpoint(&mut self, addr: Address) {
+        self.breakpoints.insert(addr);
+    }
 
-                Instruction::IncMem(addr.into())
-            }
+    pub fn remove_breakpoint(&mut self, addr: Address) {
+        self.breakpoints.remove(&addr);
+    }
 
-            OpCode::PushReg => {
-                let arg = Register::try_from(bytecode[0])?;
-                Instruction::PushReg(arg)
-            }
-            OpCode::PushMem => {
-                let addr = u32::from_le_bytes([bytecode[0], bytecode[1], bytecode[2], bytecode[3]]);
+    /// runs until the next breakpoint or halt, stopping before the instruction at a breakpoint
+    /// is executed. the instruction at IP when called always runs so that a stopped cpu can be
+    /// resumed
+    pub fn run_until_breakpoint(&mut self) -> Result<StopReason, Error> {
+        let mut first = true;
 
-                Instruction::PushMem(addr.into())
-            }
-            OpCode::PushU8 => {
-                let val = Value::U8(bytecode[0]);
+        loop {
+            let ip = Address::from(self.registers[Register::IP]);
 
-                Instruction::PushVal(val)
+            if !first && self.breakpoints.contains(&ip) {
+                return Ok(StopReason::Breakpoint(ip));
             }
-            OpCode::PushU16 => {
-                let val = u16::from_le_bytes([bytecode[0], bytecode[1]]);
-                let val = Value::U16(val);
+            first = false;
 
-                Instruction::PushVal(val)
+            if self.step()?.is_break() {
+                return Ok(StopReason::Halted);
             }
-            OpCode::PushU32 => {
-                let val = u32::from_le_bytes([bytecode[0], bytecode[1], bytecode[2], bytecode[3]]);
-                let val = Value::U32(val);
+        }
+    }
 
-                Instruction::PushVal(val)
-            }
+    /// sets the comparison flags from both sides as unsigned numbers
+    fn compare(&mut self, left: u32, right: u32) {
+        self.flags.set_ordering(left.cmp(&right));
+    }
 
-            OpCode::PopReg => {
-                let reg = Register::try_from(bytecode[0])?;
+    /// orders both sides as two's complement
+    fn compare_signed(&mut self, left: i32, right: i32) {
+        self.flags.set_ordering(left.cmp(&right));
+    }
 
-                Instruction::PopReg(reg)
-            }
+    /// orders both sides as floats
+    fn compare_float(&mut self, left: f32, right: f32) {
+        match left.partial_cmp(&right) {
+            Some(ordering) => self.flags.set_ordering(ordering),
+            None => self.flags.set_unordered(),
+        }
+    }
 
-            OpCode::CmpReg => Instruction::CmpReg(
-                Register::try_from(bytecode[0])?,
-                Register::try_from(bytecode[1])?,
-            ),
+    /// runs `op` on the registers as f32 bits, storing the result in `dst`
+    fn float_op(&mut self, dst: Register, src: Register, op: fn(f32, f32) -> f32) {
+        let left = f32::from_bits(self.registers[dst]);
+        let right = f32::from_bits(self.registers[src]);
+        self.registers[dst] = op(left, right).to_bits();
+    }
 
-            OpCode::CmpU8 => Instruction::CmpVal(Value::U8(bytecode[0]), Value::U8(bytecode[0])),
-            OpCode::CmpU16 => Instruction::CmpVal(
-                Value::U16(u16::from_le_bytes([bytecode[0], bytecode[1]])),
-                Value::U16(u16::from_le_bytes([bytecode[2], bytecode[3]])),
-            ),
+    fn arithmetic(&self, op: ArithOp, left: u32, right: u32) -> Result<u32, Error> {
+        let (wrapped, overflowed) = match op {
+            ArithOp::Add => left.overflowing_add(right),
+            ArithOp::Sub => left.overflowing_sub(right),
+            ArithOp::Mul => left.overflowing_mul(right),
+        };
+
+        match self.arithmetic_mode {
+            ArithmeticMode::Wrapping => Ok(wrapped),
+            ArithmeticMode::Saturating => Ok(match op {
+                ArithOp::Add => left.saturating_add(right),
+                ArithOp::Sub => left.saturating_sub(right),
+                ArithOp::Mul => left.saturating_mul(right),
+            }),
+            ArithmeticMode::Checked if overflowed => Err(Error::Overflow),
+            ArithmeticMode::Checked => Ok(wrapped),
+        }
+    }
 
-            OpCode::CmpU32 => Instruction::CmpVal(
-                Value::U32(u32::from_le_bytes([
-                    bytecode[0],
-                    bytecode[1],
-                    bytecode[2],
-                    bytecode[3],
-                ])),
-                Value::U32(u32::from_le_bytes([
-                    bytecode[4],
-                    bytecode[5],
-                    bytecode[6],
-                    bytecode[7],
-                ])),
-            ),
+    fn fetch_instruction(&self) -> Result<Instruction, Error> {
+        let ip = self.registers[Register::IP];
+        let byte = self.memory.read(ip.into())?;
+        let op = OpCode::try_from(byte)?;
 
-            OpCode::Jump => {
-                let addr = u32::from_le_bytes([bytecode[0], bytecode[1], bytecode[2], bytecode[3]]);
+        let bytes = self
+            .memory
+            .get(ip.into()..(ip + op.increment_amount() as u32).into())?;
 
-                Instruction::Jump(addr.into())
-            }
-            OpCode::JumpGe => {
-                let addr = u32::from_le_bytes([bytecode[0], bytecode[1], bytecode[2], bytecode[3]]);
+        Ok(Instruction::decode(&bytes)?.0)
+    }
 
-                Instruction::JumpGe(addr.into())
+    fn execute_instruction(&mut self, inst: Instruction) -> Result<ControlFlow<(), Ip>, Error> {
+        match inst {
+            Instruction::MovRegMem(register, address) => {
+                let address = self.store_address((self.program_start + address)?, 4)?;
+                self.memory.write_u32(address, self.registers[register])?
             }
-            OpCode::JumpGte => {
-                let addr = u32::from_le_bytes([bytecode[0], bytecode[1], bytecode[2], bytecode[3]]);
-
-                Instruction::JumpGte(addr.into())
+            Instruction::MovRegReg(register, register1) => {
+                self.registers[register] = self.registers[register1]
             }
-            OpCode::JumpLe => {
-                let addr = u32::from_le_bytes([bytecode[0], bytecode[1], bytecode[2], bytecode[3]]);
-
-                Instruction::JumpLt(addr.into())
+            Instruction::MovRegNum(register, val) => self.registers[register] = val.as_u32(),
+            Instruction::MovRegLowU16(register, val) => self.registers.set_lower(register, val),
+            Instruction::MovRegHighU16(register, val) => self.registers.set_upper(register, val),
+            Instruction::SetByte(register, byte) => {
+                self.registers[register] = (self.registers[register] & 0xffff_ff00) | byte as u32
             }
-            OpCode::JumpLte => {
-                let addr = u32::from_le_bytes([bytecode[0], bytecode[1], bytecode[2], bytecode[3]]);
 
-                Instruction::JumpLte(addr.into())
+            Instruction::MovMemReg(address, register) => {
+                self.registers[register] = self.memory.read_u32((self.program_start + address)?)?
             }
-
-            OpCode::Call => {
-                let addr = u32::from_le_bytes([bytecode[0], bytecode[1], bytecode[2], bytecode[3]]);
-
-                Instruction::Call(addr.into())
+            Instruction::MovMemNum(address, val) => {
+                let address = self.store_address((self.program_start + address)?, val.width())?;
+                match val {
+                    Value::U8(val) => self.memory.write(address, val),
+                    Value::U16(val) => self.memory.write_u16(address, val),
+                    Value::U32(val) => self.memory.write_u32(address, val),
+                    Value::I32(val) => self.memory.write_u32(address, val as u32),
+                    Value::F32(val) => self.memory.write_u32(address, val.to_bits()),
+                }?
             }
 
-            OpCode::Load => {
-                let reg = Register::try_from(bytecode[0])?;
-                let addr = u32::from_le_bytes([bytecode[1], bytecode[2], bytecode[3], bytecode[4]]);
-                Instruction::Load(reg, addr.into())
+            Instruction::AddRegReg(register, register1) => {
+                self.registers[register] = self.arithmetic(
+                    ArithOp::Add,
+                    self.registers[register],
+                    self.registers[register1],
+                )?
             }
-
-            OpCode::StoreReg => {
-                let reg = Register::try_from(bytecode[0])?;
-                let addr = u32::from_le_bytes([bytecode[1], bytecode[2], bytecode[3], bytecode[4]]);
-
-                warn!(?reg, ?addr);
-
-                Instruction::StoreReg(addr.into(), reg)
+            // when wrapping, adding a negative number in two's complement subtracts it
+            Instruction::AddRegNum(register, val) => {
+                self.registers[register] =
+                    self.arithmetic(ArithOp::Add, self.registers[register], val.as_u32())?
             }
-            OpCode::StoreU8 => {
-                let addr = u32::from_le_bytes([bytecode[0], bytecode[1], bytecode[2], bytecode[3]]);
-                let val = Value::U8(bytecode[4]);
-
-                Instruction::StoreVal(addr.into(), val)
+            Instruction::AddRegMem(register, address) => {
+                let val = self.memory.read_u32((self.program_start + address)?)?;
+                self.registers[register] =
+                    self.arithmetic(ArithOp::Add, self.registers[register], val)?
             }
-            OpCode::StoreU16 => {
-                let addr = u32::from_le_bytes([bytecode[0], bytecode[1], bytecode[2], bytecode[3]]);
-
-                let val = u16::from_le_bytes([bytecode[4], bytecode[5]]);
-                let val = Value::U16(val);
-
-                Instruction::StoreVal(addr.into(), val)
+            Instruction::AddMemReg(address, register) => {
+                let address = self.store_address((self.program_start + address)?, 4)?;
+                let val = self.memory.read_u32(address)?;
+                let sum = self.arithmetic(ArithOp::Add, val, self.registers[register])?;
+                self.memory.write_u32(address, sum)?;
             }
-            OpCode::StoreU32 => {
-                let addr = u32::from_le_bytes([bytecode[0], bytecode[1], bytecode[2], bytecode[3]]);
 
-                let val = u32::from_le_bytes([bytecode[4], bytecode[5], bytecode[6], bytecode[7]]);
-                let val = Value::U32(val);
-
-                Instruction::StoreVal(addr.into(), val)
+            Instruction::AddPair {
+                lo,
+                hi,
+                src_lo,
+                src_hi,
+            } => {
+                let (low, carry) = self.registers[lo].overflowing_add(self.registers[src_lo]);
+                let (high, carry_hi) = self.registers[hi].overflowing_add(self.registers[src_hi]);
+                let (high, carry_in) = high.overflowing_add(carry as u32);
+
+                self.registers[lo] = low;
+                self.registers[hi] = high;
+                self.flags.set_carry(carry_hi || carry_in);
             }
 
-            OpCode::Interrupt => Instruction::Interrupt(u32::from_le_bytes([
-                bytecode[0],
-                bytecode[1],
-                bytecode[2],
-                bytecode[3],
-            ])),
-            OpCode::InterruptReg => Instruction::InterruptReg(Register::try_from(bytecode[0])?),
-
-            OpCode::Halt => Instruction::Halt,
-            OpCode::Ret => Instruction::Ret,
-        })
-    }
+            Instruction::SubRegReg(register, register1) => {
+                self.registers[register] = self.arithmetic(
+                    ArithOp::Sub,
+                    self.registers[register],
+                    self.registers[register1],
+                )?
+            }
+            Instruction::MulRegReg(register, register1) => {
+                self.registers[register] = self.arithmetic(
+                    ArithOp::Mul,
+                    self.registers[register],
+                    self.registers[register1],
+                )?
+            }
+            Instruction::DivRegReg(register, register1) => {
+                self.registers[register] = self.registers[register]
+                    .checked_div(self.registers[register1])
+                    .ok_or(Error::DivideByZero)?
+            }
 
-    fn execute_instruction(&mut self, inst: Instruction) -> Result<ControlFlow<(), ()>, Error> {
-        match inst {
-            Instruction::MovRegMem(register, address) => self
-                .memory
-                .write_u32(self.program_start + address, self.registers[register])?,
-            Instruction::MovRegReg(register, register1) => {
-                self.registers[register] = self.registers[register1]
+            Instruction::AddF32RegReg(register, register1) => {
+                self.float_op(register, register1, |left, right| left + right)
+            }
+            Instruction::SubF32RegReg(register, register1) => {
+                self.float_op(register, register1, |left, right| left - right)
+            }
+            Instruction::MulF32RegReg(register, register1) => {
+                self.float_op(register, register1, |left, right| left * right)
             }
-            Instruction::MovRegNum(register, val) => {
-                let val = match val {
-                    Value::U8(val) => val as u32,
-                    Value::U16(val) => val as u32,
-                    Value::U32(val) => val,
-                };
-                self.registers[register] = val
+            // dividing by zero gives an infinity or nan like any other float division
+            Instruction::DivF32RegReg(register, register1) => {
+                self.float_op(register, register1, |left, right| left / right)
             }
 
-            Instruction::MovMemReg(address, register) => {
-                self.registers[register] = self.memory.read_u32(self.program_start + address)?
+            Instruction::AndRegReg(register, register1) => {
+                self.registers[register] &= self.registers[register1]
+            }
+            Instruction::AndRegNum(register, val) => self.registers[register] &= val.as_u32(),
+            Instruction::OrRegReg(register, register1) => {
+                self.registers[register] |= self.registers[register1]
             }
-            Instruction::MovMemNum(address, val) => match val {
-                Value::U8(val) => self.memory.write(self.program_start + address, val),
-                Value::U16(val) => self.memory.write_u16(self.program_start + address, val),
-                Value::U32(val) => self.memory.write_u32(self.program_start + address, val),
-            }?,
+            Instruction::OrRegNum(register, val) => self.registers[register] |= val.as_u32(),
+            Instruction::XorRegReg(register, register1) => {
+                self.registers[register] ^= self.registers[register1]
+            }
+            Instruction::XorRegNum(register, val) => self.registers[register] ^= val.as_u32(),
+            Instruction::NotReg(register) => self.registers[register] = !self.registers[register],
 
-            Instruction::AddRegReg(register, register1) => {
-                self.registers[register] += self.registers[register1]
+            // the count wraps at the register width instead of panicking
+            Instruction::ShlRegReg(register, register1) => {
+                self.registers[register] =
+                    self.registers[register].wrapping_shl(self.registers[register1])
             }
-            Instruction::AddRegNum(register, val) => match val {
-                Value::U8(val) => self.registers[register] += val as u32,
-                Value::U16(val) => self.registers[register] += val as u32,
-                Value::U32(val) => self.registers[register] += val,
-            },
-            Instruction::AddRegMem(register, address) => {
-                self.registers[register] += self.memory.read_u32(self.program_start + address)?
+            Instruction::ShlRegNum(register, count) => {
+                self.registers[register] = self.registers[register].wrapping_shl(count as u32)
             }
-            Instruction::AddMemReg(address, register) => {
-                let val = self.memory.read_u32(address)?;
-                self.memory
-                    .write_u32(address, val + self.registers[register])?;
+            Instruction::ShrRegReg(register, register1) => {
+                self.registers[register] =
+                    self.registers[register].wrapping_shr(self.registers[register1])
+            }
+            Instruction::ShrRegNum(register, count) => {
+                self.registers[register] = self.registers[register].wrapping_shr(count as u32)
             }
 
             Instruction::IncReg(register) => {
-                self.registers[register] += 1;
+                self.registers[register] =
+                    self.arithmetic(ArithOp::Add, self.registers[register], 1)?
             }
 
-            // should it incremement the address or what it points to?
-            Instruction::IncMem(_address) => todo!(),
+            Instruction::IncMem(address) => {
+                let address = self.store_address((self.program_start + address)?, 4)?;
+                let val = self.arithmetic(ArithOp::Add, self.memory.read_u32(address)?, 1)?;
+                self.memory.write_u32(address, val)?;
+            }
 
             Instruction::PushReg(register) => self.push_stack(self.registers[register])?,
             Instruction::PushMem(address) => {
-                let val = self.memory.read_u32(self.program_start + address)?;
+                let val = self.memory.read_u32((self.program_start + address)?)?;
 
                 self.push_stack(val)?
             }
-            Instruction::PushVal(val) => {
-                let val = match val {
-                    Value::U8(val) => val as u32,
-                    Value::U16(val) => val as u32,
-                    Value::U32(val) => val,
-                };
-                self.push_stack(val)?
-            }
+            Instruction::PushVal(val) => self.push_stack(val.as_u32())?,
 
             Instruction::PopReg(register) => {
                 let val = self.pop_stack()?;
                 self.registers[register] = val;
             }
 
-            Instruction::CmpReg(reg, reg1) => {
-                if reg == reg1 {
-                    self.flags.cmp = Comparison::Eq
+            Instruction::PushAll => {
+                for register in Register::GENERAL {
+                    self.push_stack(self.registers[register])?;
                 }
-
-                let val = self.registers[reg];
-                let val1 = self.registers[reg1];
-
-                if val > val1 {
-                    self.flags.cmp = Comparison::Gt
+            }
+            Instruction::PopAll => {
+                for register in Register::GENERAL.into_iter().rev() {
+                    self.registers[register] = self.pop_stack()?;
                 }
+            }
 
-                if val < val1 {
-                    self.flags.cmp = Comparison::Lt
-                }
+            Instruction::StackDepthReg(register) => {
+                let used = self
+                    .stack_start
+                    .0
+                    .wrapping_sub(self.registers[Register::SP]);
+                self.registers[register] = used / memory::WORD_SIZE;
             }
-            Instruction::CmpVal(val, val1) => {
-                if val == val1 {
-                    self.flags.cmp = Comparison::Eq
-                }
 
-                if val > val1 {
-                    self.flags.cmp = Comparison::Gt
+            Instruction::CmpReg(reg, reg1) => {
+                self.compare(self.registers[reg], self.registers[reg1])
+            }
+            Instruction::CmpVal(val, val1) => {
+                if matches!(val, Value::F32(_)) || matches!(val1, Value::F32(_)) {
+                    self.compare_float(val.as_f32(), val1.as_f32())
+                } else if matches!(val, Value::I32(_)) || matches!(val1, Value::I32(_)) {
+                    self.compare_signed(val.as_u32() as i32, val1.as_u32() as i32)
+                } else {
+                    self.compare(val.as_u32(), val1.as_u32())
                 }
-
-                if val < val1 {
-                    self.flags.cmp = Comparison::Lt
+            }
+            Instruction::CmpRegNum(reg, val) => {
+                let left = self.registers[reg];
+                match val {
+                    Value::I32(val) => self.compare_signed(left as i32, val),
+                    Value::F32(val) => self.compare_float(f32::from_bits(left), val),
+                    val => self.compare(left, val.as_u32()),
                 }
             }
 
-            Instruction::Jump(address) => {
-                self.registers[Register::IP] = (self.program_start + address).into()
-            }
+            Instruction::Jump(address) => return self.jump(true, address),
             Instruction::JumpGe(address) => {
-                if self.flags.cmp == Comparison::Gt {
-                    self.registers[Register::IP] = (self.program_start + address).into()
-                }
+                return self.jump(self.flags.is_set(Flags::GT), address);
             }
-
             Instruction::JumpGte(address) => {
-                if self.flags.cmp == Comparison::Gt || self.flags.cmp == Comparison::Gte {
-                    self.registers[Register::IP] = (self.program_start + address).into()
-                }
+                return self.jump(self.flags.any(Flags::GT | Flags::EQ), address);
             }
             Instruction::JumpLt(address) => {
-                if self.flags.cmp == Comparison::Lt {
-                    self.registers[Register::IP] = (self.program_start + address).into()
-                }
+                return self.jump(self.flags.is_set(Flags::LT), address);
             }
             Instruction::JumpLte(address) => {
-                if self.flags.cmp == Comparison::Lt || self.flags.cmp == Comparison::Lte {
-                    self.registers[Register::IP] = (self.program_start + address).into()
-                }
+                return self.jump(self.flags.any(Flags::LT | Flags::EQ), address);
             }
+            Instruction::JumpEq(address) => {
+                return self.jump(self.flags.is_set(Flags::EQ), address);
+            }
+            Instruction::JumpNe(address) => {
+                return self.jump(self.flags.is_set(Flags::NE), address);
+            }
+            Instruction::JumpReg(register) => {
+                let address = (self.program_start + Address::from(self.registers[register]))?;
+                self.registers[Register::IP] = address.into();
 
-            Instruction::Call(addr) => {
-                self.registers[Register::SP] = self.registers[Register::IP];
-                self.registers[Register::IP] = (self.program_start + addr).into();
-                self.push_stack(self.registers[Register::R1])?;
-                self.push_stack(self.registers[Register::R2])?;
-                self.push_stack(self.registers[Register::R3])?;
-                self.push_stack(self.registers[Register::R4])?;
-                self.push_stack(self.registers[Register::IP])?;
-
-                self.registers[Register::FP] = self.registers[Register::SP].saturating_sub(1);
+                return Ok(ControlFlow::Continue(Ip::Set));
             }
 
+            Instruction::Call(addr) => return self.call(inst, addr, RegisterMask::NONVOLATILE),
+            Instruction::CallSaving(addr, saved) => return self.call(inst, addr, saved),
+
             Instruction::Load(register, address) => {
-                self.registers[register] = self.memory.read_u32(self.program_start + address)?
+                self.registers[register] = self.memory.read_u32((self.program_start + address)?)?
             }
 
             Instruction::StoreReg(address, register) => {
-                let num = self.registers[register];
-                self.memory.write_u32(self.program_start + address, num)?
+                let address = self.store_address((self.program_start + address)?, 4)?;
+                self.memory.write_u32(address, self.registers[register])?
+            }
+            Instruction::StoreVal(address, bytecode) => {
+                let address =
+                    self.store_address((self.program_start + address)?, bytecode.width())?;
+                match bytecode {
+                    Value::U8(num) => self.memory.write(address, num)?,
+                    Value::U16(num) => self.memory.write_u16(address, num)?,
+                    Value::U32(num) => self.memory.write_u32(address, num)?,
+                    Value::I32(num) => self.memory.write_u32(address, num as u32)?,
+                    Value::F32(num) => self.memory.write_u32(address, num.to_bits())?,
+                }
             }
-            Instruction::StoreVal(address, bytecode) => match bytecode {
-                Value::U8(num) => self.memory.write(self.program_start + address, num)?,
-                Value::U16(num) => self.memory.write_u16(self.program_start + address, num)?,
-                Value::U32(num) => self.memory.write_u32(self.program_start + address, num)?,
-            },
 
-            Instruction::Interrupt(idx) => self.handle_interrupt(idx)?,
+            Instruction::LoadIndirect(dest, addr) => {
+                let address = (self.program_start + Address::from(self.registers[addr]))?;
+                self.registers[dest] = self.memory.read_u32(address)?
+            }
+            Instruction::StoreIndirect(addr, src) => {
+                let address = (self.program_start + Address::from(self.registers[addr]))?;
+                let address = self.store_address(address, 4)?;
+                self.memory.write_u32(address, self.registers[src])?
+            }
+            Instruction::StoreRegIndexed(base, index, src) => {
+                let address =
+                    ((self.program_start + base)? + Address::from(self.registers[index]))?;
+                let address = self.store_address(address, 4)?;
+                self.memory.write_u32(address, self.registers[src])?
+            }
+
+            // like an empty fill, an empty copy is not checked against the program
+            Instruction::MemCopy(dest, src, len) if self.registers[len] > 0 => {
+                let len = self.registers[len];
+                let src = (self.program_start + src)?;
+                let bytes = self.memory.get(src..(src + Address::from(len))?)?;
+                let dest = self.store_address((self.program_start + dest)?, len as usize)?;
+                self.memory.write_bytes(dest, &bytes)?
+            }
+            Instruction::MemCopy(..) => {}
+            // an empty fill writes nothing, so it is not checked against the program or memory
+            Instruction::MemSet(dest, byte, len) if self.registers[len] > 0 => {
+                let len = self.registers[len];
+                let dest = self.store_address((self.program_start + dest)?, len as usize)?;
+                // nothing is written when the range runs off the end of memory
+                let end = dest.0 as usize + len as usize;
+                if end > self.memory.capacity() {
+                    return Err(memory::Error::InvalidAddress((end - 1) as u32).into());
+                }
+                self.memory.write_bytes(dest, &vec![byte; len as usize])?
+            }
+            Instruction::MemSet(..) => {}
+
+            Instruction::Interrupt(idx) => {
+                if self.handle_interrupt(idx, OpCode::Interrupt.increment_amount())? {
+                    return Ok(ControlFlow::Continue(Ip::Set));
+                }
+            }
             Instruction::InterruptReg(register) => {
                 let idx = self.registers[register];
-                self.handle_interrupt(idx)?
+                if self.handle_interrupt(idx, OpCode::InterruptReg.increment_amount())? {
+                    return Ok(ControlFlow::Continue(Ip::Set));
+                }
             }
 
+            Instruction::Nop => {}
             Instruction::Halt => return Ok(ControlFlow::Break(())),
 
-            Instruction::Ret => self.restore_stack()?,
+            Instruction::Ret => {
+                self.restore_stack()?;
+                self.call_depth = self.call_depth.saturating_sub(1);
+
+                return Ok(ControlFlow::Continue(Ip::Set));
+            }
+            Instruction::IRet => {
+                if self.interrupt_depth == 0 {
+                    return Err(Error::NotInInterrupt);
+                }
+
+                self.restore_stack()?;
+                self.interrupt_depth -= 1;
+
+                return Ok(ControlFlow::Continue(Ip::Set));
+            }
         }
 
-        Ok(ControlFlow::Continue(()))
+        Ok(ControlFlow::Continue(Ip::Advance))
     }
 
-    #[instrument(skip(self))]
-    pub fn step(&mut self) -> Result<ControlFlow<(), ()>, Error> {
-        let inst = self.fetch_instruction()?;
+    /// passes `address` through unless writing `len` bytes there would change the loaded
+    /// program while it is protected
+    fn store_address(&self, address: Address, len: usize) -> Result<Address, Error> {
+        let overlaps = address.0 < self.program_end.0
+            && address.0.saturating_add(len as u32) > self.program_start.0;
+        if overlaps && !self.code_writable {
+            return Err(Error::WriteProtected(address));
+        }
 
-        let res = self.execute_instruction(inst);
+        Ok(address)
+    }
 
-        self.registers[Register::IP] += OpCode::from(inst).increment_amount() as u32;
+    /// moves IP to `address` when `taken`, otherwise execution falls through
+    fn jump(&mut self, taken: bool, address: Address) -> Result<ControlFlow<(), Ip>, Error> {
+        if !taken {
+            return Ok(ControlFlow::Continue(Ip::Advance));
+        }
 
-        res
+        self.registers[Register::IP] = (self.program_start + address)?.into();
+        Ok(ControlFlow::Continue(Ip::Set))
     }
 
-    fn write_val(&mut self, address: Address, val: u32) -> Result<(), memory::Error> {
-        trace!("writing to address {address} value {val}");
+    #[cfg_attr(feature = "trace", instrument(skip(self)))]
+    pub fn step(&mut self) -> Result<ControlFlow<(), ()>, Error> {
+        let inst = self.fetch_instruction()?;
+        trace!(?inst, "step");
 
-        let sp: Address = self.registers()[Register::SP].into();
+        if let Some(hook) = &mut self.step_hook.0 {
+            hook(&inst, &self.registers);
+        }
 
-        let bytes = val.to_le_bytes();
+        self.cycles += OpCode::from(inst).cost() as u64;
+        if let Some(coverage) = &mut self.coverage {
+            coverage.insert(Address::from(self.registers[Register::IP]));
+        }
+        let res = self.execute_instruction(inst);
 
-        for (i, byte) in bytes.iter().rev().enumerate() {
-            info!(?byte, ?i);
-            self.memory.write(sp - i.into(), *byte)?;
+        // taken jumps, calls, interrupts and returns leave IP on the next instruction to run
+        if !matches!(res, Ok(ControlFlow::Continue(Ip::Set))) {
+            self.registers[Register::IP] += inst.size() as u32;
         }
 
-        Ok(())
+        res.map(|flow| match flow {
+            ControlFlow::Continue(_) => ControlFlow::Continue(()),
+            ControlFlow::Break(()) => ControlFlow::Break(()),
+        })
     }
 
-    fn push_stack(&mut self, val: u32) -> Result<(), memory::Error> {
-        let sp: Address = self.registers()[Register::SP].into();
-
-        self.write_val(sp, val)?;
+    /// the lowest address the stack can grow down to, a stack above the program stops where
+    /// the program ends
+    fn stack_limit(&self) -> u32 {
+        if self.program_start.0 <= self.stack_start.0 {
+            self.program_end.0
+        } else {
+            0
+        }
+    }
+
+    /// stack slots are written most significant byte first, growing down from SP, so the low
+    /// byte of the most recently pushed value sits just below the previous top of the stack
+    fn push_stack(&mut self, val: u32) -> Result<(), memory::Error> {
+        let mut sp: Address = self.registers()[Register::SP].into();
+
+        match sp.0.checked_sub(memory::WORD_SIZE) {
+            Some(top) if top >= self.stack_limit() => {}
+            _ => return Err(memory::Error::StackOverflow),
+        }
+
+        for byte in val.to_le_bytes() {
+            sp = sp.prev()?;
+            trace!("pushing {byte} to {sp}");
+            self.memory.write(sp, byte)?;
+        }
 
-        let prev: u32 = sp.prev()?.into();
-        self.registers[Register::SP] -= prev.saturating_sub(4);
+        self.registers[Register::SP] = sp.into();
         Ok(())
     }
 
@@ -549,107 +1029,156 @@ impl Cpu {
         Ok(val)
     }
 
-    fn read_mem_u32<A>(&self, address: A) -> Result<u32, memory::Error>
-    where
-        A: Into<Address>,
-    {
-        let address = address.into();
-
-        let l8bit = self.memory.read(address)?;
-        let address = address.next()?;
-
-        let h8bit = self.memory.read(address)?;
-        let address = address.next()?;
-
-        let address = address.next()?;
-        let l16bit = self.memory.read(address)?;
-
-        let h16bit = self.memory.read(address)?;
-
-        let val = u32::from_le_bytes([l8bit, h8bit, l16bit, h16bit]);
-
-        Ok(val)
-    }
-
     fn pop_stack(&mut self) -> Result<u32, memory::Error> {
-        let sp: Address = self.registers[Register::SP].into();
+        let mut sp: Address = self.registers[Register::SP].into();
 
-        let val = self.read_mem_u32(sp)?;
+        match sp.0.checked_add(memory::WORD_SIZE) {
+            Some(end) if end <= self.stack_start.0 => {}
+            _ => return Err(memory::Error::StackUnderflow),
+        }
 
-        let sp = sp.next()?;
+        let mut bytes = [0; 4];
+        for byte in bytes.iter_mut() {
+            *byte = self.memory.read(sp)?;
+            sp = sp.next()?;
+        }
 
         self.registers.set(Register::SP, sp.into());
-        Ok(val)
+        Ok(u32::from_be_bytes(bytes))
     }
 
-    fn save_stack(&mut self) -> Result<(), memory::Error> {
-        self.push_stack(self.registers[Register::R1])?;
-        self.push_stack(self.registers[Register::R2])?;
-        self.push_stack(self.registers[Register::R3])?;
-        self.push_stack(self.registers[Register::R4])?;
+    /// pushes a frame holding the registers in `saved`, the mask itself, IP and FP, then points
+    /// FP at it. undone by [`Cpu::restore_stack`]
+    fn save_stack(&mut self, saved: RegisterMask) -> Result<(), memory::Error> {
+        for register in saved.registers() {
+            self.push_stack(self.registers[register])?;
+        }
+        self.push_stack(saved.0 as u32)?;
         self.push_stack(self.registers[Register::IP])?;
+        self.push_stack(self.registers[Register::FP])?;
+
+        self.registers[Register::FP] = self.registers[Register::SP];
 
-        let sp = self.registers[Register::SP];
-        let fp = self.registers[Register::FP];
+        Ok(())
+    }
 
-        let next_fp = sp.saturating_sub(1);
-        let fp_size = fp - next_fp;
+    /// pops the frame FP points at, restoring the registers the mask in it names
+    fn restore_stack(&mut self) -> Result<(), memory::Error> {
+        self.registers[Register::SP] = self.registers[Register::FP];
 
-        self.memory.write_u32(Address::from(sp), fp_size)?;
+        self.registers[Register::FP] = self.pop_stack()?;
+        self.registers[Register::IP] = self.pop_stack()?;
+        let saved = RegisterMask(self.pop_stack()? as u8);
+        for register in saved.registers().rev() {
+            self.registers[register] = self.pop_stack()?;
+        }
 
         Ok(())
     }
 
-    fn restore_stack(&mut self) -> Result<(), memory::Error> {
-        let fp = self.registers[Register::FP];
+    /// enters the function at `target`, saving the registers in `saved`
+    fn call(
+        &mut self,
+        inst: Instruction,
+        target: Address,
+        saved: RegisterMask,
+    ) -> Result<ControlFlow<(), Ip>, Error> {
+        if self
+            .max_call_depth
+            .is_some_and(|max| self.call_depth >= max)
+        {
+            return Err(Error::CallDepthExceeded);
+        }
 
-        self.registers[Register::SP] = fp;
+        // the frame holds the address of the instruction after the call, ret resumes there
+        self.registers[Register::IP] += inst.size() as u32;
+        self.save_stack(saved)?;
+        self.call_depth += 1;
 
-        let frame_size = self.pop_stack()?;
-        let ip = self.pop_stack()?;
-        let r4 = self.pop_stack()?;
-        let r3 = self.pop_stack()?;
-        let r2 = self.pop_stack()?;
-        let r1 = self.pop_stack()?;
+        self.registers[Register::IP] = (self.program_start + target)?.into();
+        Ok(ControlFlow::Continue(Ip::Set))
+    }
+
+    /// enters the handler for interrupt `idx`, returning whether it jumped to one in the
+    /// program. every entry saves a frame holding the address of the next instruction, which
+    /// the handler's iret returns to
+    fn handle_interrupt(&mut self, idx: u32, size: u8) -> Result<bool, memory::Error> {
+        // the handler is taken out while it runs so it can borrow the cpu
+        if let Some(mut handler) = self.interrupt_handlers.0.remove(&idx) {
+            handler(self);
+            self.interrupt_handlers.0.entry(idx).or_insert(handler);
+            return Ok(false);
+        }
 
-        self.registers[Register::IP] = ip;
-        self.registers[Register::R4] = r4;
-        self.registers[Register::R3] = r3;
-        self.registers[Register::R2] = r2;
-        self.registers[Register::R1] = r1;
+        let ptr = (self.interrupt_table + idx.into())?;
+        let handler = self.memory.read_u32(ptr)?;
 
-        let prev_frame_ptr = fp + frame_size;
-        self.registers[Register::FP] = prev_frame_ptr;
+        self.registers[Register::IP] += size as u32;
+        self.save_stack(RegisterMask::NONVOLATILE)?;
 
-        Ok(())
+        self.interrupt_depth += 1;
+        self.registers[Register::IP] = handler;
+
+        Ok(true)
     }
+}
 
-    fn handle_interrupt(&mut self, idx: u32) -> Result<(), memory::Error> {
-        let ptr = self.interrupt_table + idx.into();
+impl Cpu<CpuMemory> {
+    /// loads the code of an [`Image`] at address 0 with IP on its entry, the stack starts at
+    /// the same place the cli puts it
+    pub fn load_image(bytes: &[u8]) -> Result<Self, Error> {
+        let image = Image::parse(bytes)?;
 
-        if !self.in_interrupt {
-            self.save_stack()?;
-        }
+        let mut cpu = Self::new(
+            CpuMemory::default(),
+            0,
+            DEFAULT_STACK_START,
+            Address::default(),
+        );
+        cpu.load_program(image.code)?;
+        cpu.registers[Register::IP] = (cpu.program_start + image.entry)?.into();
 
-        let fp = self.memory.read_u32(ptr)?;
+        Ok(cpu)
+    }
+
+    /// places the stack at the top of `memory`
+    pub fn with_stack_at_top(
+        memory: CpuMemory,
+        program_start: u32,
+        interrupt_table: Address,
+    ) -> Self {
+        let top = memory.capacity().saturating_sub(1);
+        let stack_start = u32::try_from(top).unwrap_or(u32::MAX);
 
-        self.in_interrupt = true;
-        self.registers[Register::IP] = fp;
+        Self::new(memory, program_start, stack_start, interrupt_table)
+    }
 
-        Ok(())
+    pub fn register_mmio(&mut self, range: Range<Address>, device: Box<dyn MmioDevice>) {
+        self.memory.register_mmio(range, device);
     }
 }
 
 #[cfg(test)]
 mod test {
-    use std::ops::ControlFlow;
+    use alloc::{boxed::Box, rc::Rc, vec::Vec};
+    use core::{
+        cell::{Cell, RefCell},
+        ops::ControlFlow,
+    };
 
     use tracing::{info, level_filters::LevelFilter, trace};
     use tracing_subscriber::util::SubscriberInitExt;
 
-    use crate::{cpu::CpuMemory, memory::Address, opcodes::OpCode, registers::Register};
+    use crate::{
+        cpu::CpuMemory,
+        image::{self, Image},
+        memory::{self, Address, Memory, Stack, PAGE_SIZE},
+        mmio::MmioDevice,
+        opcodes::{Comparison, Instruction, OpCode, Value},
+        registers::{Register, RegisterMask},
+    };
 
-    use super::Cpu;
+    use super::{ArithmeticMode, Cpu, Error, Flags, StopReason, TraceEntry};
 
     fn setup_logger() {
         let _ = tracing_subscriber::FmtSubscriber::builder()
@@ -662,7 +1191,7 @@ mod test {
     fn setup_cpu(bytes: &[u8]) -> Cpu {
         let mut mem = super::CpuMemory::default();
 
-        mem.write_bytes(0, bytes as &[u8]).unwrap();
+        mem.write_bytes(Address::from(0), bytes as &[u8]).unwrap();
 
         Cpu::new(mem, 0, u16::MAX as u32, 0.into())
     }
@@ -683,204 +1212,1771 @@ mod test {
     #[test]
     fn add_reg_reg() {}
     #[test]
-    fn add_reg_num() {}
+    fn add_reg_num() {
+        setup_logger();
+
+        let minus_five = (-5_i32 as u32).to_le_bytes();
+        let mut cpu = setup_cpu(&[
+            OpCode::MovRegU8 as u8,
+            Register::R1 as u8,
+            10,
+            OpCode::AddRegU32 as u8,
+            Register::R1 as u8,
+            minus_five[0],
+            minus_five[1],
+            minus_five[2],
+            minus_five[3],
+            OpCode::Halt as u8,
+        ]);
+
+        cpu.execute();
+
+        assert_eq!(cpu.registers[Register::R1], 5);
+    }
     #[test]
     fn add_reg_mem() {}
     #[test]
-    fn add_mem_reg() {}
+    fn add_mem_reg() {
+        setup_logger();
+
+        // the address is relative to the program start like every other memory operand
+        let mut bytes = Instruction::MovRegNum(Register::R1, Value::U8(3)).encode();
+        bytes.extend(Instruction::AddMemReg(Address::from(50), Register::R1).encode());
+        bytes.extend(Instruction::Halt.encode());
+        let mut cpu = Cpu::new(CpuMemory::default(), 100, u16::MAX as u32, 0.into());
+        cpu.load_program(&bytes).unwrap();
+        cpu.memory.write_u32(Address::from(150), 4).unwrap();
+        cpu.execute();
+
+        assert_eq!(cpu.memory.read_u32(Address::from(150)).unwrap(), 7);
+        assert_eq!(cpu.memory.read_u32(Address::from(50)).unwrap(), 0);
+    }
 
     #[test]
-    fn inc_reg() {}
+    fn add_pair() {
+        setup_logger();
+
+        // (R1, R2) = 0x1_ffff_ffff, (R3, R4) = 0x2_0000_0001
+        let mut bytes = Instruction::MovRegNum(Register::R1, Value::U32(u32::MAX)).encode();
+        bytes.extend(Instruction::MovRegNum(Register::R2, Value::U8(1)).encode());
+        bytes.extend(Instruction::MovRegNum(Register::R3, Value::U8(1)).encode());
+        bytes.extend(Instruction::MovRegNum(Register::R4, Value::U8(2)).encode());
+        bytes.extend(
+            Instruction::AddPair {
+                lo: Register::R1,
+                hi: Register::R2,
+                src_lo: Register::R3,
+                src_hi: Register::R4,
+            }
+            .encode(),
+        );
+        bytes.push(OpCode::Halt as u8);
+
+        let mut cpu = setup_cpu(&bytes);
+        cpu.execute();
+
+        assert_eq!(cpu.registers[Register::R1], 0);
+        assert_eq!(cpu.registers[Register::R2], 4);
+        assert!(!cpu.flags.is_set(Flags::CARRY));
+    }
+
     #[test]
-    fn inc_mem() {}
+    fn add_pair_carry_out() {
+        setup_logger();
+
+        let mut bytes = Instruction::MovRegNum(Register::R1, Value::U32(u32::MAX)).encode();
+        bytes.extend(Instruction::MovRegNum(Register::R2, Value::U32(u32::MAX)).encode());
+        bytes.extend(Instruction::MovRegNum(Register::R3, Value::U8(1)).encode());
+        bytes.extend(
+            Instruction::AddPair {
+                lo: Register::R1,
+                hi: Register::R2,
+                src_lo: Register::R3,
+                src_hi: Register::R4,
+            }
+            .encode(),
+        );
+        bytes.push(OpCode::Halt as u8);
+
+        let mut cpu = setup_cpu(&bytes);
+        cpu.execute();
+
+        assert_eq!(cpu.registers[Register::R1], 0);
+        assert_eq!(cpu.registers[Register::R2], 0);
+        assert!(cpu.flags.is_set(Flags::CARRY));
+    }
 
     #[test]
-    fn push_reg() {
+    fn sub_mul_div() {
         setup_logger();
 
         let mut cpu = setup_cpu(&[
             OpCode::MovRegU8 as u8,
             Register::R1 as u8,
             10,
-            OpCode::PushReg as u8,
+            OpCode::MovRegU8 as u8,
+            Register::R2 as u8,
+            4,
+            OpCode::SubRegReg as u8,
+            Register::R1 as u8,
+            Register::R2 as u8,
+            OpCode::MulRegReg as u8,
             Register::R1 as u8,
+            Register::R2 as u8,
+            OpCode::DivRegReg as u8,
+            Register::R1 as u8,
+            Register::R2 as u8,
             OpCode::Halt as u8,
         ]);
 
         cpu.execute();
 
-        assert_eq!(cpu.read_mem_u8(u16::MAX as usize - 1).unwrap(), 10);
+        assert_eq!(cpu.registers[Register::R1], 6);
     }
+
     #[test]
-    fn push_mem() {
+    fn div_by_zero() {
         setup_logger();
 
-        let mut mem = CpuMemory::default();
+        let mut cpu = setup_cpu(&[
+            OpCode::DivRegReg as u8,
+            Register::R1 as u8,
+            Register::R2 as u8,
+        ]);
 
-        let bytes = &[OpCode::PushMem as u8, 6, 0, 0, 0, OpCode::Halt as u8, 90];
-        mem.write_bytes(0, bytes as &[u8]).unwrap();
+        assert!(matches!(cpu.step(), Err(Error::DivideByZero)));
+    }
 
-        let mut cpu = Cpu::new(mem, 0, u16::MAX as u32, 0.into());
+    #[test]
+    fn rust_interrupt() {
+        setup_logger();
 
-        tracing::info!("{:?}", cpu.memory.get(0.into()..10.into()));
+        let mut bytes = Instruction::Interrupt(0).encode();
+        bytes.extend(Instruction::Interrupt(0).encode());
+        bytes.extend(Instruction::MovRegNum(Register::R1, Value::U8(7)).encode());
+        bytes.push(OpCode::Halt as u8);
 
+        let count = Rc::new(Cell::new(0));
+        let counter = count.clone();
+
+        let mut cpu = setup_cpu(&bytes);
+        cpu.register_interrupt(0, Box::new(move |_| counter.set(counter.get() + 1)));
         cpu.execute();
+
+        assert_eq!(count.get(), 2);
+        assert_eq!(cpu.registers[Register::R1], 7);
     }
-    #[test]
-    fn push_val() {}
 
-    #[test]
-    fn pop_reg() {}
+    #[derive(Default)]
+    struct Recorder(Rc<RefCell<Vec<(u32, u8)>>>);
 
-    #[test]
-    fn cmp_reg() {}
+    impl MmioDevice for Recorder {
+        fn read(&self, offset: u32) -> u8 {
+            offset as u8 + 1
+        }
 
-    #[test]
-    fn cmp_val() {}
+        fn write(&mut self, offset: u32, byte: u8) {
+            self.0.borrow_mut().push((offset, byte));
+        }
+    }
 
     #[test]
-    fn jump() {}
-    #[test]
-    fn jump_ge() {}
-    #[test]
-    fn jump_gte() {}
-    #[test]
-    fn jump_lt() {}
+    fn mmio() {
+        setup_logger();
+
+        let mut bytes = Instruction::StoreVal(Address::from(0x1000), Value::U8(b'h')).encode();
+        bytes.extend(Instruction::StoreVal(Address::from(0x1001), Value::U8(b'i')).encode());
+        bytes.extend(Instruction::Load(Register::R1, Address::from(0x1000)).encode());
+        bytes.push(OpCode::Halt as u8);
+
+        let recorder = Recorder::default();
+        let written = recorder.0.clone();
+
+        let mut cpu = setup_cpu(&bytes);
+        cpu.register_mmio(
+            Address::from(0x1000)..Address::from(0x1010),
+            Box::new(recorder),
+        );
+        cpu.execute();
+
+        assert_eq!(*written.borrow(), [(0, b'h'), (1, b'i')]);
+        assert_eq!(
+            cpu.registers[Register::R1],
+            u32::from_le_bytes([1, 2, 3, 4])
+        );
+    }
+
+    /// runs `u32::MAX + 2` in `mode`
+    fn overflowing_add(mode: ArithmeticMode) -> (Cpu, Result<(), Error>) {
+        let mut bytes = Instruction::MovRegNum(Register::R1, Value::U32(u32::MAX)).encode();
+        bytes.extend(Instruction::AddRegNum(Register::R1, Value::U8(2)).encode());
+
+        let mut cpu = setup_cpu(&bytes);
+        cpu.set_arithmetic_mode(mode);
+
+        let _ = cpu.step().unwrap();
+        let res = cpu.step().map(|_| ());
+
+        (cpu, res)
+    }
+
     #[test]
-    fn jump_lte() {}
+    fn wrapping_add() {
+        setup_logger();
+
+        let (cpu, res) = overflowing_add(ArithmeticMode::Wrapping);
+
+        assert!(res.is_ok());
+        assert_eq!(cpu.registers[Register::R1], 1);
+    }
 
     #[test]
-    fn call() {}
+    fn saturating_add() {
+        setup_logger();
+
+        let (cpu, res) = overflowing_add(ArithmeticMode::Saturating);
+
+        assert!(res.is_ok());
+        assert_eq!(cpu.registers[Register::R1], u32::MAX);
+    }
 
     #[test]
-    fn load() {}
+    fn checked_add() {
+        setup_logger();
+
+        let (cpu, res) = overflowing_add(ArithmeticMode::Checked);
+
+        assert!(matches!(res, Err(Error::Overflow)));
+        assert_eq!(cpu.registers[Register::R1], u32::MAX);
+    }
 
     #[test]
-    fn store_reg() {
+    fn xor_reg_reg() {
         setup_logger();
 
         let mut cpu = setup_cpu(&[
             OpCode::MovRegU8 as u8,
             Register::R1 as u8,
-            9,
-            OpCode::StoreReg as u8,
+            0b1010,
+            OpCode::XorRegReg as u8,
+            Register::R1 as u8,
             Register::R1 as u8,
-            10,
-            0,
-            0,
-            0,
             OpCode::Halt as u8,
         ]);
 
         cpu.execute();
 
-        assert_eq!(cpu.memory.read_u32(10).unwrap(), 9);
+        assert_eq!(cpu.registers[Register::R1], 0);
     }
 
     #[test]
-    fn store_val() {
+    fn and_or_reg_num() {
         setup_logger();
 
-        let mut cpu = setup_cpu(&[OpCode::StoreU8 as u8, 5, 0, 0, 0, 10]);
-        trace!("{:?}", cpu.memory.get(Address::from(0)..10.into()));
-
-        let _ = cpu.step().unwrap();
+        let mut cpu = setup_cpu(&[
+            OpCode::MovRegU8 as u8,
+            Register::R1 as u8,
+            0b1100,
+            OpCode::AndRegU8 as u8,
+            Register::R1 as u8,
+            0b0110,
+            OpCode::OrRegU8 as u8,
+            Register::R1 as u8,
+            0b0001,
+            OpCode::Halt as u8,
+        ]);
 
-        trace!("{:?}", cpu.memory.get(Address::from(0)..10.into()));
+        cpu.execute();
 
-        assert!(cpu.memory.read(5).unwrap() == 10);
+        assert_eq!(cpu.registers[Register::R1], 0b0101);
     }
 
     #[test]
-    fn interrupt() {
+    fn not_reg() {
         setup_logger();
 
-        let mut mem = CpuMemory::default();
-
-        let bytes: &[u8] = &[
-            OpCode::Interrupt as u8,
-            0,
-            0,
-            0,
-            1,
-            OpCode::IncReg as u8,
+        let mut cpu = setup_cpu(&[
+            OpCode::MovRegU8 as u8,
+            Register::R1 as u8,
+            0x0f,
+            OpCode::NotReg as u8,
             Register::R1 as u8,
             OpCode::Halt as u8,
-        ];
-
-        mem.write_bytes(0, bytes as &[u8]).unwrap();
+        ]);
 
-        let mut cpu = Cpu::new(mem, 0, u16::MAX as u32, 10.into());
+        cpu.execute();
 
-        for i in 0..3 {
-            let hlt = cpu.step().unwrap();
-            info!(?hlt);
-            if i == 3 {
-                assert!(hlt == ControlFlow::Break(()));
-            }
-        }
+        assert_eq!(cpu.registers[Register::R1], 0xffff_fff0);
     }
 
     #[test]
-    fn interrupt_reg() {
+    fn shift() {
         setup_logger();
 
-        let mut mem = CpuMemory::default();
-
-        let bytes: &[u8] = &[
+        let mut cpu = setup_cpu(&[
             OpCode::MovRegU8 as u8,
             Register::R1 as u8,
-            6,
-            OpCode::InterruptReg as u8,
+            1,
+            OpCode::ShlRegU8 as u8,
             Register::R1 as u8,
-            OpCode::IncReg as u8,
+            8,
+            OpCode::MovRegU8 as u8,
+            Register::R2 as u8,
+            4,
+            OpCode::ShrRegReg as u8,
             Register::R1 as u8,
+            Register::R2 as u8,
             OpCode::Halt as u8,
-        ];
-
-        mem.write_bytes(0, bytes as &[u8]).unwrap();
+        ]);
 
-        let mut cpu = Cpu::new(mem, 0, u16::MAX as u32, 10.into());
+        cpu.execute();
 
-        for i in 0..3 {
-            let hlt = cpu.step().unwrap();
-            info!(?hlt);
-            if i == 3 {
-                assert!(hlt == ControlFlow::Break(()));
-            }
-        }
+        assert_eq!(cpu.registers[Register::R1], 1 << 4);
     }
 
     #[test]
-    fn halt() {
+    fn shift_wraps_count() {
         setup_logger();
 
-        let mut cpu = setup_cpu(&[OpCode::Halt as u8]);
+        let mut cpu = setup_cpu(&[
+            OpCode::MovRegU8 as u8,
+            Register::R1 as u8,
+            1,
+            OpCode::ShlRegU8 as u8,
+            Register::R1 as u8,
+            40,
+            OpCode::Halt as u8,
+        ]);
 
-        assert!(cpu.step().unwrap() == ControlFlow::Break(()));
+        cpu.execute();
+
+        assert_eq!(cpu.registers[Register::R1], 1 << 8);
     }
 
     #[test]
-    fn ret() {
-        todo!()
+    fn inc_reg() {}
+    #[test]
+    fn inc_mem() {
+        setup_logger();
+
+        let inc = |mode, start| {
+            let bytes = Instruction::IncMem(Address::from(100)).encode();
+            let mut cpu = setup_cpu(&bytes);
+            cpu.set_arithmetic_mode(mode);
+            cpu.memory.write_u32(Address::from(100), start).unwrap();
+            let res = cpu.step().map(|_| ());
+            (res, cpu.memory.read_u32(Address::from(100)).unwrap())
+        };
+
+        assert!(matches!(inc(ArithmeticMode::Wrapping, 41), (Ok(()), 42)));
+        assert!(matches!(
+            inc(ArithmeticMode::Wrapping, u32::MAX),
+            (Ok(()), 0)
+        ));
+        assert!(matches!(
+            inc(ArithmeticMode::Saturating, u32::MAX),
+            (Ok(()), u32::MAX)
+        ));
+        assert!(matches!(
+            inc(ArithmeticMode::Checked, u32::MAX),
+            (Err(Error::Overflow), u32::MAX)
+        ));
     }
 
     #[test]
-    fn read_mem_u32() {
+    fn push_reg() {
         setup_logger();
 
-        let mut mem = CpuMemory::default();
+        let mut cpu = setup_cpu(&[
+            OpCode::MovRegU8 as u8,
+            Register::R1 as u8,
+            10,
+            OpCode::PushReg as u8,
+            Register::R1 as u8,
+            OpCode::Halt as u8,
+        ]);
 
-        let bytes: &[u8] = &[6, 0, 0, 0];
-        mem.write_bytes(0, bytes as &[u8]).unwrap();
+        cpu.execute();
 
-        let cpu = Cpu::new(mem, 0, u16::MAX as u32, 0.into());
+        assert_eq!(cpu.read_mem_u8(u16::MAX as usize - 1).unwrap(), 10);
+    }
+    #[test]
+    fn stack_depth() {
+        setup_logger();
 
-        let val = cpu.read_mem_u32(0).unwrap();
+        let mut cpu = setup_cpu(&[
+            OpCode::PushU8 as u8,
+            1,
+            OpCode::PushU8 as u8,
+            2,
+            OpCode::PushU8 as u8,
+            3,
+            OpCode::StackDepthReg as u8,
+            Register::R1 as u8,
+            OpCode::PopReg as u8,
+            Register::R2 as u8,
+            OpCode::StackDepthReg as u8,
+            Register::R3 as u8,
+            OpCode::Halt as u8,
+        ]);
+
+        cpu.execute();
+
+        assert_eq!(cpu.registers[Register::R1], 3);
+        assert_eq!(cpu.registers[Register::R3], 2);
+    }
+
+    #[test]
+    fn push_at_top_of_memory() {
+        setup_logger();
+
+        let mut mem = CpuMemory::default();
+        let top = mem.capacity() - 1;
+
+        let bytes: &[u8] = &[
+            OpCode::MovRegU8 as u8,
+            Register::R1 as u8,
+            10,
+            OpCode::PushReg as u8,
+            Register::R1 as u8,
+            OpCode::Halt as u8,
+        ];
+        mem.write_bytes(Address::from(0), bytes).unwrap();
+
+        let mut cpu = Cpu::with_stack_at_top(mem, 0, 0.into());
+        assert_eq!(cpu.registers[Register::SP] as usize, top);
+
+        cpu.execute();
+
+        assert_eq!(cpu.registers[Register::SP] as usize, top - 4);
+        assert_eq!(cpu.read_mem_u8(top - 1).unwrap(), 10);
+    }
+
+    #[test]
+    fn push_mem() {
+        setup_logger();
+
+        let mut mem = CpuMemory::default();
+
+        let bytes = &[OpCode::PushMem as u8, 6, 0, 0, 0, OpCode::Halt as u8, 90];
+        mem.write_bytes(Address::from(0), bytes as &[u8]).unwrap();
+
+        let mut cpu = Cpu::new(mem, 0, u16::MAX as u32, 0.into());
+
+        tracing::info!("{:?}", cpu.memory.get(0.into()..10.into()));
+
+        cpu.execute();
+    }
+    #[test]
+    fn push_val() {}
+
+    #[test]
+    fn pop_reg() {}
+
+    #[test]
+    fn cmp_reg() {
+        setup_logger();
+
+        let mut bytes = Instruction::MovRegNum(Register::R1, Value::U8(3)).encode();
+        bytes.extend(Instruction::MovRegNum(Register::R2, Value::U8(7)).encode());
+        bytes.extend(Instruction::CmpReg(Register::R1, Register::R2).encode());
+        bytes.extend(Instruction::CmpReg(Register::R2, Register::R1).encode());
+        bytes.extend(Instruction::CmpReg(Register::R1, Register::R1).encode());
+        bytes.extend(Instruction::Halt.encode());
+        let mut cpu = setup_cpu(&bytes);
+
+        let _ = cpu.step().unwrap();
+        let _ = cpu.step().unwrap();
+        let _ = cpu.step().unwrap();
+        assert_eq!(cpu.flags().comparison(), Comparison::Lt);
+
+        let _ = cpu.step().unwrap();
+        assert_eq!(cpu.flags().comparison(), Comparison::Gt);
+
+        let _ = cpu.step().unwrap();
+        assert_eq!(cpu.flags().comparison(), Comparison::Eq);
+    }
+
+    #[test]
+    fn cmp_val() {
+        setup_logger();
+
+        let mut bytes = Instruction::CmpVal(Value::U8(9), Value::U8(2)).encode();
+        bytes.extend(Instruction::CmpVal(Value::U16(300), Value::U16(400)).encode());
+        bytes.extend(Instruction::CmpVal(Value::U32(70000), Value::U32(70000)).encode());
+        bytes.extend(Instruction::Halt.encode());
+        let mut cpu = setup_cpu(&bytes);
+
+        let _ = cpu.step().unwrap();
+        assert_eq!(cpu.flags().comparison(), Comparison::Gt);
+
+        let _ = cpu.step().unwrap();
+        assert_eq!(cpu.flags().comparison(), Comparison::Lt);
+
+        let _ = cpu.step().unwrap();
+        assert_eq!(cpu.flags().comparison(), Comparison::Eq);
+        assert!(cpu.flags().is_set(Flags::EQ | Flags::ZERO));
+    }
+
+    #[test]
+    fn cmp_signed() {
+        setup_logger();
+
+        let mut bytes = Instruction::CmpVal(Value::I32(-1), Value::I32(1)).encode();
+        bytes.extend(Instruction::CmpVal(Value::U32(u32::MAX), Value::U32(1)).encode());
+        bytes.extend(Instruction::CmpVal(Value::I32(-1), Value::U8(1)).encode());
+        bytes.extend(Instruction::Halt.encode());
+        let mut cpu = setup_cpu(&bytes);
+
+        let _ = cpu.step().unwrap();
+        assert_eq!(cpu.flags.comparison(), Comparison::Lt);
+
+        // the same bits compared unsigned
+        let _ = cpu.step().unwrap();
+        assert_eq!(cpu.flags.comparison(), Comparison::Gt);
+
+        let _ = cpu.step().unwrap();
+        assert_eq!(cpu.flags.comparison(), Comparison::Lt);
+    }
+
+    #[test]
+    fn float_arithmetic() {
+        setup_logger();
+
+        let mut bytes = Instruction::MovRegNum(Register::R1, Value::F32(1.5)).encode();
+        bytes.extend(Instruction::MovRegNum(Register::R2, Value::F32(2.5)).encode());
+        bytes.extend(Instruction::AddF32RegReg(Register::R1, Register::R2).encode());
+        bytes.extend(Instruction::MovRegNum(Register::R3, Value::U32(3.0_f32.to_bits())).encode());
+        bytes.extend(Instruction::MulF32RegReg(Register::R3, Register::R2).encode());
+        bytes.extend(Instruction::Halt.encode());
+        let mut cpu = setup_cpu(&bytes);
+        cpu.execute();
+
+        assert_eq!(cpu.registers[Register::R1], 4.0_f32.to_bits());
+        assert_eq!(cpu.registers[Register::R3], 7.5_f32.to_bits());
+        // the source register of a float op is left as it was
+        assert_eq!(cpu.registers[Register::R2], 2.5_f32.to_bits());
+    }
+
+    #[test]
+    fn cmp_reg_num() {
+        setup_logger();
+
+        let mut bytes = Instruction::MovRegNum(Register::R1, Value::U8(20)).encode();
+        bytes.extend(Instruction::CmpRegNum(Register::R1, Value::U8(10)).encode());
+        bytes.extend(Instruction::CmpRegNum(Register::R1, Value::U32(20)).encode());
+        bytes.extend(Instruction::CmpRegNum(Register::R1, Value::I32(-1)).encode());
+        bytes.extend(Instruction::Halt.encode());
+        let mut cpu = setup_cpu(&bytes);
+
+        let _ = cpu.step().unwrap();
+        let _ = cpu.step().unwrap();
+        assert_eq!(cpu.flags.comparison(), Comparison::Gt);
+
+        let _ = cpu.step().unwrap();
+        assert_eq!(cpu.flags.comparison(), Comparison::Eq);
+
+        let _ = cpu.step().unwrap();
+        assert_eq!(cpu.flags.comparison(), Comparison::Gt);
+    }
+
+    #[test]
+    fn cmp_reg_num_f32() {
+        setup_logger();
+
+        let mut bytes = Instruction::MovRegNum(Register::R1, Value::F32(-1.5)).encode();
+        bytes.extend(Instruction::CmpRegNum(Register::R1, Value::F32(2.0)).encode());
+        bytes.extend(Instruction::CmpRegNum(Register::R1, Value::F32(-2.0)).encode());
+        bytes.extend(Instruction::MovRegNum(Register::R1, Value::F32(0.0)).encode());
+        bytes.extend(Instruction::CmpRegNum(Register::R1, Value::F32(-0.0)).encode());
+        bytes.extend(Instruction::CmpRegNum(Register::R1, Value::F32(f32::NAN)).encode());
+        bytes.extend(Instruction::Halt.encode());
+        let mut cpu = setup_cpu(&bytes);
+
+        // the sign bit would make -1.5 the larger number if the bits were compared
+        let _ = cpu.step().unwrap();
+        let _ = cpu.step().unwrap();
+        assert_eq!(cpu.flags.comparison(), Comparison::Lt);
+
+        let _ = cpu.step().unwrap();
+        assert_eq!(cpu.flags.comparison(), Comparison::Gt);
+
+        let _ = cpu.step().unwrap();
+        let _ = cpu.step().unwrap();
+        assert_eq!(cpu.flags.comparison(), Comparison::Eq);
+
+        let _ = cpu.step().unwrap();
+        assert_eq!(cpu.flags.bits(), Flags::NE);
+    }
+
+    #[test]
+    fn repeated_cmp() {
+        setup_logger();
+
+        let mut bytes = vec![OpCode::MovRegU8 as u8, Register::R2 as u8, 3];
+        for _ in 0..6 {
+            bytes.extend([OpCode::CmpReg as u8, Register::R1 as u8, Register::R2 as u8]);
+            bytes.extend([OpCode::CmpReg as u8, Register::R1 as u8, Register::R2 as u8]);
+            bytes.extend([OpCode::IncReg as u8, Register::R1 as u8]);
+        }
+        bytes.push(OpCode::Halt as u8);
+
+        let mut cpu = setup_cpu(&bytes);
+
+        while cpu.step().unwrap().is_continue() {
+            let inst = cpu.current_instruction().unwrap();
+            if inst != Instruction::IncReg(Register::R1) {
+                continue;
+            }
+
+            // the inc comes right after the second compare of the current values
+            let (r1, r2) = (cpu.registers[Register::R1], cpu.registers[Register::R2]);
+            let expected = match r1.cmp(&r2) {
+                core::cmp::Ordering::Equal => Comparison::Eq,
+                core::cmp::Ordering::Greater => Comparison::Gt,
+                core::cmp::Ordering::Less => Comparison::Lt,
+            };
+            assert_eq!(cpu.flags.comparison(), expected);
+        }
+
+        assert_eq!(cpu.flags.comparison(), Comparison::Gt);
+    }
+
+    #[test]
+    fn flags_bitset() {
+        setup_logger();
+
+        let mut bytes = Instruction::CmpVal(Value::U8(5), Value::U8(3)).encode();
+        bytes.extend(Instruction::CmpVal(Value::U8(3), Value::U8(3)).encode());
+        bytes.extend(Instruction::Halt.encode());
+        let mut cpu = setup_cpu(&bytes);
+
+        let _ = cpu.step().unwrap();
+        assert!(cpu.flags.is_set(Flags::GT | Flags::NE));
+        assert!(!cpu.flags.any(Flags::EQ | Flags::LT | Flags::ZERO));
+        assert_eq!(cpu.flags.comparison(), Comparison::Gt);
+
+        let _ = cpu.step().unwrap();
+        assert!(cpu.flags.is_set(Flags::EQ | Flags::ZERO));
+        assert!(!cpu.flags.any(Flags::NE | Flags::GT | Flags::LT));
+    }
+
+    #[test]
+    fn jump() {}
+    #[test]
+    fn jump_ge() {}
+    #[test]
+    fn jump_gte() {}
+    #[test]
+    fn jump_lt() {}
+    #[test]
+    fn jump_lte() {}
+
+    /// runs `cmp` followed by `jump` and reports whether the jump left the fall-through path
+    fn jump_taken(cmp: &[u8], jump: OpCode) -> (Comparison, bool) {
+        let mut bytes = cmp.to_vec();
+        bytes.extend([jump as u8, 100, 0, 0, 0, OpCode::Halt as u8]);
+        let fall_through = bytes.len() as u32 - 1;
+
+        let mut cpu = setup_cpu(&bytes);
+
+        while cpu.registers[Register::IP] < cmp.len() as u32 {
+            let _ = cpu.step().unwrap();
+        }
+        let cmp = cpu.flags.comparison();
+
+        let _ = cpu.step().unwrap();
+
+        (cmp, cpu.registers[Register::IP] != fall_through)
+    }
+
+    #[test]
+    fn conditional_jump_matrix() {
+        setup_logger();
+
+        let outcomes = [
+            (1, 2, Comparison::Lt),
+            (2, 2, Comparison::Eq),
+            (3, 2, Comparison::Gt),
+        ];
+
+        // whether each jump is taken after a Lt, Eq and Gt comparison respectively
+        let jumps = [
+            (OpCode::Jump, [true, true, true]),
+            (OpCode::JumpGe, [false, false, true]),
+            (OpCode::JumpGte, [false, true, true]),
+            (OpCode::JumpLe, [true, false, false]),
+            (OpCode::JumpLte, [true, true, false]),
+            (OpCode::JumpEq, [false, true, false]),
+            (OpCode::JumpNe, [true, false, true]),
+        ];
+
+        for (i, (left, right, expected_cmp)) in outcomes.into_iter().enumerate() {
+            let cmp_reg = [
+                OpCode::MovRegU8 as u8,
+                Register::R1 as u8,
+                left,
+                OpCode::MovRegU8 as u8,
+                Register::R2 as u8,
+                right,
+                OpCode::CmpReg as u8,
+                Register::R1 as u8,
+                Register::R2 as u8,
+            ];
+            let cmp_val = [OpCode::CmpU8 as u8, left, right];
+
+            for (jump, taken) in jumps {
+                for cmp in [&cmp_reg as &[u8], &cmp_val] {
+                    let (cmp_result, was_taken) = jump_taken(cmp, jump);
+
+                    assert_eq!(cmp_result, expected_cmp, "cmp {left}, {right} via {cmp:?}");
+                    assert_eq!(
+                        was_taken, taken[i],
+                        "{jump:?} after cmp {left}, {right} via {cmp:?}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn jump_ne_loop() {
+        setup_logger();
+
+        let mut cpu = setup_cpu(&[
+            OpCode::MovRegU8 as u8,
+            Register::R1 as u8,
+            0,
+            OpCode::MovRegU8 as u8,
+            Register::R2 as u8,
+            5,
+            OpCode::IncReg as u8,
+            Register::R1 as u8,
+            OpCode::CmpReg as u8,
+            Register::R1 as u8,
+            Register::R2 as u8,
+            OpCode::JumpNe as u8,
+            6,
+            0,
+            0,
+            0,
+            OpCode::Halt as u8,
+        ]);
+
+        cpu.execute();
+
+        assert_eq!(cpu.registers[Register::R1], 5);
+    }
+
+    #[test]
+    fn jump_lands_on_target() {
+        setup_logger();
+
+        let mut bytes = Instruction::Jump(Address::from(10)).encode();
+        bytes.extend(Instruction::JumpNe(Address::from(0)).encode());
+        bytes.extend(Instruction::Halt.encode());
+
+        let mut cpu = setup_cpu(&bytes);
+        let _ = cpu.step().unwrap();
+        assert_eq!(cpu.registers[Register::IP], 10);
+
+        // the flags start out equal so jumpne falls through to the halt
+        cpu.registers[Register::IP] = 5;
+        let _ = cpu.step().unwrap();
+        assert_eq!(cpu.registers[Register::IP], 10);
+    }
+
+    #[test]
+    fn jump_reg() {
+        setup_logger();
+
+        let mut bytes = Instruction::MovRegNum(Register::R1, Value::U8(12)).encode();
+        bytes.extend(Instruction::JumpReg(Register::R1).encode());
+        bytes.extend(Instruction::Halt.encode());
+
+        let mut mem = CpuMemory::default();
+        mem.write_bytes(Address::from(100), bytes.as_slice())
+            .unwrap();
+        let mut cpu = Cpu::new(mem, 100, u16::MAX as u32, 0.into());
+
+        let _ = cpu.step().unwrap();
+        let _ = cpu.step().unwrap();
+
+        // relative to the program start like every other jump
+        assert_eq!(cpu.registers[Register::IP], 112);
+    }
+
+    #[test]
+    fn call() {}
+
+    #[test]
+    fn load() {}
+
+    #[test]
+    fn store_reg() {
+        setup_logger();
+
+        let mut cpu = setup_cpu(&[
+            OpCode::MovRegU8 as u8,
+            Register::R1 as u8,
+            9,
+            OpCode::StoreReg as u8,
+            10,
+            0,
+            0,
+            0,
+            Register::R1 as u8,
+            OpCode::Halt as u8,
+        ]);
+
+        cpu.execute();
+
+        assert_eq!(cpu.memory.read_u32(Address::from(10)).unwrap(), 9);
+    }
+
+    #[test]
+    fn store_val() {
+        setup_logger();
+
+        let mut cpu = setup_cpu(&[OpCode::StoreU8 as u8, 5, 0, 0, 0, 10]);
+        trace!("{:?}", cpu.memory.get(Address::from(0)..10.into()));
+
+        let _ = cpu.step().unwrap();
+
+        trace!("{:?}", cpu.memory.get(Address::from(0)..10.into()));
+
+        assert!(cpu.memory.read(Address::from(5)).unwrap() == 10);
+    }
+
+    #[test]
+    fn store_load_indirect() {
+        setup_logger();
+
+        let mut bytes = Instruction::MovRegNum(Register::R2, Value::U8(100)).encode();
+        bytes.extend(Instruction::MovRegNum(Register::R1, Value::U16(1234)).encode());
+        bytes.extend(Instruction::StoreIndirect(Register::R2, Register::R1).encode());
+        bytes.extend(Instruction::LoadIndirect(Register::R3, Register::R2).encode());
+        bytes.push(OpCode::Halt as u8);
+
+        let mut cpu = setup_cpu(&bytes);
+        cpu.execute();
+
+        assert_eq!(cpu.memory.read_u32(Address::from(100)).unwrap(), 1234);
+        assert_eq!(cpu.registers[Register::R3], cpu.registers[Register::R1]);
+    }
+
+    #[test]
+    fn mov_halves() {
+        setup_logger();
+
+        let mut bytes = Instruction::MovRegLowU16(Register::R1, 0xEEEE).encode();
+        bytes.extend(Instruction::MovRegHighU16(Register::R1, 0xAAAA).encode());
+        bytes.extend(Instruction::MovRegNum(Register::R2, Value::U32(0x1234_5678)).encode());
+        bytes.extend(Instruction::MovRegLowU16(Register::R2, 0).encode());
+        bytes.extend(Instruction::Halt.encode());
+
+        let mut cpu = setup_cpu(&bytes);
+        cpu.execute();
+
+        assert_eq!(cpu.registers[Register::R1], 0xAAAAEEEE);
+        assert_eq!(cpu.registers[Register::R2], 0x1234_0000);
+    }
+
+    #[test]
+    fn run_traced() {
+        setup_logger();
+
+        let program = [
+            Instruction::MovRegNum(Register::R1, Value::U8(5)),
+            Instruction::MovRegNum(Register::R2, Value::U8(7)),
+            Instruction::AddRegReg(Register::R1, Register::R2),
+            Instruction::Halt,
+        ];
+        let bytes = program
+            .iter()
+            .flat_map(Instruction::encode)
+            .collect::<Vec<_>>();
+        let mut cpu = setup_cpu(&bytes);
+
+        let trace = cpu.run_traced().unwrap();
+        assert_eq!(trace.len(), 4);
+
+        let addresses = trace
+            .iter()
+            .map(|entry| entry.address.0)
+            .collect::<Vec<_>>();
+        assert_eq!(addresses, [0, 3, 6, 9]);
+        let insts = trace.iter().map(|entry| entry.inst).collect::<Vec<_>>();
+        assert_eq!(insts, program);
+
+        let r1_r2 = |entry: &TraceEntry| {
+            (
+                entry.registers[Register::R1 as usize],
+                entry.registers[Register::R2 as usize],
+            )
+        };
+        assert_eq!(r1_r2(&trace[0]), (5, 0));
+        assert_eq!(r1_r2(&trace[1]), (5, 7));
+        assert_eq!(r1_r2(&trace[2]), (12, 7));
+        assert_eq!(r1_r2(&trace[3]), (12, 7));
+        assert_eq!(trace[2].registers[Register::IP as usize], 9);
+
+        assert!(format!("{}", trace[2]).starts_with("0x0006  add r1, r2 ip=9 sp=65535"));
+    }
+
+    #[test]
+    fn set_byte() {
+        setup_logger();
+
+        let mut bytes = Instruction::MovRegNum(Register::R1, Value::U32(0x1122_3344)).encode();
+        bytes.extend(Instruction::SetByte(Register::R1, 0xff).encode());
+        bytes.extend(Instruction::Halt.encode());
+
+        let mut cpu = setup_cpu(&bytes);
+        cpu.execute();
+
+        assert_eq!(cpu.registers[Register::R1], 0x1122_33ff);
+    }
+
+    #[test]
+    fn mem_copy() {
+        setup_logger();
+
+        let mut bytes = Instruction::MovRegNum(Register::R1, Value::U8(4)).encode();
+        bytes.extend(
+            Instruction::MemCopy(Address::from(200), Address::from(100), Register::R1).encode(),
+        );
+        // overlapping, the destination starts inside the source
+        bytes.extend(Instruction::MovRegNum(Register::R1, Value::U8(6)).encode());
+        bytes.extend(
+            Instruction::MemCopy(Address::from(302), Address::from(300), Register::R1).encode(),
+        );
+        bytes.extend(Instruction::Halt.encode());
+
+        let mut cpu = setup_cpu(&bytes);
+        cpu.memory
+            .write_bytes(Address::from(100), &[1u8, 2, 3, 4][..])
+            .unwrap();
+        cpu.memory
+            .write_bytes(Address::from(300), &[1u8, 2, 3, 4, 5, 6][..])
+            .unwrap();
+        cpu.execute();
+
+        assert_eq!(
+            cpu.read_range(Address::from(200)..Address::from(204))
+                .unwrap(),
+            [1, 2, 3, 4]
+        );
+        assert_eq!(
+            cpu.read_range(Address::from(300)..Address::from(308))
+                .unwrap(),
+            [1, 2, 1, 2, 3, 4, 5, 6]
+        );
+    }
+
+    #[test]
+    fn mem_set() {
+        setup_logger();
+
+        let mut bytes = Instruction::MovRegNum(Register::R1, Value::U8(4)).encode();
+        bytes.extend(Instruction::MemSet(Address::from(200), 0xaa, Register::R1).encode());
+        // r2 is still 0, so this one writes nothing
+        bytes.extend(Instruction::MemSet(Address::from(300), 0xbb, Register::R2).encode());
+        bytes.extend(Instruction::Halt.encode());
+
+        let mut cpu = setup_cpu(&bytes);
+        cpu.memory.write(Address::from(300), 7).unwrap();
+        cpu.execute();
+
+        assert_eq!(
+            cpu.read_range(Address::from(199)..Address::from(205))
+                .unwrap(),
+            [0, 0xaa, 0xaa, 0xaa, 0xaa, 0]
+        );
+        assert_eq!(cpu.memory.read(Address::from(300)).unwrap(), 7);
+    }
+
+    #[test]
+    fn mem_set_past_capacity() {
+        setup_logger();
+
+        let len = 2 << 20;
+        let mut bytes = Instruction::MovRegNum(Register::R1, Value::U32(len)).encode();
+        bytes.extend(Instruction::MemSet(Address::from(100), 0xff, Register::R1).encode());
+        let mut cpu = setup_cpu(&bytes);
+
+        let _ = cpu.step().unwrap();
+        assert!(matches!(
+            cpu.step(),
+            Err(Error::MemError(memory::Error::InvalidAddress(end))) if end == 100 + len - 1
+        ));
+        assert!(cpu
+            .read_range(Address::from(100)..Address::from(PAGE_SIZE * 2))
+            .unwrap()
+            .iter()
+            .all(|byte| *byte == 0));
+    }
+
+    #[test]
+    fn empty_mem_set_in_program() {
+        setup_logger();
+
+        // r2 is 0, so the fill touches nothing and is not a write into the program
+        let mut bytes = Instruction::MemSet(Address::from(0), 0xff, Register::R2).encode();
+        bytes.extend(Instruction::Halt.encode());
+        let mut cpu = Cpu::new(CpuMemory::default(), 0, u16::MAX as u32, 0.into());
+        cpu.load_program(&bytes).unwrap();
+
+        assert!(cpu.step().is_ok());
+        assert_eq!(
+            cpu.memory.read(Address::from(0)).unwrap(),
+            OpCode::MemSet as u8
+        );
+    }
+
+    #[test]
+    fn mem_set_out_of_range() {
+        setup_logger();
+
+        let mut bytes = Instruction::MovRegNum(Register::R1, Value::U8(8)).encode();
+        bytes.extend(Instruction::MemSet(Address::from(250), 0xff, Register::R1).encode());
+        let mut cpu = Cpu::new(Stack::<256>::new(), 0, 255, 0.into());
+        cpu.load_program(&bytes).unwrap();
+
+        let _ = cpu.step().unwrap();
+        assert!(matches!(
+            cpu.step(),
+            Err(Error::MemError(memory::Error::InvalidAddress(257)))
+        ));
+        assert_eq!(cpu.memory().read(250.into()).unwrap(), 0);
+    }
+
+    #[test]
+    fn peek_poke() {
+        setup_logger();
+
+        let mut bytes = Instruction::Load(Register::R1, Address::from(100)).encode();
+        bytes.extend(Instruction::AddRegReg(Register::R1, Register::R2).encode());
+        bytes.extend(Instruction::StoreReg(Address::from(104), Register::R1).encode());
+        bytes.extend(Instruction::Halt.encode());
+
+        let mut cpu = setup_cpu(&bytes);
+        cpu.poke_u32(Address::from(100), 37).unwrap();
+        cpu.set_register(Register::R2, 5);
+        cpu.execute();
+
+        assert_eq!(cpu.peek_u32(Address::from(104)).unwrap(), 42);
+        assert_eq!(cpu.get_register(Register::R1), 42);
+    }
+
+    #[test]
+    fn store_reg_indexed() {
+        setup_logger();
+
+        let mut bytes = Instruction::MovRegNum(Register::R1, Value::U8(8)).encode();
+        bytes.extend(Instruction::MovRegNum(Register::R2, Value::U16(4321)).encode());
+        bytes.extend(
+            Instruction::StoreRegIndexed(Address::from(100), Register::R1, Register::R2).encode(),
+        );
+        bytes.extend(Instruction::Load(Register::R3, Address::from(108)).encode());
+        bytes.push(OpCode::Halt as u8);
+
+        let mut cpu = setup_cpu(&bytes);
+        cpu.execute();
+
+        assert_eq!(cpu.memory.read_u32(Address::from(108)).unwrap(), 4321);
+        assert_eq!(cpu.registers[Register::R3], 4321);
+    }
+
+    #[test]
+    fn interrupt() {
+        setup_logger();
+
+        let mut mem = CpuMemory::default();
+
+        let bytes: &[u8] = &[
+            OpCode::Interrupt as u8,
+            0,
+            0,
+            0,
+            1,
+            OpCode::IncReg as u8,
+            Register::R1 as u8,
+            OpCode::Halt as u8,
+        ];
+
+        mem.write_bytes(Address::from(0), bytes as &[u8]).unwrap();
+
+        let mut cpu = Cpu::new(mem, 0, u16::MAX as u32, 10.into());
+
+        for i in 0..3 {
+            let hlt = cpu.step().unwrap();
+            info!(?hlt);
+            if i == 3 {
+                assert!(hlt == ControlFlow::Break(()));
+            }
+        }
+    }
+
+    #[test]
+    fn reset() {
+        setup_logger();
+
+        let mut bytes = Instruction::MovRegNum(Register::R1, Value::U8(5)).encode();
+        bytes.extend(Instruction::PushReg(Register::R1).encode());
+        bytes.extend(Instruction::CmpReg(Register::R1, Register::R2).encode());
+        bytes.push(OpCode::Halt as u8);
+
+        let mut cpu = setup_cpu(&bytes);
+        let initial = cpu.registers_snapshot();
+        cpu.execute();
+
+        cpu.reset(0, u16::MAX as u32, false);
+
+        assert_eq!(cpu.registers_snapshot(), initial);
+        assert_eq!(cpu.flags.comparison(), Comparison::Eq);
+        // the program is still there to run again
+        cpu.execute();
+        assert_eq!(cpu.registers[Register::R1], 5);
+
+        cpu.reset(0, u16::MAX as u32, true);
+
+        assert_eq!(cpu.memory.read(Address::from(0)).unwrap(), 0);
+    }
+
+    #[test]
+    fn interrupt_return() {
+        setup_logger();
+
+        let mut bytes = Instruction::MovRegNum(Register::R1, Value::U8(5)).encode();
+        bytes.extend(Instruction::Interrupt(0).encode());
+        bytes.push(OpCode::Halt as u8);
+        // the handler, at 9
+        bytes.extend(Instruction::MovRegNum(Register::R1, Value::U8(99)).encode());
+        bytes.extend(Instruction::MovRegNum(Register::R5, Value::U8(42)).encode());
+        bytes.extend(Instruction::IRet.encode());
+
+        let mut cpu = setup_cpu(&bytes);
+        cpu.memory.write_u32(Address::from(100), 9).unwrap();
+        cpu.interrupt_table = 100.into();
+
+        cpu.execute();
+
+        assert_eq!(cpu.registers[Register::R1], 5);
+        assert_eq!(cpu.registers[Register::R5], 42);
+        assert_eq!(cpu.registers[Register::IP], 9);
+        assert_eq!(cpu.registers[Register::SP], u16::MAX as u32);
+        assert_eq!(cpu.interrupt_depth, 0);
+    }
+
+    #[test]
+    fn iret_outside_interrupt() {
+        setup_logger();
+
+        let mut cpu = setup_cpu(&Instruction::IRet.encode());
+
+        assert!(matches!(cpu.step(), Err(Error::NotInInterrupt)));
+    }
+
+    #[test]
+    fn interrupt_reg() {
+        setup_logger();
+
+        let mut mem = CpuMemory::default();
+
+        let bytes: &[u8] = &[
+            OpCode::MovRegU8 as u8,
+            Register::R1 as u8,
+            6,
+            OpCode::InterruptReg as u8,
+            Register::R1 as u8,
+            OpCode::IncReg as u8,
+            Register::R1 as u8,
+            OpCode::Halt as u8,
+        ];
+
+        mem.write_bytes(Address::from(0), bytes as &[u8]).unwrap();
+
+        let mut cpu = Cpu::new(mem, 0, u16::MAX as u32, 10.into());
+
+        for i in 0..3 {
+            let hlt = cpu.step().unwrap();
+            info!(?hlt);
+            if i == 3 {
+                assert!(hlt == ControlFlow::Break(()));
+            }
+        }
+    }
+
+    #[test]
+    fn load_empty_program() {
+        setup_logger();
+
+        let mut cpu = Cpu::new(CpuMemory::default(), 0, u16::MAX as u32, 0.into());
+
+        assert!(matches!(cpu.load_program(&[]), Err(Error::EmptyProgram)));
+    }
+
+    #[test]
+    fn load_program_bounds() {
+        setup_logger();
+
+        let mut cpu = Cpu::new(CpuMemory::default(), 16, 32, 0.into());
+
+        assert!(matches!(
+            cpu.load_program(&[OpCode::Halt as u8; 17]),
+            Err(Error::MemError(memory::Error::InvalidAddress(32)))
+        ));
+        // right up to the stack is fine
+        assert!(cpu.load_program(&[OpCode::Halt as u8; 16]).is_ok());
+
+        // and so is a program above the stack, which grows away from it
+        let mut cpu = Cpu::new(CpuMemory::default(), 64, 32, 0.into());
+        assert!(cpu.load_program(&[OpCode::Halt as u8; 100]).is_ok());
+
+        // as long as it fits in memory
+        let mut cpu = Cpu::new(Stack::<64>::new(), 40, 32, 0.into());
+        assert!(matches!(
+            cpu.load_program(&[OpCode::Halt as u8; 40]),
+            Err(Error::MemError(memory::Error::InvalidAddress(64)))
+        ));
+        assert_eq!(cpu.memory().read(40.into()).unwrap(), 0);
+    }
+
+    #[test]
+    fn stack_overflow() {
+        setup_logger();
+
+        // recurses forever, every call pushes a frame
+        let program = Instruction::Call(Address::from(0)).encode();
+        let mut cpu = Cpu::new(Stack::<256>::new(), 0, 255, 0.into());
+        cpu.load_program(&program).unwrap();
+
+        let err = loop {
+            if let Err(e) = cpu.step() {
+                break e;
+            }
+        };
+        assert!(matches!(err, Error::StackOverflow));
+        assert!(cpu.registers[Register::SP] >= program.len() as u32);
+        assert_eq!(
+            cpu.memory()
+                .get(0.into()..(program.len() as u32).into())
+                .unwrap(),
+            program
+        );
+    }
+
+    #[test]
+    fn call_depth_exceeded() {
+        setup_logger();
+
+        // recurses with no base case
+        let mut cpu = setup_cpu(&Instruction::Call(Address::from(0)).encode());
+        cpu.set_max_call_depth(Some(10));
+
+        let err = loop {
+            if let Err(e) = cpu.step() {
+                break e;
+            }
+        };
+        assert!(matches!(err, Error::CallDepthExceeded));
+        assert_eq!(cpu.call_depth(), 10);
+
+        cpu.reset(0, u16::MAX as u32, false);
+        assert_eq!(cpu.call_depth(), 0);
+    }
+
+    #[test]
+    fn coverage() {
+        setup_logger();
+
+        // the jump skips the two incs, they are never run
+        let program = [
+            Instruction::MovRegNum(Register::R1, Value::U8(1)),
+            Instruction::Jump(Address::from(12)),
+            Instruction::IncReg(Register::R1),
+            Instruction::IncReg(Register::R1),
+            Instruction::Halt,
+        ];
+        let mut offsets = Vec::new();
+        let mut offset = 0;
+        for inst in &program {
+            offsets.push(Address::from(offset));
+            offset += inst.size() as u32;
+        }
+
+        let bytes = program
+            .iter()
+            .flat_map(Instruction::encode)
+            .collect::<Vec<_>>();
+        let mut cpu = Cpu::new(CpuMemory::default(), 100, u16::MAX as u32, 0.into());
+        cpu.load_program(&bytes).unwrap();
+        assert_eq!(cpu.coverage(), None);
+        cpu.set_coverage(true);
+        cpu.execute();
+
+        assert_eq!(cpu.registers[Register::R1], 1);
+        assert_eq!(
+            cpu.coverage()
+                .unwrap()
+                .iter()
+                .map(|addr| addr.0)
+                .collect::<Vec<_>>(),
+            [100, 103, 112]
+        );
+        assert_eq!(
+            cpu.unreached(offsets.clone()).unwrap(),
+            [Address::from(8), Address::from(10)]
+        );
+
+        cpu.reset(100, u16::MAX as u32, false);
+        assert!(cpu.coverage().unwrap().is_empty());
+
+        cpu.set_coverage(false);
+        assert_eq!(cpu.unreached(offsets), None);
+    }
+
+    #[test]
+    fn write_protect() {
+        setup_logger();
+
+        // the store lands on its own opcode
+        let mut bytes = Instruction::StoreVal(Address::from(0), Value::U8(0xff)).encode();
+        bytes.extend(Instruction::Halt.encode());
+        let mut cpu = Cpu::new(CpuMemory::default(), 0, u16::MAX as u32, 0.into());
+        cpu.load_program(&bytes).unwrap();
+
+        assert!(matches!(cpu.step(), Err(Error::WriteProtected(Address(0)))));
+        assert_eq!(
+            cpu.memory.read(Address::from(0)).unwrap(),
+            OpCode::StoreU8 as u8
+        );
+
+        cpu.set_code_writable(true);
+        cpu.set_register(Register::IP, 0);
+        let _ = cpu.step().unwrap();
+        assert_eq!(cpu.memory.read(Address::from(0)).unwrap(), 0xff);
+    }
+
+    #[test]
+    fn empty_mem_copy_in_program() {
+        setup_logger();
+
+        let mut bytes =
+            Instruction::MemCopy(Address::from(0), Address::from(100), Register::R2).encode();
+        bytes.extend(Instruction::Halt.encode());
+        let mut cpu = Cpu::new(CpuMemory::default(), 0, u16::MAX as u32, 0.into());
+        cpu.load_program(&bytes).unwrap();
+
+        assert!(cpu.step().is_ok());
+        assert_eq!(
+            cpu.memory.read(Address::from(0)).unwrap(),
+            OpCode::MemCopy as u8
+        );
+    }
+
+    #[test]
+    fn call_saving() {
+        setup_logger();
+
+        // only r1 is saved, so the callee's write to r2 outlives the call
+        let mut bytes = Instruction::MovRegNum(Register::R1, Value::U8(1)).encode();
+        bytes.extend(Instruction::MovRegNum(Register::R2, Value::U8(2)).encode());
+        bytes.extend(
+            Instruction::CallSaving(Address::from(13), RegisterMask::from_iter([Register::R1]))
+                .encode(),
+        );
+        bytes.extend(Instruction::Halt.encode());
+        assert_eq!(bytes.len(), 13);
+        bytes.extend(Instruction::MovRegNum(Register::R1, Value::U8(10)).encode());
+        bytes.extend(Instruction::MovRegNum(Register::R2, Value::U8(20)).encode());
+        bytes.extend(Instruction::Ret.encode());
+        let mut cpu = setup_cpu(&bytes);
+        let sp = cpu.registers[Register::SP];
+
+        cpu.execute();
+
+        assert_eq!(cpu.registers[Register::R1], 1);
+        assert_eq!(cpu.registers[Register::R2], 20);
+        assert_eq!(cpu.registers[Register::SP], sp);
+        assert_eq!(cpu.call_depth(), 0);
+    }
+
+    #[test]
+    fn jump_past_address_space() {
+        setup_logger();
+
+        let mut cpu = setup_cpu(&Instruction::Halt.encode());
+        cpu.program_start = Address(u32::MAX - 4);
+
+        let res = cpu.execute_instruction(Instruction::Jump(Address::from(8)));
+        assert!(matches!(res, Err(Error::StackOverflow)));
+    }
+
+    #[test]
+    fn load_image() {
+        setup_logger();
+
+        // the entry skips the first mov
+        let mut code = Instruction::MovRegNum(Register::R1, Value::U8(9)).encode();
+        code.extend(Instruction::IncReg(Register::R1).encode());
+        code.extend(Instruction::Halt.encode());
+        let bytes = Image::new(Address::from(3), &code).encode();
+
+        let mut cpu = Cpu::load_image(&bytes).unwrap();
+        assert_eq!(cpu.registers[Register::IP], 3);
+        cpu.execute();
+        assert_eq!(cpu.registers[Register::R1], 1);
+
+        // raw bytecode has no header
+        assert!(matches!(
+            Cpu::load_image(&[OpCode::Halt as u8; 16]),
+            Err(Error::ImageError(image::Error::InvalidMagic))
+        ));
+    }
+
+    #[test]
+    fn stack_underflow() {
+        setup_logger();
+
+        let mut cpu = setup_cpu(&Instruction::PopReg(Register::R1).encode());
+
+        assert!(matches!(cpu.step(), Err(Error::StackUnderflow)));
+        assert_eq!(cpu.registers[Register::SP], u16::MAX as u32);
+    }
+
+    #[test]
+    fn push_pop_all() {
+        setup_logger();
+
+        let mut bytes = Vec::new();
+        for (i, register) in Register::GENERAL.into_iter().enumerate() {
+            bytes
+                .extend(Instruction::MovRegNum(register, Value::U32(i as u32 * 1000 + 1)).encode());
+        }
+        bytes.extend(Instruction::PushAll.encode());
+        for register in Register::GENERAL {
+            bytes.extend(Instruction::MovRegNum(register, Value::U8(0)).encode());
+        }
+        bytes.extend(Instruction::PopAll.encode());
+        bytes.extend(Instruction::Halt.encode());
+
+        let mut cpu = setup_cpu(&bytes);
+        let sp = cpu.registers[Register::SP];
+        cpu.execute();
+
+        for (i, register) in Register::GENERAL.into_iter().enumerate() {
+            assert_eq!(cpu.registers[register], i as u32 * 1000 + 1, "{register}");
+        }
+        assert_eq!(cpu.registers[Register::SP], sp);
+    }
+
+    fn push_pop_on<M: Memory>(memory: M) -> Cpu<M> {
+        let mut bytes = Instruction::MovRegNum(Register::R1, Value::U16(0x1234)).encode();
+        bytes.extend(Instruction::PushReg(Register::R1).encode());
+        bytes.extend(Instruction::PopReg(Register::R2).encode());
+        bytes.extend(Instruction::Halt.encode());
+
+        let mut cpu = Cpu::new(memory, 0, 4095, 0.into());
+        cpu.load_program(&bytes).unwrap();
+        cpu.execute();
+
+        cpu
+    }
+
+    #[test]
+    fn step_hook() {
+        setup_logger();
+
+        let program = [
+            Instruction::MovRegNum(Register::R1, Value::U8(1)),
+            Instruction::MovRegNum(Register::R2, Value::U8(2)),
+            Instruction::AddRegReg(Register::R1, Register::R2),
+            Instruction::IncReg(Register::R1),
+            Instruction::Halt,
+        ];
+        let bytes = program
+            .iter()
+            .flat_map(Instruction::encode)
+            .collect::<Vec<_>>();
+        let mut cpu = setup_cpu(&bytes);
+
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let hook_seen = seen.clone();
+        cpu.set_step_hook(Box::new(move |inst, registers| {
+            hook_seen
+                .borrow_mut()
+                .push((*inst, registers[Register::R1]));
+        }));
+        cpu.execute();
+
+        let seen = seen.borrow();
+        assert_eq!(
+            seen.iter().map(|(inst, _)| *inst).collect::<Vec<_>>(),
+            program
+        );
+        // the hook runs before the instruction does
+        assert_eq!(seen[3].1, 3);
+        assert_eq!(cpu.registers[Register::R1], 4);
+    }
+
+    #[test]
+    fn cycles() {
+        setup_logger();
+
+        let program = [
+            Instruction::MovRegNum(Register::R1, Value::U8(1)),
+            Instruction::PushReg(Register::R1),
+            Instruction::PopReg(Register::R2),
+            Instruction::MulRegReg(Register::R1, Register::R2),
+            Instruction::Halt,
+        ];
+        let bytes = program
+            .iter()
+            .flat_map(Instruction::encode)
+            .collect::<Vec<_>>();
+        let mut cpu = setup_cpu(&bytes);
+        cpu.execute();
+
+        let expected = program
+            .iter()
+            .map(|inst| OpCode::from(inst).cost() as u64)
+            .sum::<u64>();
+        assert_eq!(expected, 1 + 3 + 3 + 2 + 1);
+        assert_eq!(cpu.cycles(), expected);
+
+        cpu.reset(0, u16::MAX as u32, false);
+        assert_eq!(cpu.cycles(), 0);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn run_benchmark() {
+        setup_logger();
+
+        let mut bytes = Instruction::IncReg(Register::R1).encode();
+        bytes.extend(Instruction::IncReg(Register::R1).encode());
+        bytes.extend(Instruction::Halt.encode());
+        let mut cpu = setup_cpu(&bytes);
+
+        let stats = cpu.run_benchmark().unwrap();
+        assert_eq!(stats.instructions, 3);
+        assert_eq!(cpu.registers[Register::R1], 2);
+        assert!(stats.instructions_per_second() > 0.0);
+    }
+
+    #[test]
+    fn dump_memory() {
+        let mut bytes = b"hello, world!\n".to_vec();
+        bytes.extend(0_u8..18);
+        let cpu = setup_cpu(&bytes);
+
+        let dump = cpu.dump_memory(Address::from(0)..Address::from(32));
+        let lines = dump.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[0],
+            "00000000  68 65 6c 6c 6f 2c 20 77  6f 72 6c 64 21 0a 00 01  |hello, world!...|"
+        );
+        assert_eq!(
+            lines[1],
+            "00000010  02 03 04 05 06 07 08 09  0a 0b 0c 0d 0e 0f 10 11  |................|"
+        );
+
+        let dump = cpu.dump_memory(Address::from(16)..Address::from(19));
+        assert_eq!(
+            dump,
+            "00000010  02 03 04                                          |...|\n"
+        );
+    }
+
+    #[test]
+    fn dump_registers() {
+        let mut cpu = setup_cpu(&[]);
+        cpu.registers[Register::R1] = 7;
+
+        let dump = cpu.dump_registers();
+
+        assert!(dump.contains("R1 7\n"));
+        assert!(dump.ends_with("CMP Eq\nCARRY false\n"));
+    }
+
+    #[test]
+    fn memory_backends() {
+        setup_logger();
+
+        let cpu = push_pop_on(Stack::<4096>::new());
+        assert_eq!(cpu.registers[Register::R2], 0x1234);
+
+        let cpu = push_pop_on(CpuMemory::default());
+        assert_eq!(cpu.registers[Register::R2], 0x1234);
+
+        let boxed: Box<dyn Memory> = Box::new(Stack::<4096>::new());
+        let cpu = push_pop_on(boxed);
+        assert_eq!(cpu.registers[Register::R2], 0x1234);
+    }
+
+    #[test]
+    fn stack_bounds() {
+        let mut stack = Stack::<4096>::new();
+
+        assert!(stack.write(4095.into(), 1).is_ok());
+        assert!(matches!(
+            stack.write(4096.into(), 1),
+            Err(memory::Error::InvalidAddress(4096))
+        ));
+        assert!(stack.read(4096.into()).is_err());
+    }
+
+    #[test]
+    fn halt() {
+        setup_logger();
+
+        let mut cpu = setup_cpu(&[OpCode::Halt as u8]);
+
+        assert!(cpu.step().unwrap() == ControlFlow::Break(()));
+    }
+
+    #[test]
+    fn current_instruction() {
+        setup_logger();
+
+        let mut cpu = setup_cpu(&[OpCode::IncReg as u8, Register::R1 as u8, OpCode::Halt as u8]);
+
+        let ip = cpu.registers_snapshot()[Register::IP as usize];
+        let inst = cpu.current_instruction().unwrap();
+
+        assert_eq!(inst, Instruction::IncReg(Register::R1));
+        assert_eq!(cpu.registers_snapshot()[Register::IP as usize], ip);
+
+        let _ = cpu.step().unwrap();
+
+        assert_eq!(cpu.registers_snapshot()[Register::IP as usize], ip + 2);
+        assert_eq!(cpu.registers_snapshot()[Register::R1 as usize], 1);
+        assert_eq!(cpu.current_instruction().unwrap(), Instruction::Halt);
+        assert_eq!(
+            cpu.read_range(Address::from(0)..Address::from(2)).unwrap(),
+            vec![OpCode::IncReg as u8, Register::R1 as u8]
+        );
+    }
+
+    #[test]
+    fn run_until_breakpoint() {
+        setup_logger();
+
+        let mut cpu = setup_cpu(&[
+            OpCode::IncReg as u8,
+            Register::R1 as u8,
+            OpCode::IncReg as u8,
+            Register::R1 as u8,
+            OpCode::IncReg as u8,
+            Register::R2 as u8,
+            OpCode::IncReg as u8,
+            Register::R3 as u8,
+            OpCode::Halt as u8,
+        ]);
+
+        cpu.add_breakpoint(Address::from(6));
+
+        assert_eq!(
+            cpu.run_until_breakpoint().unwrap(),
+            StopReason::Breakpoint(Address::from(6))
+        );
+        assert_eq!(cpu.registers[Register::IP], 6);
+        assert_eq!(cpu.registers[Register::R1], 2);
+        assert_eq!(cpu.registers[Register::R2], 1);
+        assert_eq!(cpu.registers[Register::R3], 0);
+
+        assert_eq!(cpu.run_until_breakpoint().unwrap(), StopReason::Halted);
+        assert_eq!(cpu.registers[Register::R3], 1);
+    }
+
+    #[test]
+    fn nop() {
+        setup_logger();
+
+        let mut cpu = setup_cpu(&[
+            OpCode::Nop as u8,
+            OpCode::Nop as u8,
+            OpCode::Nop as u8,
+            OpCode::Halt as u8,
+        ]);
+        let ip = cpu.registers[Register::IP];
+
+        for step in 1..=3 {
+            assert_eq!(cpu.step().unwrap(), ControlFlow::Continue(()));
+            assert_eq!(cpu.registers[Register::IP], ip + step);
+        }
+
+        assert_eq!(cpu.step().unwrap(), ControlFlow::Break(()));
+    }
+
+    #[test]
+    fn ret() {
+        setup_logger();
+
+        let mut bytes = Instruction::MovRegNum(Register::R1, Value::U8(5)).encode();
+        bytes.extend(Instruction::Call(Address::from(12)).encode());
+        let after_call = bytes.len() as u32;
+        bytes.extend(Instruction::MovRegNum(Register::R3, Value::U8(7)).encode());
+        bytes.extend(Instruction::Halt.encode());
+        // the subroutine clobbers r1, which ret restores
+        assert_eq!(bytes.len(), 12);
+        bytes.extend(Instruction::MovRegNum(Register::R1, Value::U8(99)).encode());
+        bytes.extend(Instruction::Ret.encode());
+
+        let mut cpu = setup_cpu(&bytes);
+        let (sp, fp) = (cpu.registers[Register::SP], cpu.registers[Register::FP]);
+
+        let _ = cpu.step().unwrap();
+        let _ = cpu.step().unwrap();
+        assert_eq!(cpu.registers[Register::IP], 12);
+        assert_eq!(cpu.call_depth(), 1);
+
+        let _ = cpu.step().unwrap();
+        assert_eq!(cpu.registers[Register::R1], 99);
+
+        let _ = cpu.step().unwrap();
+        assert_eq!(cpu.registers[Register::IP], after_call);
+        assert_eq!(cpu.registers[Register::R1], 5);
+        assert_eq!(cpu.registers[Register::SP], sp);
+        assert_eq!(cpu.registers[Register::FP], fp);
+        assert_eq!(cpu.call_depth(), 0);
+
+        cpu.execute();
+        assert_eq!(cpu.registers[Register::R3], 7);
+    }
+
+    #[test]
+    fn read_mem_u32() {
+        setup_logger();
+
+        let mut mem = CpuMemory::default();
+
+        let bytes: &[u8] = &[6, 0, 0, 0];
+        mem.write_bytes(Address::from(0), bytes as &[u8]).unwrap();
+
+        let cpu = Cpu::new(mem, 0, u16::MAX as u32, 0.into());
+
+        let val = cpu.memory.read_u32(Address::from(0)).unwrap();
 
         assert!(val == 6)
     }
 
+    #[test]
+    fn read_mem_u32_high_bytes() {
+        setup_logger();
+
+        let mut stack = Stack::<4096>::new();
+        stack.write_bytes(8.into(), &[1, 2, 3, 4]).unwrap();
+        assert_eq!(stack.read_u32(8.into()).unwrap(), 0x04030201);
+
+        let cpu = Cpu::new(stack, 0, 4095, 0.into());
+        assert_eq!(cpu.memory.read_u32(Address::from(8)).unwrap(), 0x04030201);
+    }
+
     // #[test]
     // fn mem_test() {
     //     setup_logger();
@@ -1,6 +1,7 @@
-use std::fmt::Display;
+use core::fmt::Display;
 
-use crate::memory;
+use crate::image;
+use crate::memory::{self, Address};
 use crate::opcodes;
 use crate::registers;
 
@@ -9,10 +10,27 @@ pub enum Error {
     MemError(memory::Error),
     OpCodeError(opcodes::Error),
     RegisterError(registers::Error),
+    ImageError(image::Error),
+    DivideByZero,
+    /// an arithmetic instruction overflowed while the cpu is in checked mode
+    Overflow,
+    EmptyProgram,
+    /// an iret outside of an interrupt handler
+    NotInInterrupt,
+    /// a push would have run into the program
+    StackOverflow,
+    /// a pop went past the start of the stack
+    StackUnderflow,
+    /// a call went deeper than the cpu's max call depth
+    CallDepthExceeded,
+    /// an instruction tried to write into the loaded program, see [`Cpu::set_code_writable`]
+    ///
+    /// [`Cpu::set_code_writable`]: crate::cpu::Cpu::set_code_writable
+    WriteProtected(Address),
 }
 
 impl Display for Error {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "{self:?}")
     }
 }
@@ -25,7 +43,17 @@ impl From<opcodes::Error> for Error {
 
 impl From<memory::Error> for Error {
     fn from(value: memory::Error) -> Self {
-        Error::MemError(value)
+        match value {
+            memory::Error::StackOverflow => Error::StackOverflow,
+            memory::Error::StackUnderflow => Error::StackUnderflow,
+            _ => Error::MemError(value),
+        }
+    }
+}
+
+impl From<image::Error> for Error {
+    fn from(value: image::Error) -> Self {
+        Error::ImageError(value)
     }
 }
 
@@ -35,6 +63,6 @@ impl From<registers::Error> for Error {
     }
 }
 
-pub type Result<T> = std::result::Result<T, Error>;
+pub type Result<T> = core::result::Result<T, Error>;
 
-impl std::error::Error for Error {}
+impl core::error::Error for Error {}
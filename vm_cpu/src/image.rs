@@ -0,0 +1,113 @@
+//! a minimal container for programs: a header naming where execution begins, followed by the
+//! code
+
+use alloc::vec::Vec;
+
+use crate::memory::Address;
+
+pub const MAGIC: [u8; 4] = *b"PHNY";
+pub const VERSION: u8 = 1;
+/// the magic, the version, then the entry and the code length as little endian u32s
+pub const HEADER_SIZE: usize = 13;
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    InvalidMagic,
+    UnsupportedVersion(u8),
+    /// the image ended before its header or code did
+    Truncated,
+    /// the entry does not point into the code
+    EntryOutOfRange(Address),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Image<'a> {
+    /// the offset into the code execution begins at
+    pub entry: Address,
+    pub code: &'a [u8],
+}
+
+impl<'a> Image<'a> {
+    pub fn new(entry: Address, code: &'a [u8]) -> Self {
+        Self { entry, code }
+    }
+
+    /// reads the header and the code it describes, anything after the code is ignored
+    pub fn parse(bytes: &'a [u8]) -> Result<Self, Error> {
+        let header = bytes.get(..HEADER_SIZE).ok_or(Error::Truncated)?;
+        if header[..4] != MAGIC {
+            return Err(Error::InvalidMagic);
+        }
+        if header[4] != VERSION {
+            return Err(Error::UnsupportedVersion(header[4]));
+        }
+
+        let entry = u32::from_le_bytes([header[5], header[6], header[7], header[8]]);
+        let len = u32::from_le_bytes([header[9], header[10], header[11], header[12]]) as usize;
+
+        let code = bytes
+            .get(HEADER_SIZE..)
+            .and_then(|rest| rest.get(..len))
+            .ok_or(Error::Truncated)?;
+
+        if entry as usize >= code.len() {
+            return Err(Error::EntryOutOfRange(entry.into()));
+        }
+
+        Ok(Self::new(entry.into(), code))
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_SIZE + self.code.len());
+        bytes.extend(MAGIC);
+        bytes.push(VERSION);
+        bytes.extend(self.entry.0.to_le_bytes());
+        bytes.extend((self.code.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(self.code);
+
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::memory::Address;
+
+    use super::{Error, Image, HEADER_SIZE};
+
+    #[test]
+    fn round_trip() {
+        let code = [1, 2, 3, 4, 5];
+        let image = Image::new(Address::from(3), &code);
+        let bytes = image.encode();
+
+        assert_eq!(bytes.len(), HEADER_SIZE + code.len());
+        assert_eq!(Image::parse(&bytes), Ok(image));
+    }
+
+    #[test]
+    fn malformed() {
+        let code = [1, 2, 3];
+        let bytes = Image::new(Address::from(0), &code).encode();
+
+        assert_eq!(Image::parse(&bytes[..5]), Err(Error::Truncated));
+        assert_eq!(
+            Image::parse(&bytes[..bytes.len() - 1]),
+            Err(Error::Truncated)
+        );
+
+        let mut bad = bytes.clone();
+        bad[0] = b'X';
+        assert_eq!(Image::parse(&bad), Err(Error::InvalidMagic));
+
+        let mut bad = bytes.clone();
+        bad[4] = 9;
+        assert_eq!(Image::parse(&bad), Err(Error::UnsupportedVersion(9)));
+
+        let bytes = Image::new(Address::from(3), &code).encode();
+        assert_eq!(
+            Image::parse(&bytes),
+            Err(Error::EntryOutOfRange(Address::from(3)))
+        );
+    }
+}
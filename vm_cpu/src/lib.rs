@@ -1,5 +1,42 @@
+//! the execution engine. without the `std` feature it only needs `alloc`
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+// the tests print and set up logging, which needs std even when the crate itself does not
+#[cfg(all(test, not(feature = "std")))]
+#[macro_use]
+extern crate std;
+
+pub mod builder;
 pub mod cpu;
 pub mod error;
+pub mod image;
 pub mod memory;
+pub mod mmio;
 pub mod opcodes;
 pub mod registers;
+pub mod verify;
+
+/// run with `cargo test -p vm_cpu --no-default-features` to check the engine without std
+#[cfg(test)]
+mod test {
+    use crate::{
+        cpu::Cpu,
+        memory::CpuMemory,
+        opcodes::{Instruction, Value},
+        registers::Register,
+    };
+
+    #[test]
+    fn runs_program() {
+        let mut program = Instruction::MovRegNum(Register::R1, Value::U8(2)).encode();
+        program.extend(Instruction::AddRegNum(Register::R1, Value::U8(3)).encode());
+        program.extend(Instruction::Halt.encode());
+
+        let mut cpu = Cpu::new(CpuMemory::default(), 0, u16::MAX as u32, 0.into());
+        cpu.load_program(&program).unwrap();
+        cpu.execute();
+
+        assert_eq!(cpu.registers()[Register::R1], 5);
+    }
+}
@@ -0,0 +1,136 @@
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand};
+use vm_cpu::{
+    cpu::{Cpu, DEFAULT_STACK_START},
+    error::Error,
+    memory::{Address, CpuMemory},
+};
+
+#[derive(Parser, Debug)]
+struct Args {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// loads a bytecode file and executes it until it halts
+    Run {
+        input: PathBuf,
+        #[arg(long, default_value_t = 0)]
+        program_start: u32,
+        #[arg(long, default_value_t = DEFAULT_STACK_START)]
+        stack_start: u32,
+        /// the input is an image with a header rather than raw bytecode, the header decides
+        /// where execution begins
+        #[arg(long, conflicts_with_all = ["program_start", "stack_start"])]
+        image: bool,
+    },
+}
+
+/// loads `bytes` at `program_start` and runs them until the cpu halts
+fn run(bytes: &[u8], program_start: u32, stack_start: u32) -> Result<Cpu, Error> {
+    let mut cpu = Cpu::new(
+        CpuMemory::default(),
+        program_start,
+        stack_start,
+        Address::default(),
+    );
+    cpu.load_program(bytes)?;
+    run_to_halt(&mut cpu)?;
+
+    Ok(cpu)
+}
+
+/// steps until a halt, unlike [`Cpu::execute`] an error is returned rather than panicking
+fn run_to_halt(cpu: &mut Cpu) -> Result<(), Error> {
+    while cpu.step()?.is_continue() {}
+
+    Ok(())
+}
+
+fn main() {
+    let args = Args::parse();
+
+    match args.command {
+        Command::Run {
+            input,
+            program_start,
+            stack_start,
+            image,
+        } => {
+            let bytes = std::fs::read(&input).expect("failed to read input file");
+
+            let res = if image {
+                Cpu::load_image(&bytes).and_then(|mut cpu| run_to_halt(&mut cpu).map(|_| cpu))
+            } else {
+                run(&bytes, program_start, stack_start)
+            };
+
+            match res {
+                Ok(cpu) => println!("{}", cpu.registers()),
+                Err(e) => {
+                    eprintln!("{e}");
+                    std::process::exit(1);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use clap::{error::ErrorKind, Parser};
+    use vm_cpu::{
+        error::Error,
+        opcodes::{self, OpCode},
+        registers::Register,
+    };
+
+    use super::{run, Args};
+
+    #[test]
+    fn run_bytes() {
+        let bytes = [
+            OpCode::MovRegU8 as u8,
+            Register::R1 as u8,
+            5,
+            OpCode::IncReg as u8,
+            Register::R1 as u8,
+            OpCode::Halt as u8,
+        ];
+
+        let cpu = run(&bytes, 16, u16::MAX as u32).unwrap();
+
+        assert_eq!(cpu.registers().get(Register::R1), 6);
+        assert_eq!(cpu.registers().get(Register::IP), 16 + bytes.len() as u32);
+    }
+
+    #[test]
+    fn run_invalid_opcode() {
+        assert!(matches!(
+            run(&[0xff], 0, u16::MAX as u32),
+            Err(Error::OpCodeError(opcodes::Error::InvalidOpCode(0xff)))
+        ));
+    }
+
+    #[test]
+    fn run_empty() {
+        assert!(matches!(
+            run(&[], 0, u16::MAX as u32),
+            Err(Error::EmptyProgram)
+        ));
+    }
+
+    #[test]
+    fn image_conflicts_with_starts() {
+        for flag in ["--program-start", "--stack-start"] {
+            let err = Args::try_parse_from(["vm_cpu", "run", "in.bin", "--image", flag, "16"])
+                .unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::ArgumentConflict);
+        }
+
+        assert!(Args::try_parse_from(["vm_cpu", "run", "in.bin", "--image"]).is_ok());
+    }
+}
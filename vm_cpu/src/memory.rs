@@ -1,3 +1,11 @@
+use alloc::{boxed::Box, collections::BTreeMap, vec, vec::Vec};
+use core::{fmt::Debug, ops::Range};
+
+use crate::{
+    memory::{self},
+    mmio::{Mmio, MmioDevice},
+};
+
 #[derive(Debug)]
 pub struct Page {
     data: Vec<u8>,
@@ -48,6 +56,14 @@ impl Pager {
         }
     }
 
+    pub fn read(&self, ptr: Pointer, idx: u32) -> u8 {
+        let id = ptr.page();
+
+        let page = self.pages.get(id as usize).unwrap();
+
+        page.data[idx as usize]
+    }
+
     pub fn write(&mut self, ptr: Pointer, idx: u32, bytes: &[u8]) {
         let id = ptr.page();
 
@@ -60,15 +76,27 @@ impl Pager {
     }
 }
 
+pub const PAGE_SIZE: u32 = 4096;
+/// a Pointer can only name 256 pages
+pub const MAX_PAGES: u32 = 256;
+/// the size of a stack slot
+pub const WORD_SIZE: u32 = 4;
+
+/// the addresses below [`CpuMemory::capacity`], backed by pages that are only allocated once
+/// they are written to. reading memory that has never been written yields 0, so do addresses
+/// past the capacity, which can only be written through mmio
 #[derive(Debug, Default)]
 pub struct CpuMemory {
     memory: Pager,
+    mapped: BTreeMap<u32, Pointer>,
+    devices: Vec<Mmio>,
 }
 
+#[derive(Clone, Copy)]
 pub struct Pointer(u32);
 
 impl Debug for Pointer {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "page:{:?} ", self.page())?;
         write!(f, "len:{:?}", self.len())?;
 
@@ -76,11 +104,11 @@ impl Debug for Pointer {
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 /// the lower 24 bits of the u32 from a Pointer
 pub struct PointerLen(u32);
 
-impl std::ops::Add<u32> for PointerLen {
+impl core::ops::Add<u32> for PointerLen {
     type Output = PointerLen;
     fn add(self, rhs: u32) -> Self::Output {
         let rhs = PointerLen::from(rhs);
@@ -88,7 +116,7 @@ impl std::ops::Add<u32> for PointerLen {
     }
 }
 
-impl std::ops::BitOrAssign for PointerLen {
+impl core::ops::BitOrAssign for PointerLen {
     fn bitor_assign(&mut self, rhs: Self) {
         self.0 &= 0xFF00_0000;
         self.0 |= rhs.0;
@@ -96,10 +124,6 @@ impl std::ops::BitOrAssign for PointerLen {
 }
 
 impl PointerLen {
-    fn len(&self) -> u32 {
-        self.0 & 0x00FF_FFFF
-    }
-
     const fn max() -> u32 {
         u32::MAX >> 8
     }
@@ -142,88 +166,210 @@ impl Pointer {
 }
 
 impl CpuMemory {
-    pub fn memcpy(&mut self, ptr: Pointer, bytes: Option<&[u8]>) {}
-    pub fn alloc(&mut self, amount: u32) {}
+    pub fn memcpy(&mut self, ptr: Pointer, bytes: Option<&[u8]>) {
+        if let Some(bytes) = bytes {
+            self.memory.memcpy(ptr, bytes);
+        }
+    }
 
-    pub fn read<A>(&self, address: A) -> Result<u8, Error>
-    where
-        A: Into<Address> + Copy,
-    {
-        todo!()
+    pub fn alloc(&mut self, amount: u32) -> Pointer {
+        self.memory.alloc(amount)
     }
 
-    pub fn read_u16<A>(&self, address: A) -> Result<u16, Error>
-    where
-        A: Into<Address> + Copy,
-    {
-        todo!()
+    /// how many bytes all the pages that can be allocated hold together, every address below
+    /// this has a page it can be mapped to
+    pub fn capacity(&self) -> usize {
+        (MAX_PAGES * PAGE_SIZE) as usize
     }
 
-    pub fn read_u32<A>(&self, address: A) -> Result<u32, Error>
-    where
-        A: Into<Address> + Copy,
-    {
-        todo!()
+    /// sets every byte back to 0, keeping the pages that are already allocated
+    pub fn zero(&mut self) {
+        for page in self.memory.pages.iter_mut() {
+            page.data.fill(0);
+        }
+    }
+
+    /// routes reads and writes within `range` to `device` instead of ram
+    pub fn register_mmio(&mut self, range: Range<Address>, device: Box<dyn MmioDevice>) {
+        self.devices.push(Mmio { range, device });
+    }
+
+    fn device(&self, address: Address) -> Option<usize> {
+        self.devices
+            .iter()
+            .position(|mmio| mmio.range.contains(&address))
+    }
+
+    fn page(&mut self, address: Address) -> Result<Pointer, Error> {
+        let id = address.0 / PAGE_SIZE;
+        if id >= MAX_PAGES {
+            return Err(Error::InvalidAddress(address.0));
+        }
+
+        if let Some(ptr) = self.mapped.get(&id) {
+            return Ok(*ptr);
+        }
+
+        if self.memory.pages.len() >= MAX_PAGES as usize {
+            return Err(Error::InvalidAddress(address.0));
+        }
+
+        let ptr = self.memory.alloc(PAGE_SIZE);
+        self.mapped.insert(id, ptr);
+
+        Ok(ptr)
     }
+}
+
+/// the memory a [`crate::cpu::Cpu`] runs on. only single bytes have to be implemented, wider
+/// values are little endian
+pub trait Memory: Debug {
+    fn read(&self, address: Address) -> Result<u8, Error>;
+    fn write(&mut self, address: Address, byte: u8) -> Result<(), Error>;
+    /// sets every byte back to 0
+    fn zero(&mut self);
+    /// the number of addressable bytes, every address below it can be read and written
+    fn capacity(&self) -> usize;
 
-    pub fn write<A>(&mut self, address: A, byte: impl Into<u8>) -> Result<(), Error>
-    where
-        A: Into<Address> + Copy,
-    {
-        todo!()
+    fn read_u16(&self, address: Address) -> Result<u16, Error> {
+        Ok(u16::from_le_bytes([
+            self.read(address)?,
+            self.read(address.next()?)?,
+        ]))
     }
 
-    pub fn write_bytes<'a, A>(
-        &mut self,
-        address: A,
-        bytes: impl Into<&'a [u8]>,
-    ) -> Result<(), Error>
-    where
-        A: Into<Address> + Copy,
-    {
-        let addr = address.into();
-        let bytes: &[u8] = bytes.into();
+    fn read_u32(&self, address: Address) -> Result<u32, Error> {
+        let bytes = self.get(address..(address + 4.into())?)?;
 
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn write_bytes(&mut self, address: Address, bytes: &[u8]) -> Result<(), Error> {
         for (i, byte) in bytes.iter().enumerate() {
-            self.write(addr + Address::from(i), *byte)?;
+            self.write((address + Address::from(i))?, *byte)?;
         }
 
         Ok(())
     }
 
-    pub fn write_u16<A>(&mut self, address: A, num: u16) -> Result<(), Error>
-    where
-        A: Into<Address> + Copy,
-    {
-        let bytes = num.to_le_bytes();
+    fn write_u16(&mut self, address: Address, num: u16) -> Result<(), Error> {
+        self.write_bytes(address, &num.to_le_bytes())
+    }
+
+    fn write_u32(&mut self, address: Address, num: u32) -> Result<(), Error> {
+        self.write_bytes(address, &num.to_le_bytes())
+    }
+
+    fn get(&self, bytes: Range<Address>) -> Result<Vec<u8>, Error> {
+        (bytes.start.0..bytes.end.0)
+            .map(|addr| self.read(addr.into()))
+            .collect()
+    }
+}
+
+impl Memory for CpuMemory {
+    fn read(&self, address: Address) -> Result<u8, Error> {
+        if let Some(idx) = self.device(address) {
+            let mmio = &self.devices[idx];
+            return Ok(mmio.device.read(address.0 - mmio.range.start.0));
+        }
 
-        self.write_bytes(address, &bytes as &[u8])
+        Ok(match self.mapped.get(&(address.0 / PAGE_SIZE)) {
+            Some(ptr) => self.memory.read(*ptr, address.0 % PAGE_SIZE),
+            None => 0,
+        })
     }
 
-    pub fn write_u32<A>(&mut self, address: A, num: u32) -> Result<(), Error>
-    where
-        A: Into<Address> + Copy,
-    {
-        let bytes = num.to_le_bytes();
+    fn write(&mut self, address: Address, byte: u8) -> Result<(), Error> {
+        if let Some(idx) = self.device(address) {
+            let mmio = &mut self.devices[idx];
+            mmio.device.write(address.0 - mmio.range.start.0, byte);
+            return Ok(());
+        }
 
-        self.write_bytes(address, &bytes as &[u8])
+        let ptr = self.page(address)?;
+
+        self.memory.write(ptr, address.0 % PAGE_SIZE, &[byte]);
+
+        Ok(())
     }
 
-    pub fn get(&self, bytes: std::ops::Range<Address>) -> Result<&[u8], Error> {
-        todo!()
+    fn zero(&mut self) {
+        CpuMemory::zero(self)
+    }
+
+    fn capacity(&self) -> usize {
+        CpuMemory::capacity(self)
     }
 }
 
-use std::{fmt::Debug, ops::Range};
+impl<M: Memory + ?Sized> Memory for Box<M> {
+    fn read(&self, address: Address) -> Result<u8, Error> {
+        (**self).read(address)
+    }
 
-use tracing::{error, info, warn};
+    fn write(&mut self, address: Address, byte: u8) -> Result<(), Error> {
+        (**self).write(address, byte)
+    }
 
-use crate::{
-    memory::{self},
-    registers::WordSize,
-};
+    fn zero(&mut self) {
+        (**self).zero()
+    }
+
+    fn capacity(&self) -> usize {
+        (**self).capacity()
+    }
+}
+
+/// a fixed `SIZE` bytes of memory, addresses past the end are invalid
+#[derive(Debug)]
+pub struct Stack<const SIZE: usize> {
+    data: Box<[u8]>,
+}
+
+impl<const SIZE: usize> Stack<SIZE> {
+    pub fn new() -> Self {
+        Self {
+            data: vec![0; SIZE].into_boxed_slice(),
+        }
+    }
+}
 
-#[derive(Default, Debug, Clone, Copy, PartialEq, PartialOrd, Eq)]
+impl<const SIZE: usize> Default for Stack<SIZE> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const SIZE: usize> Memory for Stack<SIZE> {
+    fn read(&self, address: Address) -> Result<u8, Error> {
+        self.data
+            .get(address.0 as usize)
+            .copied()
+            .ok_or(Error::InvalidAddress(address.0))
+    }
+
+    fn write(&mut self, address: Address, byte: u8) -> Result<(), Error> {
+        let slot = self
+            .data
+            .get_mut(address.0 as usize)
+            .ok_or(Error::InvalidAddress(address.0))?;
+        *slot = byte;
+
+        Ok(())
+    }
+
+    fn zero(&mut self) {
+        self.data.fill(0);
+    }
+
+    fn capacity(&self) -> usize {
+        SIZE
+    }
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, PartialOrd, Eq, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Address(pub u32);
 
 macro_rules! impl_address {
@@ -274,25 +420,33 @@ impl Address {
     }
 }
 
-impl std::fmt::Display for Address {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl core::fmt::Display for Address {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "{self:?}")
     }
 }
 
-impl std::ops::Add for Address {
-    type Output = Address;
+/// fails with [`memory::Error::StackOverflow`] instead of wrapping past `u32::MAX`
+impl core::ops::Add for Address {
+    type Output = Result<Address, memory::Error>;
 
     fn add(self, rhs: Self) -> Self::Output {
-        Address::from(self.0 + rhs.0)
+        let Some(addr) = self.0.checked_add(rhs.0) else {
+            return Err(memory::Error::StackOverflow);
+        };
+        Ok(Address(addr))
     }
 }
 
-impl std::ops::Sub for Address {
-    type Output = Address;
+/// fails with [`memory::Error::StackUnderflow`] instead of wrapping below zero
+impl core::ops::Sub for Address {
+    type Output = Result<Address, memory::Error>;
 
     fn sub(self, rhs: Self) -> Self::Output {
-        Address::from(self.0 - rhs.0)
+        let Some(addr) = self.0.checked_sub(rhs.0) else {
+            return Err(memory::Error::StackUnderflow);
+        };
+        Ok(Address(addr))
     }
 }
 
@@ -305,12 +459,14 @@ pub enum Error {
 
 #[cfg(test)]
 mod test {
+    use alloc::boxed::Box;
+
     use tracing::{info, level_filters::LevelFilter};
     use tracing_subscriber::util::SubscriberInitExt;
 
     use crate::memory::PointerLen;
 
-    use super::{Pager, Pointer};
+    use super::{Address, CpuMemory, Error, Memory, Pager, Pointer, Stack};
 
     fn setup_logger() {
         let _ = tracing_subscriber::FmtSubscriber::builder()
@@ -328,7 +484,7 @@ mod test {
         ptr.set_page(10);
         ptr.set_len(crate::memory::PointerLen::from(1));
 
-        assert_eq!(ptr.len().len(), 1);
+        assert_eq!(ptr.len(), crate::memory::PointerLen::from(1));
         assert_eq!(ptr.page(), 10);
         info!(?ptr);
     }
@@ -361,4 +517,51 @@ mod test {
         info!(?max);
         assert_eq!(max, 16777215)
     }
+
+    #[test]
+    fn address_round_trip() {
+        let addr = Address::from(u32::MAX);
+
+        assert_eq!(u32::from(addr), u32::MAX);
+        assert_eq!(addr.prev().unwrap(), Address::from(u32::MAX - 1));
+        assert!(addr.next().is_err());
+    }
+
+    #[test]
+    fn capacity() {
+        assert_eq!(Stack::<4096>::new().capacity(), 4096);
+        assert_eq!(Memory::capacity(&CpuMemory::default()), 1 << 20);
+
+        let boxed: Box<dyn Memory> = Box::new(Stack::<64>::new());
+        assert_eq!(boxed.capacity(), 64);
+    }
+
+    #[test]
+    fn cpu_memory_bounds() {
+        let mut mem = CpuMemory::default();
+        let last = Address::from(mem.capacity() - 1);
+        let past = Address::from(mem.capacity());
+
+        mem.write(last, 7).unwrap();
+        assert_eq!(mem.read(last).unwrap(), 7);
+
+        assert!(matches!(mem.write(past, 7), Err(Error::InvalidAddress(addr)) if addr == past.0));
+        assert_eq!(mem.read(past).unwrap(), 0);
+    }
+
+    #[test]
+    fn address_overflow() {
+        assert!(matches!(
+            Address(u32::MAX) + Address(1),
+            Err(Error::StackOverflow)
+        ));
+        assert!(matches!(
+            Address(0) - Address(1),
+            Err(Error::StackUnderflow)
+        ));
+        assert_eq!(
+            (Address(u32::MAX - 1) + Address(1)).unwrap(),
+            Address(u32::MAX)
+        );
+    }
 }
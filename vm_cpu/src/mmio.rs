@@ -0,0 +1,44 @@
+use alloc::boxed::Box;
+use core::{fmt::Debug, ops::Range};
+
+use crate::memory::Address;
+
+/// a host side device mapped into the address space. offsets are relative to the start of
+/// the range the device was registered at
+pub trait MmioDevice {
+    fn read(&self, offset: u32) -> u8;
+    fn write(&mut self, offset: u32, byte: u8);
+}
+
+pub(crate) struct Mmio {
+    pub(crate) range: Range<Address>,
+    pub(crate) device: Box<dyn MmioDevice>,
+}
+
+impl Debug for Mmio {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Mmio").field("range", &self.range).finish()
+    }
+}
+
+/// prints every byte written to offset 0 as a char, reads always give 0
+#[cfg(feature = "std")]
+#[derive(Debug, Default)]
+pub struct Stdout;
+
+#[cfg(feature = "std")]
+impl MmioDevice for Stdout {
+    fn read(&self, _offset: u32) -> u8 {
+        0
+    }
+
+    fn write(&mut self, offset: u32, byte: u8) {
+        use std::io::Write;
+
+        if offset == 0 {
+            let mut stdout = std::io::stdout();
+            let _ = stdout.write_all(&[byte]);
+            let _ = stdout.flush();
+        }
+    }
+}
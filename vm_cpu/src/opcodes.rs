@@ -1,14 +1,55 @@
-use crate::{memory::Address, registers::Register};
+use alloc::{vec, vec::Vec};
+use core::cmp::Ordering;
+
+use crate::{
+    error,
+    memory::Address,
+    registers::{Register, RegisterMask},
+};
 
 #[derive(Debug)]
 pub enum Error {
     InvalidOpCode(u8),
     InvalidComparison(u8),
+    InvalidOperands(OpCode),
+    /// the bytes ended before all the operands of the opcode
+    Truncated(OpCode),
+    /// there were no bytes to decode
+    Empty,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OperandKind {
+    Reg,
+    Addr,
+    U8,
+    U16,
+    U32,
+    I32,
+    F32,
+}
+
+impl OperandKind {
+    pub fn size(&self) -> usize {
+        match self {
+            OperandKind::Reg | OperandKind::U8 => 1,
+            OperandKind::U16 => 2,
+            OperandKind::Addr | OperandKind::U32 | OperandKind::I32 | OperandKind::F32 => 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operand {
+    Reg(Register),
+    Addr(Address),
+    Imm(Value),
 }
 
 macro_rules! op_codes {
-    ($($variant:ident, $amount:literal),* $(,)?) => {
+    ($($variant:ident, [$($operand:ident),* $(,)?]),* $(,)?) => {
         #[derive(Debug, Clone, Copy, PartialEq)]
+        #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
         #[repr(u8)]
         #[rustfmt::skip]
         pub enum OpCode {
@@ -16,9 +57,10 @@ macro_rules! op_codes {
         }
 
         impl OpCode {
-            pub fn increment_amount(&self) -> u8 {
+            /// the operands following the opcode byte, in the order they are encoded
+            pub fn layout(&self) -> &'static [OperandKind] {
                 match self {
-                    $(OpCode::$variant => $amount+1,)*
+                    $(OpCode::$variant => &[$(OperandKind::$operand),*],)*
                 }
             }
         }
@@ -40,8 +82,8 @@ macro_rules! op_codes {
             }
         }
 
-        impl std::fmt::Display for OpCode {
-            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        impl core::fmt::Display for OpCode {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
             match self {
                 $(Self::$variant { .. } => f.write_str(stringify!($variant))?,)*
             }
@@ -53,66 +95,158 @@ macro_rules! op_codes {
 }
 
 op_codes! {
-    MovRegMem, 5,
-    MovRegReg, 2,
-    MovRegU8, 2,
-    MovRegU16, 3,
-    MovRegU32, 5,
-    MovMemReg, 5,
-    MovMemU8, 5,
-    MovMemU16, 6,
-    MovMemU32, 8,
-
-    AddRegReg, 2,
-    AddRegMem, 5,
-    AddMemReg, 5,
-    AddRegU8, 2,
-    AddRegU16, 3,
-    AddRegU32, 5,
-
-    IncReg, 1,
-    IncMem, 4,
-
-    PushReg,   1,
-    PushMem,   4,
-    PushU8, 1,
-    PushU16, 2,
-    PushU32, 4,
-
-    PopReg,    1,
-
-    CmpReg,    2,
-    CmpU8,     2,
-    CmpU16,    3,
-    CmpU32,    5,
-
-    Jump,      4,
-    JumpGe,    4,
-    JumpGte,    4,
-    JumpLe,    4,
-    JumpLte,    4,
-
-    Call,      4,
-
-    Load, 4,
-
-    Halt,      0,
-    Ret,       0,
-
-    Interrupt, 4,
-    InterruptReg, 5,
-
-    StoreReg, 5,
-    StoreU8, 5,
-    StoreU16, 6,
-    StoreU32, 8,
+    MovRegMem, [Reg, Addr],
+    MovRegReg, [Reg, Reg],
+    MovRegU8, [Reg, U8],
+    MovRegU16, [Reg, U16],
+    MovRegU32, [Reg, U32],
+    MovRegLowU16, [Reg, U16],
+    MovRegHighU16, [Reg, U16],
+    SetByte, [Reg, U8],
+    MovMemReg, [Addr, Reg],
+    MovMemU8, [Addr, U8],
+    MovMemU16, [Addr, U16],
+    MovMemU32, [Addr, U32],
+
+    AddRegReg, [Reg, Reg],
+    AddRegMem, [Reg, Addr],
+    AddMemReg, [Addr, Reg],
+    AddRegU8, [Reg, U8],
+    AddRegU16, [Reg, U16],
+    AddRegU32, [Reg, U32],
+
+    AddPair, [Reg, Reg, Reg, Reg],
+
+    SubRegReg, [Reg, Reg],
+    MulRegReg, [Reg, Reg],
+    DivRegReg, [Reg, Reg],
+
+    AddF32RegReg, [Reg, Reg],
+    SubF32RegReg, [Reg, Reg],
+    MulF32RegReg, [Reg, Reg],
+    DivF32RegReg, [Reg, Reg],
+
+    AndRegReg, [Reg, Reg],
+    AndRegU8, [Reg, U8],
+    AndRegU16, [Reg, U16],
+    AndRegU32, [Reg, U32],
+    OrRegReg, [Reg, Reg],
+    OrRegU8, [Reg, U8],
+    OrRegU16, [Reg, U16],
+    OrRegU32, [Reg, U32],
+    XorRegReg, [Reg, Reg],
+    XorRegU8, [Reg, U8],
+    XorRegU16, [Reg, U16],
+    XorRegU32, [Reg, U32],
+    NotReg, [Reg],
+
+    ShlRegReg, [Reg, Reg],
+    ShlRegU8, [Reg, U8],
+    ShrRegReg, [Reg, Reg],
+    ShrRegU8, [Reg, U8],
+
+    IncReg, [Reg],
+    IncMem, [Addr],
+
+    PushReg, [Reg],
+    PushMem, [Addr],
+    PushU8, [U8],
+    PushU16, [U16],
+    PushU32, [U32],
+
+    PopReg, [Reg],
+
+    PushAll, [],
+    PopAll, [],
+
+    StackDepthReg, [Reg],
+
+    CmpReg, [Reg, Reg],
+    CmpU8, [U8, U8],
+    CmpU16, [U16, U16],
+    CmpU32, [U32, U32],
+    CmpI32, [I32, I32],
+    CmpF32, [F32, F32],
+    CmpRegU8, [Reg, U8],
+    CmpRegU16, [Reg, U16],
+    CmpRegU32, [Reg, U32],
+    CmpRegI32, [Reg, I32],
+    CmpRegF32, [Reg, F32],
+
+    Jump, [Addr],
+    JumpGe, [Addr],
+    JumpGte, [Addr],
+    JumpLe, [Addr],
+    JumpLte, [Addr],
+    JumpEq, [Addr],
+    JumpNe, [Addr],
+    JumpReg, [Reg],
+
+    Call, [Addr],
+    CallSaving, [Addr, U8],
+
+    Load, [Reg, Addr],
+    LoadIndirect, [Reg, Reg],
+
+    Nop, [],
+    Halt, [],
+    Ret, [],
+    IRet, [],
+
+    Interrupt, [U32],
+    InterruptReg, [Reg],
+
+    StoreReg, [Addr, Reg],
+    StoreU8, [Addr, U8],
+    StoreU16, [Addr, U16],
+    StoreU32, [Addr, U32],
+    StoreIndirect, [Reg, Reg],
+    StoreRegIndexed, [Addr, Reg, Reg],
+
+    MemCopy, [Addr, Addr, Reg],
+    MemSet, [Addr, U8, Reg],
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+impl OpCode {
+    /// the size of the whole instruction, opcode included
+    pub fn increment_amount(&self) -> u8 {
+        1 + self.layout().iter().map(OperandKind::size).sum::<usize>() as u8
+    }
+
+    /// how many cycles the instruction takes to run, touching memory costs more than working
+    /// on registers and saving or restoring a frame costs the most
+    pub fn cost(&self) -> u8 {
+        use OpCode::*;
+
+        match self {
+            Call | CallSaving | Ret | IRet | Interrupt | InterruptReg | PushAll | PopAll => 5,
+
+            MovRegMem | MovMemReg | MovMemU8 | MovMemU16 | MovMemU32 | AddRegMem | AddMemReg
+            | IncMem | PushReg | PushMem | PushU8 | PushU16 | PushU32 | PopReg | Load
+            | LoadIndirect | StoreReg | StoreU8 | StoreU16 | StoreU32 | StoreIndirect
+            | StoreRegIndexed | MemCopy | MemSet => 3,
+
+            MulRegReg | DivRegReg | AddPair => 2,
+            AddF32RegReg | SubF32RegReg | MulF32RegReg | DivF32RegReg => 2,
+
+            _ => 1,
+        }
+    }
+}
+
+/// values order by the number they hold rather than by width, so `U8(255) > U16(100)`.
+/// values that hold the same number order by width and floats compare by their bits for
+/// equality, which keeps the order total
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Value {
     U8(u8),
     U16(u16),
     U32(u32),
+    /// only compares tell it apart from a u32, everywhere else it is encoded as its bits
+    I32(i32),
+    /// always encoded as its bits, only the float instructions treat registers as f32
+    F32(f32),
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
@@ -142,11 +276,18 @@ impl TryFrom<u8> for Comparison {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Instruction {
     MovRegMem(Register, Address),
     MovRegReg(Register, Register),
     MovRegNum(Register, Value),
+    /// sets the low 16 bits of the register, keeping the high ones
+    MovRegLowU16(Register, u16),
+    /// sets the high 16 bits of the register, keeping the low ones
+    MovRegHighU16(Register, u16),
+    /// sets the low 8 bits of the register, keeping the rest
+    SetByte(Register, u8),
 
     MovMemReg(Address, Register),
     MovMemNum(Address, Value),
@@ -156,6 +297,37 @@ pub enum Instruction {
     AddRegMem(Register, Address),
     AddMemReg(Address, Register),
 
+    /// adds the 64 bit value in (src lo, src hi) to (lo, hi), carrying from lo into hi
+    AddPair {
+        lo: Register,
+        hi: Register,
+        src_lo: Register,
+        src_hi: Register,
+    },
+
+    SubRegReg(Register, Register),
+    MulRegReg(Register, Register),
+    DivRegReg(Register, Register),
+
+    /// float arithmetic on the bits held in the registers
+    AddF32RegReg(Register, Register),
+    SubF32RegReg(Register, Register),
+    MulF32RegReg(Register, Register),
+    DivF32RegReg(Register, Register),
+
+    AndRegReg(Register, Register),
+    AndRegNum(Register, Value),
+    OrRegReg(Register, Register),
+    OrRegNum(Register, Value),
+    XorRegReg(Register, Register),
+    XorRegNum(Register, Value),
+    NotReg(Register),
+
+    ShlRegReg(Register, Register),
+    ShlRegNum(Register, u8),
+    ShrRegReg(Register, Register),
+    ShrRegNum(Register, u8),
+
     IncReg(Register),
     IncMem(Address),
 
@@ -165,27 +337,58 @@ pub enum Instruction {
 
     PopReg(Register),
 
+    /// pushes r1 through r8, in that order
+    PushAll,
+    /// pops into r8 through r1, undoing a [`Instruction::PushAll`]
+    PopAll,
+
+    StackDepthReg(Register),
+
     CmpReg(Register, Register),
     CmpVal(Value, Value),
+    /// compares the register against the value, signed if the value is an i32
+    CmpRegNum(Register, Value),
 
     Jump(Address),
     JumpGe(Address),
     JumpGte(Address),
     JumpLt(Address),
     JumpLte(Address),
+    JumpEq(Address),
+    JumpNe(Address),
+    /// jumps to the address held in the register
+    JumpReg(Register),
 
     Call(Address),
+    /// like call but saves only the registers in the mask, ret restores the same set
+    CallSaving(Address, RegisterMask),
 
     Load(Register, Address),
+    /// loads into the first register from the address held in the second
+    LoadIndirect(Register, Register),
 
     StoreReg(Address, Register),
     StoreVal(Address, Value),
+    /// stores the second register at the address held in the first
+    StoreIndirect(Register, Register),
+    /// stores the second register at the address plus the value of the first register
+    StoreRegIndexed(Address, Register, Register),
+
+    /// copies as many bytes as the register holds from the second address to the first. the
+    /// bytes are read before any are written, so overlapping ranges copy like `memmove`
+    MemCopy(Address, Address, Register),
+    /// fills as many bytes as the register holds, starting at the address, with the byte
+    MemSet(Address, u8, Register),
 
     Interrupt(u32),
     InterruptReg(Register),
 
+    /// does nothing, useful as padding or to reserve space for a later patch
+    Nop,
     Halt,
     Ret,
+    /// returns from an interrupt handler to the instruction after the interrupt
+    IRet,
 }
 
 impl From<Instruction> for OpCode {
@@ -198,15 +401,18 @@ impl From<Instruction> for OpCode {
             MovRegNum(_, val) => match val {
                 Value::U8(_) => OpCode::MovRegU8,
                 Value::U16(_) => OpCode::MovRegU16,
-                Value::U32(_) => OpCode::MovRegU32,
+                Value::U32(_) | Value::I32(_) | Value::F32(_) => OpCode::MovRegU32,
             },
+            MovRegLowU16(..) => OpCode::MovRegLowU16,
+            MovRegHighU16(..) => OpCode::MovRegHighU16,
+            SetByte(..) => OpCode::SetByte,
 
             MovMemReg(_, _) => OpCode::MovMemReg,
 
             MovMemNum(_, val) => match val {
                 Value::U8(_) => OpCode::MovMemU8,
                 Value::U16(_) => OpCode::MovMemU16,
-                Value::U32(_) => OpCode::MovMemU32,
+                Value::U32(_) | Value::I32(_) | Value::F32(_) => OpCode::MovMemU32,
             },
 
             PushReg(_) => OpCode::PushReg,
@@ -215,11 +421,13 @@ impl From<Instruction> for OpCode {
             PushVal(val) => match val {
                 Value::U8(_) => OpCode::PushU8,
                 Value::U16(_) => OpCode::PushU16,
-                Value::U32(_) => OpCode::PushU32,
+                Value::U32(_) | Value::I32(_) | Value::F32(_) => OpCode::PushU32,
             },
 
             PopReg(_) => OpCode::PopReg,
 
+            StackDepthReg(_) => OpCode::StackDepthReg,
+
             AddRegReg(_, _) => OpCode::AddRegReg,
             AddRegMem(_, _) => OpCode::AddRegMem,
             AddMemReg(_, _) => OpCode::AddMemReg,
@@ -227,17 +435,63 @@ impl From<Instruction> for OpCode {
             AddRegNum(_, val) => match val {
                 Value::U8(_) => OpCode::AddRegU8,
                 Value::U16(_) => OpCode::AddRegU16,
-                Value::U32(_) => OpCode::AddRegU32,
+                Value::U32(_) | Value::I32(_) | Value::F32(_) => OpCode::AddRegU32,
+            },
+
+            AddPair { .. } => OpCode::AddPair,
+
+            SubRegReg(_, _) => OpCode::SubRegReg,
+            MulRegReg(_, _) => OpCode::MulRegReg,
+            DivRegReg(_, _) => OpCode::DivRegReg,
+
+            AddF32RegReg(_, _) => OpCode::AddF32RegReg,
+            SubF32RegReg(_, _) => OpCode::SubF32RegReg,
+            MulF32RegReg(_, _) => OpCode::MulF32RegReg,
+            DivF32RegReg(_, _) => OpCode::DivF32RegReg,
+
+            AndRegReg(_, _) => OpCode::AndRegReg,
+            AndRegNum(_, val) => match val {
+                Value::U8(_) => OpCode::AndRegU8,
+                Value::U16(_) => OpCode::AndRegU16,
+                Value::U32(_) | Value::I32(_) | Value::F32(_) => OpCode::AndRegU32,
+            },
+            OrRegReg(_, _) => OpCode::OrRegReg,
+            OrRegNum(_, val) => match val {
+                Value::U8(_) => OpCode::OrRegU8,
+                Value::U16(_) => OpCode::OrRegU16,
+                Value::U32(_) | Value::I32(_) | Value::F32(_) => OpCode::OrRegU32,
             },
+            XorRegReg(_, _) => OpCode::XorRegReg,
+            XorRegNum(_, val) => match val {
+                Value::U8(_) => OpCode::XorRegU8,
+                Value::U16(_) => OpCode::XorRegU16,
+                Value::U32(_) | Value::I32(_) | Value::F32(_) => OpCode::XorRegU32,
+            },
+            NotReg(_) => OpCode::NotReg,
+
+            ShlRegReg(_, _) => OpCode::ShlRegReg,
+            ShlRegNum(_, _) => OpCode::ShlRegU8,
+            ShrRegReg(_, _) => OpCode::ShrRegReg,
+            ShrRegNum(_, _) => OpCode::ShrRegU8,
 
             Interrupt(_) => OpCode::Interrupt,
             InterruptReg(_) => OpCode::InterruptReg,
 
             CmpReg(_, _) => OpCode::CmpReg,
-            CmpVal(val, _) => match val {
-                Value::U8(_) => OpCode::CmpU8,
-                Value::U16(_) => OpCode::CmpU16,
-                Value::U32(_) => OpCode::CmpU32,
+            CmpVal(Value::F32(_), _) | CmpVal(_, Value::F32(_)) => OpCode::CmpF32,
+            CmpVal(Value::I32(_), _) | CmpVal(_, Value::I32(_)) => OpCode::CmpI32,
+            // both sides are encoded at the wider of their widths
+            CmpVal(left, right) => match left.width().max(right.width()) {
+                1 => OpCode::CmpU8,
+                2 => OpCode::CmpU16,
+                _ => OpCode::CmpU32,
+            },
+            CmpRegNum(_, val) => match val {
+                Value::U8(_) => OpCode::CmpRegU8,
+                Value::U16(_) => OpCode::CmpRegU16,
+                Value::U32(_) => OpCode::CmpRegU32,
+                Value::I32(_) => OpCode::CmpRegI32,
+                Value::F32(_) => OpCode::CmpRegF32,
             },
 
             Jump(_) => OpCode::Jump,
@@ -245,24 +499,571 @@ impl From<Instruction> for OpCode {
             JumpGte(_) => OpCode::JumpGte,
             JumpLt(_) => OpCode::JumpLe,
             JumpLte(_) => OpCode::JumpLte,
+            JumpEq(_) => OpCode::JumpEq,
+            JumpNe(_) => OpCode::JumpNe,
+            JumpReg(_) => OpCode::JumpReg,
 
             Call(_) => OpCode::Call,
+            CallSaving(_, _) => OpCode::CallSaving,
 
             IncReg(_) => OpCode::IncReg,
             IncMem(_) => OpCode::IncMem,
 
+            Nop => OpCode::Nop,
+            PushAll => OpCode::PushAll,
+            PopAll => OpCode::PopAll,
             Halt => OpCode::Halt,
             Ret => OpCode::Ret,
+            IRet => OpCode::IRet,
 
             Load(_, _) => OpCode::Load,
+            LoadIndirect(_, _) => OpCode::LoadIndirect,
 
             StoreReg(_, _) => OpCode::StoreReg,
 
             StoreVal(_, val) => match val {
                 Value::U8(_) => OpCode::StoreU8,
                 Value::U16(_) => OpCode::StoreU16,
-                Value::U32(_) => OpCode::StoreU32,
+                Value::U32(_) | Value::I32(_) | Value::F32(_) => OpCode::StoreU32,
             },
+            StoreIndirect(_, _) => OpCode::StoreIndirect,
+            StoreRegIndexed(..) => OpCode::StoreRegIndexed,
+
+            MemCopy(..) => OpCode::MemCopy,
+            MemSet(..) => OpCode::MemSet,
+        }
+    }
+}
+
+impl Instruction {
+    /// decodes the instruction at the start of `bytes`, returning it along with how many bytes
+    /// it takes up. anything after the instruction is ignored
+    pub fn decode(bytes: &[u8]) -> Result<(Instruction, usize), error::Error> {
+        let (&byte, bytecode) = bytes.split_first().ok_or(Error::Empty)?;
+        let op = OpCode::try_from(byte)?;
+        let len = op.increment_amount() as usize;
+        if bytes.len() < len {
+            return Err(Error::Truncated(op).into());
+        }
+
+        let mut operands = Vec::with_capacity(op.layout().len());
+        let mut offset = 0;
+
+        for kind in op.layout() {
+            let bytes = &bytecode[offset..offset + kind.size()];
+            offset += kind.size();
+
+            operands.push(match kind {
+                OperandKind::Reg => Operand::Reg(Register::try_from(bytes[0])?),
+                OperandKind::Addr => Operand::Addr(
+                    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]).into(),
+                ),
+                OperandKind::U8 => Operand::Imm(Value::U8(bytes[0])),
+                OperandKind::U16 => {
+                    Operand::Imm(Value::U16(u16::from_le_bytes([bytes[0], bytes[1]])))
+                }
+                OperandKind::U32 => Operand::Imm(Value::U32(u32::from_le_bytes([
+                    bytes[0], bytes[1], bytes[2], bytes[3],
+                ]))),
+                OperandKind::I32 => Operand::Imm(Value::I32(i32::from_le_bytes([
+                    bytes[0], bytes[1], bytes[2], bytes[3],
+                ]))),
+                OperandKind::F32 => Operand::Imm(Value::F32(f32::from_le_bytes([
+                    bytes[0], bytes[1], bytes[2], bytes[3],
+                ]))),
+            });
+        }
+
+        Ok((Instruction::from_operands(op, &operands)?, len))
+    }
+
+    /// builds the instruction for `op` from operands decoded according to its layout
+    pub fn from_operands(op: OpCode, operands: &[Operand]) -> Result<Instruction, Error> {
+        use OpCode as Op;
+        use Operand::{Addr, Imm, Reg};
+
+        Ok(match (op, operands) {
+            (Op::MovRegMem, [Reg(reg), Addr(addr)]) => Instruction::MovRegMem(*reg, *addr),
+            (Op::MovRegReg, [Reg(left), Reg(right)]) => Instruction::MovRegReg(*left, *right),
+            (Op::MovRegU8 | Op::MovRegU16 | Op::MovRegU32, [Reg(reg), Imm(val)]) => {
+                Instruction::MovRegNum(*reg, *val)
+            }
+            (Op::MovMemReg, [Addr(addr), Reg(reg)]) => Instruction::MovMemReg(*addr, *reg),
+            (Op::MovMemU8 | Op::MovMemU16 | Op::MovMemU32, [Addr(addr), Imm(val)]) => {
+                Instruction::MovMemNum(*addr, *val)
+            }
+
+            (Op::AddRegReg, [Reg(left), Reg(right)]) => Instruction::AddRegReg(*left, *right),
+            (Op::AddRegMem, [Reg(reg), Addr(addr)]) => Instruction::AddRegMem(*reg, *addr),
+            (Op::AddMemReg, [Addr(addr), Reg(reg)]) => Instruction::AddMemReg(*addr, *reg),
+            (Op::AddRegU8 | Op::AddRegU16 | Op::AddRegU32, [Reg(reg), Imm(val)]) => {
+                Instruction::AddRegNum(*reg, *val)
+            }
+
+            (Op::AddPair, [Reg(lo), Reg(hi), Reg(src_lo), Reg(src_hi)]) => Instruction::AddPair {
+                lo: *lo,
+                hi: *hi,
+                src_lo: *src_lo,
+                src_hi: *src_hi,
+            },
+
+            (Op::SubRegReg, [Reg(left), Reg(right)]) => Instruction::SubRegReg(*left, *right),
+            (Op::MulRegReg, [Reg(left), Reg(right)]) => Instruction::MulRegReg(*left, *right),
+            (Op::DivRegReg, [Reg(left), Reg(right)]) => Instruction::DivRegReg(*left, *right),
+
+            (Op::AddF32RegReg, [Reg(left), Reg(right)]) => Instruction::AddF32RegReg(*left, *right),
+            (Op::SubF32RegReg, [Reg(left), Reg(right)]) => Instruction::SubF32RegReg(*left, *right),
+            (Op::MulF32RegReg, [Reg(left), Reg(right)]) => Instruction::MulF32RegReg(*left, *right),
+            (Op::DivF32RegReg, [Reg(left), Reg(right)]) => Instruction::DivF32RegReg(*left, *right),
+
+            (Op::AndRegReg, [Reg(left), Reg(right)]) => Instruction::AndRegReg(*left, *right),
+            (Op::AndRegU8 | Op::AndRegU16 | Op::AndRegU32, [Reg(reg), Imm(val)]) => {
+                Instruction::AndRegNum(*reg, *val)
+            }
+            (Op::OrRegReg, [Reg(left), Reg(right)]) => Instruction::OrRegReg(*left, *right),
+            (Op::OrRegU8 | Op::OrRegU16 | Op::OrRegU32, [Reg(reg), Imm(val)]) => {
+                Instruction::OrRegNum(*reg, *val)
+            }
+            (Op::XorRegReg, [Reg(left), Reg(right)]) => Instruction::XorRegReg(*left, *right),
+            (Op::XorRegU8 | Op::XorRegU16 | Op::XorRegU32, [Reg(reg), Imm(val)]) => {
+                Instruction::XorRegNum(*reg, *val)
+            }
+            (Op::NotReg, [Reg(reg)]) => Instruction::NotReg(*reg),
+
+            (Op::ShlRegReg, [Reg(left), Reg(right)]) => Instruction::ShlRegReg(*left, *right),
+            (Op::MovRegLowU16, [Reg(reg), Imm(Value::U16(val))]) => {
+                Instruction::MovRegLowU16(*reg, *val)
+            }
+            (Op::MovRegHighU16, [Reg(reg), Imm(Value::U16(val))]) => {
+                Instruction::MovRegHighU16(*reg, *val)
+            }
+            (Op::SetByte, [Reg(reg), Imm(Value::U8(byte))]) => Instruction::SetByte(*reg, *byte),
+            (Op::ShlRegU8, [Reg(reg), Imm(Value::U8(count))]) => {
+                Instruction::ShlRegNum(*reg, *count)
+            }
+            (Op::ShrRegReg, [Reg(left), Reg(right)]) => Instruction::ShrRegReg(*left, *right),
+            (Op::ShrRegU8, [Reg(reg), Imm(Value::U8(count))]) => {
+                Instruction::ShrRegNum(*reg, *count)
+            }
+
+            (Op::IncReg, [Reg(reg)]) => Instruction::IncReg(*reg),
+            (Op::IncMem, [Addr(addr)]) => Instruction::IncMem(*addr),
+
+            (Op::PushReg, [Reg(reg)]) => Instruction::PushReg(*reg),
+            (Op::PushMem, [Addr(addr)]) => Instruction::PushMem(*addr),
+            (Op::PushU8 | Op::PushU16 | Op::PushU32, [Imm(val)]) => Instruction::PushVal(*val),
+
+            (Op::PopReg, [Reg(reg)]) => Instruction::PopReg(*reg),
+
+            (Op::StackDepthReg, [Reg(reg)]) => Instruction::StackDepthReg(*reg),
+
+            (Op::CmpReg, [Reg(left), Reg(right)]) => Instruction::CmpReg(*left, *right),
+            (
+                Op::CmpU8 | Op::CmpU16 | Op::CmpU32 | Op::CmpI32 | Op::CmpF32,
+                [Imm(left), Imm(right)],
+            ) => Instruction::CmpVal(*left, *right),
+            (
+                Op::CmpRegU8 | Op::CmpRegU16 | Op::CmpRegU32 | Op::CmpRegI32 | Op::CmpRegF32,
+                [Reg(reg), Imm(val)],
+            ) => Instruction::CmpRegNum(*reg, *val),
+
+            (Op::Jump, [Addr(addr)]) => Instruction::Jump(*addr),
+            (Op::JumpGe, [Addr(addr)]) => Instruction::JumpGe(*addr),
+            (Op::JumpGte, [Addr(addr)]) => Instruction::JumpGte(*addr),
+            (Op::JumpLe, [Addr(addr)]) => Instruction::JumpLt(*addr),
+            (Op::JumpLte, [Addr(addr)]) => Instruction::JumpLte(*addr),
+            (Op::JumpEq, [Addr(addr)]) => Instruction::JumpEq(*addr),
+            (Op::JumpNe, [Addr(addr)]) => Instruction::JumpNe(*addr),
+            (Op::JumpReg, [Reg(reg)]) => Instruction::JumpReg(*reg),
+
+            (Op::Call, [Addr(addr)]) => Instruction::Call(*addr),
+            (Op::CallSaving, [Addr(addr), Imm(Value::U8(mask))]) => {
+                Instruction::CallSaving(*addr, RegisterMask(*mask))
+            }
+
+            (Op::Load, [Reg(reg), Addr(addr)]) => Instruction::Load(*reg, *addr),
+            (Op::LoadIndirect, [Reg(dest), Reg(addr)]) => Instruction::LoadIndirect(*dest, *addr),
+
+            (Op::StoreReg, [Addr(addr), Reg(reg)]) => Instruction::StoreReg(*addr, *reg),
+            (Op::StoreU8 | Op::StoreU16 | Op::StoreU32, [Addr(addr), Imm(val)]) => {
+                Instruction::StoreVal(*addr, *val)
+            }
+            (Op::StoreIndirect, [Reg(addr), Reg(src)]) => Instruction::StoreIndirect(*addr, *src),
+            (Op::StoreRegIndexed, [Addr(base), Reg(index), Reg(src)]) => {
+                Instruction::StoreRegIndexed(*base, *index, *src)
+            }
+
+            (Op::MemCopy, [Addr(dest), Addr(src), Reg(len)]) => {
+                Instruction::MemCopy(*dest, *src, *len)
+            }
+            (Op::MemSet, [Addr(dest), Imm(Value::U8(byte)), Reg(len)]) => {
+                Instruction::MemSet(*dest, *byte, *len)
+            }
+
+            (Op::Interrupt, [Imm(Value::U32(idx))]) => Instruction::Interrupt(*idx),
+            (Op::InterruptReg, [Reg(reg)]) => Instruction::InterruptReg(*reg),
+
+            (Op::Nop, []) => Instruction::Nop,
+            (Op::PushAll, []) => Instruction::PushAll,
+            (Op::PopAll, []) => Instruction::PopAll,
+            (Op::Halt, []) => Instruction::Halt,
+            (Op::Ret, []) => Instruction::Ret,
+            (Op::IRet, []) => Instruction::IRet,
+
+            _ => return Err(Error::InvalidOperands(op)),
+        })
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Value {}
+
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Value {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.as_f64()
+            .total_cmp(&other.as_f64())
+            .then(self.rank().cmp(&other.rank()))
+            .then_with(|| match (self, other) {
+                (Value::F32(left), Value::F32(right)) => left.to_bits().cmp(&right.to_bits()),
+                _ => Ordering::Equal,
+            })
+    }
+}
+
+impl Value {
+    /// every width fits in an f64 without losing precision
+    fn as_f64(self) -> f64 {
+        match self {
+            Value::U8(val) => val as f64,
+            Value::U16(val) => val as f64,
+            Value::U32(val) => val as f64,
+            Value::I32(val) => val as f64,
+            Value::F32(val) => val as f64,
+        }
+    }
+
+    /// the value as a float, integers are converted rather than reinterpreted
+    pub fn as_f32(self) -> f32 {
+        match self {
+            Value::F32(val) => val,
+            val => val.as_f64() as f32,
+        }
+    }
+
+    /// breaks ties between equal values of different kinds, narrower kinds sort first
+    fn rank(self) -> u8 {
+        match self {
+            Value::U8(_) => 0,
+            Value::U16(_) => 1,
+            Value::U32(_) => 2,
+            Value::I32(_) => 3,
+            Value::F32(_) => 4,
+        }
+    }
+
+    /// how many bytes the value takes up when encoded
+    pub fn width(&self) -> usize {
+        match self {
+            Value::U8(_) => 1,
+            Value::U16(_) => 2,
+            Value::U32(_) | Value::I32(_) | Value::F32(_) => 4,
+        }
+    }
+
+    /// the value as the u32 a register holds it as, signed values keep their two's complement
+    /// bits and floats their ieee bits
+    pub fn as_u32(&self) -> u32 {
+        match *self {
+            Value::U8(val) => val as u32,
+            Value::U16(val) => val as u32,
+            Value::U32(val) => val,
+            Value::I32(val) => val as u32,
+            Value::F32(val) => val.to_bits(),
+        }
+    }
+
+    /// adds two values, the sum has the wider of their widths. unsigned values stay unsigned
+    /// unless the other side is signed, floats only add to floats. `None` when the sum does not
+    /// fit or a float is added to an integer
+    pub fn checked_add(self, rhs: Value) -> Option<Value> {
+        match (self, rhs) {
+            (Value::F32(left), Value::F32(right)) => Some(Value::F32(left + right)),
+            (Value::F32(_), _) | (_, Value::F32(_)) => None,
+            (Value::I32(_), _) | (_, Value::I32(_)) => {
+                let signed = |val: Value| match val {
+                    Value::I32(val) => val as i64,
+                    val => val.as_u32() as i64,
+                };
+                i32::try_from(signed(self) + signed(rhs))
+                    .ok()
+                    .map(Value::I32)
+            }
+            _ => {
+                let sum = self.as_u32().checked_add(rhs.as_u32())?;
+                match self.width().max(rhs.width()) {
+                    1 => u8::try_from(sum).ok().map(Value::U8),
+                    2 => u16::try_from(sum).ok().map(Value::U16),
+                    _ => Some(Value::U32(sum)),
+                }
+            }
+        }
+    }
+
+    fn as_i32(self) -> i32 {
+        match self {
+            Value::U8(val) => val as i32,
+            Value::U16(val) => val as i32,
+            Value::U32(val) => val as i32,
+            Value::I32(val) => val,
+            Value::F32(val) => val.to_bits() as i32,
+        }
+    }
+
+    fn to_le_bytes(self) -> Vec<u8> {
+        match self {
+            Value::U8(val) => vec![val],
+            Value::U16(val) => val.to_le_bytes().to_vec(),
+            Value::U32(val) => val.to_le_bytes().to_vec(),
+            Value::I32(val) => val.to_le_bytes().to_vec(),
+            Value::F32(val) => val.to_bits().to_le_bytes().to_vec(),
+        }
+    }
+}
+
+impl Instruction {
+    /// how many bytes [`Instruction::encode`] writes, the opcode byte and every operand
+    pub fn size(&self) -> usize {
+        OpCode::from(self).increment_amount() as usize
+    }
+
+    /// the bytes the cpu decodes back into this instruction, opcode first. operands are written
+    /// in the order they appear in the variant with numbers and addresses little endian
+    pub fn encode(&self) -> Vec<u8> {
+        use Instruction::*;
+
+        let mut bytes = vec![OpCode::from(self) as u8];
+
+        match *self {
+            MovRegMem(reg, addr) | AddRegMem(reg, addr) | Load(reg, addr) => {
+                bytes.push(reg as u8);
+                bytes.extend(addr.0.to_le_bytes());
+            }
+            MovMemReg(addr, reg) | AddMemReg(addr, reg) | StoreReg(addr, reg) => {
+                bytes.extend(addr.0.to_le_bytes());
+                bytes.push(reg as u8);
+            }
+            MovRegReg(left, right)
+            | AddRegReg(left, right)
+            | SubRegReg(left, right)
+            | MulRegReg(left, right)
+            | DivRegReg(left, right)
+            | AddF32RegReg(left, right)
+            | SubF32RegReg(left, right)
+            | MulF32RegReg(left, right)
+            | DivF32RegReg(left, right)
+            | AndRegReg(left, right)
+            | OrRegReg(left, right)
+            | XorRegReg(left, right)
+            | ShlRegReg(left, right)
+            | ShrRegReg(left, right)
+            | CmpReg(left, right)
+            | LoadIndirect(left, right)
+            | StoreIndirect(left, right) => {
+                bytes.push(left as u8);
+                bytes.push(right as u8);
+            }
+            MovRegNum(reg, val)
+            | AddRegNum(reg, val)
+            | AndRegNum(reg, val)
+            | OrRegNum(reg, val)
+            | XorRegNum(reg, val)
+            | CmpRegNum(reg, val) => {
+                bytes.push(reg as u8);
+                bytes.extend(val.to_le_bytes());
+            }
+            MovMemNum(addr, val) | StoreVal(addr, val) => {
+                bytes.extend(addr.0.to_le_bytes());
+                bytes.extend(val.to_le_bytes());
+            }
+            StoreRegIndexed(base, index, src) => {
+                bytes.extend(base.0.to_le_bytes());
+                bytes.extend([index as u8, src as u8]);
+            }
+            MemCopy(dest, src, len) => {
+                bytes.extend(dest.0.to_le_bytes());
+                bytes.extend(src.0.to_le_bytes());
+                bytes.push(len as u8);
+            }
+            MemSet(dest, byte, len) => {
+                bytes.extend(dest.0.to_le_bytes());
+                bytes.extend([byte, len as u8]);
+            }
+            IncReg(reg) | NotReg(reg) | PushReg(reg) | PopReg(reg) | StackDepthReg(reg)
+            | InterruptReg(reg) | JumpReg(reg) => bytes.push(reg as u8),
+            AddPair {
+                lo,
+                hi,
+                src_lo,
+                src_hi,
+            } => bytes.extend([lo as u8, hi as u8, src_lo as u8, src_hi as u8]),
+            ShlRegNum(reg, count) | ShrRegNum(reg, count) => {
+                bytes.push(reg as u8);
+                bytes.push(count);
+            }
+            SetByte(reg, byte) => bytes.extend([reg as u8, byte]),
+            MovRegLowU16(reg, val) | MovRegHighU16(reg, val) => {
+                bytes.push(reg as u8);
+                bytes.extend(val.to_le_bytes());
+            }
+            IncMem(addr) | PushMem(addr) | Call(addr) => bytes.extend(addr.0.to_le_bytes()),
+            CallSaving(addr, mask) => {
+                bytes.extend(addr.0.to_le_bytes());
+                bytes.push(mask.0);
+            }
+            Jump(addr) | JumpGe(addr) | JumpGte(addr) | JumpLt(addr) | JumpLte(addr) => {
+                bytes.extend(addr.0.to_le_bytes())
+            }
+            JumpEq(addr) | JumpNe(addr) => bytes.extend(addr.0.to_le_bytes()),
+            PushVal(val) => bytes.extend(val.to_le_bytes()),
+            // a signed compare encodes both sides as i32, whatever width the other one has
+            CmpVal(left, right) if OpCode::from(self) == OpCode::CmpI32 => {
+                bytes.extend(left.as_i32().to_le_bytes());
+                bytes.extend(right.as_i32().to_le_bytes());
+            }
+            // and a float compare as f32
+            CmpVal(left, right) if OpCode::from(self) == OpCode::CmpF32 => {
+                bytes.extend(left.as_f32().to_le_bytes());
+                bytes.extend(right.as_f32().to_le_bytes());
+            }
+            CmpVal(left, right) => {
+                let width = left.width().max(right.width());
+                bytes.extend(&left.as_u32().to_le_bytes()[..width]);
+                bytes.extend(&right.as_u32().to_le_bytes()[..width]);
+            }
+            Interrupt(idx) => bytes.extend(idx.to_le_bytes()),
+            Nop | Halt | Ret | IRet | PushAll | PopAll => {}
+        }
+
+        bytes
+    }
+}
+
+impl core::fmt::Display for Value {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Value::U8(val) => write!(f, "{val}"),
+            Value::U16(val) => write!(f, "{val}"),
+            Value::U32(val) => write!(f, "{val}"),
+            Value::I32(val) => write!(f, "{val}"),
+            Value::F32(val) => write!(f, "{val:?}"),
+        }
+    }
+}
+
+impl core::fmt::Display for Instruction {
+    /// renders the instruction in the syntax the assembler reads, addresses are written as
+    /// `[n]`. the conditional jumps other than jumpeq and jumpne have no assembler keyword yet
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        use Instruction::*;
+
+        match *self {
+            MovRegReg(dst, src) => write!(f, "mov {dst}, {src}"),
+            MovRegNum(reg, val) => write!(f, "mov {reg}, {val}"),
+            MovRegMem(reg, addr) => write!(f, "mov [{}], {reg}", addr.0),
+            MovMemReg(addr, reg) => write!(f, "mov {reg}, [{}]", addr.0),
+            MovMemNum(addr, val) => write!(f, "mov [{}], {val}", addr.0),
+
+            AddRegReg(dst, src) => write!(f, "add {dst}, {src}"),
+            AddRegNum(reg, val) => write!(f, "add {reg}, {val}"),
+            AddRegMem(reg, addr) => write!(f, "add {reg}, [{}]", addr.0),
+            AddMemReg(addr, reg) => write!(f, "add [{}], {reg}", addr.0),
+            AddPair {
+                lo,
+                hi,
+                src_lo,
+                src_hi,
+            } => write!(f, "addpair {lo}, {hi}, {src_lo}, {src_hi}"),
+
+            SubRegReg(dst, src) => write!(f, "sub {dst}, {src}"),
+            MulRegReg(dst, src) => write!(f, "mul {dst}, {src}"),
+            DivRegReg(dst, src) => write!(f, "div {dst}, {src}"),
+
+            AddF32RegReg(dst, src) => write!(f, "addf {dst}, {src}"),
+            SubF32RegReg(dst, src) => write!(f, "subf {dst}, {src}"),
+            MulF32RegReg(dst, src) => write!(f, "mulf {dst}, {src}"),
+            DivF32RegReg(dst, src) => write!(f, "divf {dst}, {src}"),
+
+            AndRegReg(dst, src) => write!(f, "and {dst}, {src}"),
+            AndRegNum(reg, val) => write!(f, "and {reg}, {val}"),
+            OrRegReg(dst, src) => write!(f, "or {dst}, {src}"),
+            OrRegNum(reg, val) => write!(f, "or {reg}, {val}"),
+            XorRegReg(dst, src) => write!(f, "xor {dst}, {src}"),
+            XorRegNum(reg, val) => write!(f, "xor {reg}, {val}"),
+            NotReg(reg) => write!(f, "not {reg}"),
+
+            ShlRegReg(dst, src) => write!(f, "shl {dst}, {src}"),
+            ShlRegNum(reg, count) => write!(f, "shl {reg}, {count}"),
+            MovRegLowU16(reg, val) => write!(f, "movl {reg}, {val}"),
+            MovRegHighU16(reg, val) => write!(f, "movh {reg}, {val}"),
+            SetByte(reg, byte) => write!(f, "setb {reg}, {byte}"),
+            ShrRegReg(dst, src) => write!(f, "shr {dst}, {src}"),
+            ShrRegNum(reg, count) => write!(f, "shr {reg}, {count}"),
+
+            IncReg(reg) => write!(f, "inc {reg}"),
+            IncMem(addr) => write!(f, "inc [{}]", addr.0),
+
+            PushReg(reg) => write!(f, "push {reg}"),
+            PushMem(addr) => write!(f, "push [{}]", addr.0),
+            PushVal(val) => write!(f, "push {val}"),
+            PopReg(reg) => write!(f, "pop {reg}"),
+            StackDepthReg(reg) => write!(f, "stackdepth {reg}"),
+
+            CmpReg(left, right) => write!(f, "cmp {left}, {right}"),
+            CmpVal(left, right) => write!(f, "cmp {left}, {right}"),
+            CmpRegNum(reg, val) => write!(f, "cmp {reg}, {val}"),
+
+            Jump(addr) => write!(f, "jump [{}]", addr.0),
+            JumpGe(addr) => write!(f, "jumpge [{}]", addr.0),
+            JumpGte(addr) => write!(f, "jumpgte [{}]", addr.0),
+            JumpLt(addr) => write!(f, "jumplt [{}]", addr.0),
+            JumpLte(addr) => write!(f, "jumplte [{}]", addr.0),
+            JumpEq(addr) => write!(f, "jumpeq [{}]", addr.0),
+            JumpNe(addr) => write!(f, "jumpne [{}]", addr.0),
+            JumpReg(reg) => write!(f, "jumpreg {reg}"),
+
+            Call(addr) => write!(f, "call [{}]", addr.0),
+            CallSaving(addr, mask) => write!(f, "call [{}], {mask}", addr.0),
+
+            Load(reg, addr) => write!(f, "load {reg}, [{}]", addr.0),
+            LoadIndirect(dest, addr) => write!(f, "load {dest}, {addr}"),
+            StoreReg(addr, reg) => write!(f, "store [{}], {reg}", addr.0),
+            StoreVal(addr, val) => write!(f, "store [{}], {val}", addr.0),
+            StoreIndirect(addr, src) => write!(f, "store {addr}, {src}"),
+            StoreRegIndexed(base, index, src) => write!(f, "store [{} + {index}], {src}", base.0),
+
+            MemCopy(dest, src, len) => write!(f, "memcpy [{}], [{}], {len}", dest.0, src.0),
+            MemSet(dest, byte, len) => write!(f, "memset [{}], {byte}, {len}", dest.0),
+
+            Interrupt(idx) => write!(f, "interrupt {idx}"),
+            InterruptReg(reg) => write!(f, "interrupt {reg}"),
+
+            Nop => f.write_str("nop"),
+            PushAll => f.write_str("pushall"),
+            PopAll => f.write_str("popall"),
+            Halt => f.write_str("halt"),
+            Ret => f.write_str("ret"),
+            IRet => f.write_str("iret"),
         }
     }
 }
@@ -272,3 +1073,259 @@ impl From<&Instruction> for OpCode {
         OpCode::from(*value)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{memory::Address, registers::Register};
+
+    use crate::error::Error;
+
+    use super::{Error as OpError, Instruction, OpCode, Value};
+
+    #[test]
+    fn display() {
+        let cases = [
+            (
+                Instruction::MovRegNum(Register::R1, Value::U8(10)),
+                "mov r1, 10",
+            ),
+            (
+                Instruction::AddRegReg(Register::R1, Register::R2),
+                "add r1, r2",
+            ),
+            (
+                Instruction::MovMemReg(Address::from(8), Register::R3),
+                "mov r3, [8]",
+            ),
+            (
+                Instruction::MovRegMem(Register::R3, Address::from(8)),
+                "mov [8], r3",
+            ),
+            (
+                Instruction::StoreVal(Address::from(4), Value::U16(300)),
+                "store [4], 300",
+            ),
+            (Instruction::Jump(Address::from(100)), "jump [100]"),
+            (Instruction::JumpReg(Register::R1), "jumpreg r1"),
+            (Instruction::ShlRegNum(Register::R2, 3), "shl r2, 3"),
+            (Instruction::CmpVal(Value::U8(1), Value::U8(2)), "cmp 1, 2"),
+            (
+                Instruction::CmpVal(Value::I32(-1), Value::I32(1)),
+                "cmp -1, 1",
+            ),
+            (
+                Instruction::CmpRegNum(Register::R1, Value::U8(10)),
+                "cmp r1, 10",
+            ),
+            (
+                Instruction::AddF32RegReg(Register::R1, Register::R2),
+                "addf r1, r2",
+            ),
+            (
+                Instruction::MovRegNum(Register::R1, Value::F32(1.0)),
+                "mov r1, 1.0",
+            ),
+            (Instruction::PushReg(Register::SP), "push sp"),
+            (
+                Instruction::StoreIndirect(Register::R2, Register::R1),
+                "store r2, r1",
+            ),
+            (
+                Instruction::StoreRegIndexed(Address::from(100), Register::R1, Register::R2),
+                "store [100 + r1], r2",
+            ),
+            (
+                Instruction::AddPair {
+                    lo: Register::R1,
+                    hi: Register::R2,
+                    src_lo: Register::R3,
+                    src_hi: Register::R4,
+                },
+                "addpair r1, r2, r3, r4",
+            ),
+            (Instruction::Nop, "nop"),
+            (
+                Instruction::MovRegHighU16(Register::R1, 300),
+                "movh r1, 300",
+            ),
+            (Instruction::PushAll, "pushall"),
+            (Instruction::PopAll, "popall"),
+            (Instruction::Halt, "halt"),
+        ];
+
+        for (inst, text) in cases {
+            assert_eq!(format!("{inst}"), text);
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        use alloc::vec::Vec;
+
+        let program = vec![
+            Instruction::MovRegNum(Register::R1, Value::U16(300)),
+            Instruction::StoreReg(Address::from(4), Register::R1),
+            Instruction::JumpNe(Address::from(0)),
+            Instruction::Halt,
+        ];
+
+        let json = serde_json::to_string(&program).unwrap();
+        let decoded: Vec<Instruction> = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(decoded, program);
+    }
+
+    #[test]
+    fn encoded_size_matches_layout() {
+        let insts = [
+            Instruction::Load(Register::R1, Address::from(4)),
+            Instruction::CmpVal(Value::U16(1), Value::U16(2)),
+            Instruction::CmpVal(Value::U32(1), Value::U32(2)),
+            Instruction::CmpVal(Value::I32(-1), Value::U8(2)),
+            Instruction::CmpRegNum(Register::R1, Value::U16(2)),
+            Instruction::CmpRegNum(Register::R1, Value::F32(2.5)),
+            Instruction::CmpVal(Value::F32(-1.5), Value::F32(2.0)),
+            Instruction::CmpRegNum(Register::R1, Value::I32(-2)),
+            Instruction::InterruptReg(Register::R1),
+            Instruction::StoreVal(Address::from(4), Value::U16(1)),
+            Instruction::ShlRegNum(Register::R1, 3),
+            Instruction::Halt,
+        ];
+
+        for inst in insts {
+            assert_eq!(
+                inst.encode().len(),
+                OpCode::from(inst).increment_amount() as usize,
+                "{inst:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn size_matches_encoding() {
+        // zeroed operands decode for every opcode, register 0 is ip and value 0 is valid
+        let opcodes = (0..=u8::MAX).filter_map(|byte| OpCode::try_from(byte).ok());
+
+        for op in opcodes {
+            let mut bytes = vec![op as u8];
+            bytes.resize(op.increment_amount() as usize, 0);
+            let (inst, len) = Instruction::decode(&bytes).unwrap();
+
+            assert_eq!(inst.size(), inst.encode().len(), "{inst:?}");
+            assert_eq!(inst.size(), len, "{inst:?}");
+            assert_eq!(inst.size(), op.increment_amount() as usize, "{inst:?}");
+        }
+
+        // operands of different widths are encoded at the wider one
+        let mixed = [
+            (
+                Instruction::CmpVal(Value::U8(1), Value::U32(70000)),
+                Instruction::CmpVal(Value::U32(1), Value::U32(70000)),
+            ),
+            (
+                Instruction::CmpVal(Value::U32(70000), Value::U8(1)),
+                Instruction::CmpVal(Value::U32(70000), Value::U32(1)),
+            ),
+            (
+                Instruction::CmpVal(Value::U16(300), Value::U8(1)),
+                Instruction::CmpVal(Value::U16(300), Value::U16(1)),
+            ),
+            (
+                Instruction::CmpVal(Value::U8(1), Value::I32(-1)),
+                Instruction::CmpVal(Value::I32(1), Value::I32(-1)),
+            ),
+            (
+                Instruction::CmpVal(Value::U8(3), Value::F32(2.5)),
+                Instruction::CmpVal(Value::F32(3.0), Value::F32(2.5)),
+            ),
+        ];
+
+        for (inst, widened) in mixed {
+            let bytes = inst.encode();
+            assert_eq!(inst.size(), bytes.len(), "{inst:?}");
+            assert_eq!(Instruction::decode(&bytes).unwrap(), (widened, bytes.len()));
+        }
+    }
+
+    #[test]
+    fn decode() {
+        let bytes = [
+            OpCode::MovRegU16 as u8,
+            Register::R2 as u8,
+            0x34,
+            0x12,
+            0xff,
+        ];
+
+        let (inst, len) = Instruction::decode(&bytes).unwrap();
+        assert_eq!(
+            inst,
+            Instruction::MovRegNum(Register::R2, Value::U16(0x1234))
+        );
+        assert_eq!(len, 4);
+
+        let (inst, len) = Instruction::decode(&[OpCode::Halt as u8]).unwrap();
+        assert_eq!((inst, len), (Instruction::Halt, 1));
+
+        assert!(matches!(
+            Instruction::decode(&bytes[..3]),
+            Err(Error::OpCodeError(OpError::Truncated(OpCode::MovRegU16)))
+        ));
+        assert!(matches!(
+            Instruction::decode(&[]),
+            Err(Error::OpCodeError(OpError::Empty))
+        ));
+    }
+
+    #[test]
+    fn value_as_u32() {
+        assert_eq!(Value::U8(200).as_u32(), 200);
+        assert_eq!(Value::U16(300).as_u32(), 300);
+        assert_eq!(Value::U32(70000).as_u32(), 70000);
+        assert_eq!(Value::I32(-1).as_u32(), u32::MAX);
+        assert_eq!(Value::F32(1.5).as_u32(), 1.5_f32.to_bits());
+
+        assert_eq!(Value::U8(1).width(), 1);
+        assert_eq!(Value::U16(1).width(), 2);
+        assert_eq!(Value::I32(1).width(), 4);
+    }
+
+    #[test]
+    fn value_checked_add() {
+        assert_eq!(Value::U8(1).checked_add(Value::U8(2)), Some(Value::U8(3)));
+        assert_eq!(Value::U8(255).checked_add(Value::U8(1)), None);
+        assert_eq!(
+            Value::U8(255).checked_add(Value::U16(1)),
+            Some(Value::U16(256))
+        );
+        assert_eq!(Value::U16(u16::MAX).checked_add(Value::U8(1)), None);
+        assert_eq!(Value::U32(u32::MAX).checked_add(Value::U8(1)), None);
+        assert_eq!(
+            Value::I32(-5).checked_add(Value::U8(3)),
+            Some(Value::I32(-2))
+        );
+        assert_eq!(Value::I32(i32::MAX).checked_add(Value::I32(1)), None);
+        assert_eq!(Value::U32(u32::MAX).checked_add(Value::I32(-1)), None);
+        assert_eq!(
+            Value::F32(1.5).checked_add(Value::F32(2.0)),
+            Some(Value::F32(3.5))
+        );
+        assert_eq!(Value::F32(1.5).checked_add(Value::U8(1)), None);
+    }
+
+    #[test]
+    fn value_order() {
+        assert!(Value::U8(255) > Value::U16(100));
+        assert!(Value::U16(100) < Value::U32(101));
+        assert!(Value::I32(-1) < Value::U8(0));
+        assert!(Value::F32(1.5) > Value::U8(1));
+        assert!(Value::U8(1) < Value::U16(1));
+        assert_ne!(Value::U8(1), Value::U16(1));
+        assert_eq!(Value::F32(f32::NAN), Value::F32(f32::NAN));
+
+        let mut values = [Value::U32(70000), Value::I32(-5), Value::U8(3)];
+        values.sort();
+        assert_eq!(values, [Value::I32(-5), Value::U8(3), Value::U32(70000)]);
+    }
+}
@@ -1,6 +1,7 @@
-use std::{
-    fmt::Display,
-    ops::{Index, IndexMut},
+use alloc::string::{String, ToString};
+use core::{
+    fmt::{Debug, Display},
+    ops::{Index, IndexMut, Sub},
     str::FromStr,
 };
 
@@ -16,6 +17,7 @@ pub enum Error {
 
 /// Registers r1-r4 are nonvolatile, r5-r8 are volatile
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum Register {
     IP,
@@ -32,11 +34,88 @@ pub enum Register {
 }
 
 impl Register {
+    /// the general purpose registers, in the order pushall saves them
+    pub const GENERAL: [Register; 8] = [
+        Self::R1,
+        Self::R2,
+        Self::R3,
+        Self::R4,
+        Self::R5,
+        Self::R6,
+        Self::R7,
+        Self::R8,
+    ];
+
     pub const fn len() -> usize {
         11
     }
 }
 
+/// a set of general purpose registers, bit `n` stands for `Register::GENERAL[n]`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RegisterMask(pub u8);
+
+impl RegisterMask {
+    /// r1-r4, what a plain call or an interrupt saves
+    pub const NONVOLATILE: Self = Self(0b1111);
+
+    pub fn contains(&self, register: Register) -> bool {
+        Register::GENERAL
+            .iter()
+            .position(|general| *general == register)
+            .is_some_and(|bit| self.0 & 1 << bit != 0)
+    }
+
+    /// the registers in the mask, in the order they are saved
+    pub fn registers(self) -> impl DoubleEndedIterator<Item = Register> {
+        Register::GENERAL
+            .into_iter()
+            .enumerate()
+            .filter(move |(bit, _)| self.0 & 1 << bit != 0)
+            .map(|(_, register)| register)
+    }
+}
+
+impl FromIterator<Register> for RegisterMask {
+    /// ip, sp and fp are not general purpose and are left out
+    fn from_iter<T: IntoIterator<Item = Register>>(iter: T) -> Self {
+        let mut mask = 0;
+        for register in iter {
+            if let Some(bit) = Register::GENERAL.iter().position(|r| *r == register) {
+                mask |= 1 << bit;
+            }
+        }
+
+        Self(mask)
+    }
+}
+
+impl Display for RegisterMask {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Display for Register {
+    /// the name the assembler accepts for this register
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            Self::IP => "ip",
+            Self::SP => "sp",
+            Self::FP => "fp",
+            Self::R1 => "r1",
+            Self::R2 => "r2",
+            Self::R3 => "r3",
+            Self::R4 => "r4",
+            Self::R5 => "r5",
+            Self::R6 => "r6",
+            Self::R7 => "r7",
+            Self::R8 => "r8",
+        })
+    }
+}
+
 impl FromStr for Register {
     type Err = Error;
 
@@ -98,6 +177,18 @@ impl WordSize for u32 {
     }
 }
 
+impl WordSize for u64 {
+    type Output = u32;
+
+    fn upper(&self) -> Self::Output {
+        (*self >> 32) as u32
+    }
+
+    fn lower(&self) -> Self::Output {
+        *self as u32
+    }
+}
+
 impl WordSize for Address {
     type Output = u16;
 
@@ -158,80 +249,107 @@ macro_rules! register_impl {
 register_impl!(u8);
 register_impl!(u16);
 register_impl!(u32);
+register_impl!(u64);
+
+/// the type a register holds, the cpu uses u32 words
+pub trait Word:
+    Copy + Default + PartialEq + Debug + Display + Sub<Output = Self> + From<u8>
+{
+    const MAX: Self;
+}
+
+impl Word for u32 {
+    const MAX: Self = u32::MAX;
+}
+
+impl Word for u64 {
+    const MAX: Self = u64::MAX;
+}
 
 #[derive(Debug)]
-pub struct Registers([u32; Register::len()]);
+pub struct Registers<W: Word = u32>([W; Register::len()]);
 
-impl Default for Registers {
+impl<W: Word> Default for Registers<W> {
     fn default() -> Self {
-        let mut register = [0_u32; Register::len()];
-        register[Register::SP as usize] = u32::MAX;
+        let mut register = [W::default(); Register::len()];
+        register[Register::SP as usize] = W::MAX;
         Self(register)
     }
 }
 
-impl Registers {
-    pub fn program_start(&mut self, start: u32) {
+impl<W: Word> Registers<W> {
+    pub fn program_start(&mut self, start: W) {
         self.0[Register::IP as usize] = start;
     }
 
-    pub fn program_end(&mut self, end: u32) {
+    pub fn program_end(&mut self, end: W) {
         self.0[Register::SP as usize] = end;
     }
 
-    pub fn new(program_start: u32, stack_start: u32) -> Self {
+    pub fn new(program_start: W, stack_start: W) -> Self {
         let mut registers = Self::default();
         registers.0[Register::IP as usize] = program_start;
         registers.0[Register::SP as usize] = stack_start;
-        registers.0[Register::FP as usize] = stack_start - 2;
+        registers.0[Register::FP as usize] = stack_start - W::from(2);
 
         registers
     }
 
-    pub fn get(&self, register: Register) -> u32 {
+    pub fn get(&self, register: Register) -> W {
         self[register]
     }
 
-    pub fn set(&mut self, register: Register, val: u32) {
+    pub fn set(&mut self, register: Register, val: W) {
         self[register] = val;
     }
 
-    pub fn as_slice(&self) -> &[u32; Register::len()] {
+    pub fn as_slice(&self) -> &[W; Register::len()] {
         &self.0
     }
+}
 
-    pub fn get_upper(&self, register: Register) -> u16 {
-        let upper = self[register] << 16;
+impl Registers {
+    /// sets the low 16 bits of `register`, keeping the high ones
+    pub fn set_lower(&mut self, register: Register, val: u16) {
+        self[register] = (self[register] & 0xffff_0000) | val as u32;
+    }
 
-        (upper >> 16) as u16
+    /// sets the high 16 bits of `register`, keeping the low ones
+    pub fn set_upper(&mut self, register: Register, val: u16) {
+        self[register] = (self[register] & 0x0000_ffff) | (val as u32) << 16;
     }
 
-    pub fn get_lower(&self, register: Register) -> u16 {
-        let lower = self[register] >> 16;
+    /// the most significant 16 bits of `register`
+    pub fn get_upper(&self, register: Register) -> u16 {
+        self[register].upper()
+    }
 
-        lower as u16
+    /// the least significant 16 bits of `register`
+    pub fn get_lower(&self, register: Register) -> u16 {
+        self[register].lower()
     }
 }
 
-impl Display for Registers {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl<W: Word> Display for Registers<W> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         writeln!(f, "IP {}", self.0[0])?;
         writeln!(f, "SP {}", self.0[1])?;
-        writeln!(f, "R1 {}", self.0[2])?;
-        writeln!(f, "R2 {}", self.0[3])?;
-        writeln!(f, "R3 {}", self.0[4])?;
-        writeln!(f, "R4 {}", self.0[5])?;
-        writeln!(f, "R5 {}", self.0[6])?;
-        writeln!(f, "R6 {}", self.0[7])?;
-        writeln!(f, "R7 {}", self.0[8])?;
-        writeln!(f, "R8 {}", self.0[9])?;
+        writeln!(f, "FP {}", self.0[2])?;
+        writeln!(f, "R1 {}", self.0[3])?;
+        writeln!(f, "R2 {}", self.0[4])?;
+        writeln!(f, "R3 {}", self.0[5])?;
+        writeln!(f, "R4 {}", self.0[6])?;
+        writeln!(f, "R5 {}", self.0[7])?;
+        writeln!(f, "R6 {}", self.0[8])?;
+        writeln!(f, "R7 {}", self.0[9])?;
+        writeln!(f, "R8 {}", self.0[10])?;
 
         Ok(())
     }
 }
 
-impl Index<Register> for Registers {
-    type Output = u32;
+impl<W: Word> Index<Register> for Registers<W> {
+    type Output = W;
 
     #[track_caller]
     fn index(&self, index: Register) -> &Self::Output {
@@ -240,7 +358,7 @@ impl Index<Register> for Registers {
     }
 }
 
-impl IndexMut<Register> for Registers {
+impl<W: Word> IndexMut<Register> for Registers<W> {
     #[track_caller]
     fn index_mut(&mut self, index: Register) -> &mut Self::Output {
         trace!("{}", self.0[index as usize]);
@@ -248,7 +366,7 @@ impl IndexMut<Register> for Registers {
     }
 }
 
-impl PartialEq for Registers {
+impl<W: Word> PartialEq for Registers<W> {
     fn eq(&self, other: &Self) -> bool {
         self.0 == other.0
     }
@@ -256,6 +374,8 @@ impl PartialEq for Registers {
 
 #[cfg(test)]
 mod register_tests {
+    use alloc::vec::Vec;
+
     use super::*;
     use Register::*;
 
@@ -268,21 +388,28 @@ mod register_tests {
     #[test]
     fn upper() {
         let mut regs = Registers::default();
-        regs[R1] = u32::MAX;
-        println!("{:?}", regs[R1].upper());
-        assert!(regs.get_upper(R1) == 65535);
+        regs[R1] = 0xAAAA_0000;
+        assert_eq!(regs.get_upper(R1), 0xAAAA);
+        assert_eq!(regs.get_lower(R1), 0);
+
+        regs.set_upper(R1, 0x1234);
+        assert_eq!(regs[R1], 0x1234_0000);
     }
 
     #[test]
     fn lower() {
         let mut regs = Registers::default();
         regs[R1] = u16::MAX as u32;
-        assert!(regs.get_upper(R1) == 65535);
+        assert_eq!(regs.get_lower(R1), 0xFFFF);
+        assert_eq!(regs.get_upper(R1), 0);
+
+        regs.set_lower(R1, 0x1234);
+        assert_eq!(regs[R1], 0x1234);
     }
 
     #[test]
     fn upper_and_lower() {
-        let x = 0xAAAAEEEE;
+        let x: u32 = 0xAAAAEEEE;
         let mut regs = Registers::default();
         regs[R1] = x;
 
@@ -293,6 +420,35 @@ mod register_tests {
         assert_eq!(lower, 0xEEEE);
     }
 
+    #[test]
+    fn wide_registers() {
+        let mut regs = Registers::<u64>::default();
+        assert_eq!(regs[SP], u64::MAX);
+
+        regs[R1] = u32::MAX as u64 + 10;
+        regs[R2] = regs[R1] * 2;
+        assert_eq!(regs.get(R2), 0x2_0000_0012);
+        assert_eq!(regs[R2].upper(), 2);
+        assert_eq!(regs[R2].lower(), 0x12);
+
+        let regs = Registers::new(0_u64, 1 << 40);
+        assert_eq!(regs[FP], (1 << 40) - 2);
+    }
+
+    #[test]
+    fn register_mask() {
+        let mask = RegisterMask::from_iter([R1, R3, IP]);
+        assert_eq!(mask, RegisterMask(0b101));
+        assert!(mask.contains(R3));
+        assert!(!mask.contains(R2));
+        assert!(!mask.contains(IP));
+        assert_eq!(mask.registers().rev().collect::<Vec<_>>(), [R3, R1]);
+        assert_eq!(
+            RegisterMask::NONVOLATILE.registers().collect::<Vec<_>>(),
+            [R1, R2, R3, R4]
+        );
+    }
+
     #[test]
     fn len() {
         assert!(Register::len() == 11);
@@ -0,0 +1,360 @@
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    vec,
+    vec::Vec,
+};
+
+use crate::{
+    error::Error,
+    memory::Address,
+    opcodes::{self, Instruction, OpCode},
+    registers,
+};
+
+#[derive(Debug, PartialEq)]
+pub enum VerifyError {
+    /// the instruction at `index` refers to memory past the end of the backing memory
+    AddressOutOfRange { index: usize, address: Address },
+    /// the jump or call at `index` goes to an address that is not the start of an instruction
+    InvalidJumpTarget { index: usize, address: Address },
+    /// a path through the program runs off its end or loops forever without a halt or ret
+    MissingTerminator,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ValidationError {
+    /// the byte at `offset` is not an opcode
+    InvalidOpCode { offset: usize, byte: u8 },
+    /// an operand of the instruction at `offset` is not a register
+    InvalidRegister { offset: usize, byte: u8 },
+    /// the operands of the instruction at `offset` run past the end of the program
+    Truncated { offset: usize, op: OpCode },
+    /// the operands of the instruction at `offset` do not make up an instruction
+    InvalidOperands { offset: usize, op: OpCode },
+}
+
+/// walks the bytecode of `program` and checks that every instruction in it decodes. programs
+/// with data mixed into them do not pass, the data is read as instructions
+pub fn validate_program(program: &[u8]) -> Result<(), ValidationError> {
+    let mut offset = 0;
+
+    while let Some(&byte) = program.get(offset) {
+        let op =
+            OpCode::try_from(byte).map_err(|_| ValidationError::InvalidOpCode { offset, byte })?;
+
+        match Instruction::decode(&program[offset..]) {
+            Ok((_, len)) => offset += len,
+            Err(Error::RegisterError(registers::Error::InvalidRegister(byte))) => {
+                return Err(ValidationError::InvalidRegister { offset, byte })
+            }
+            Err(Error::OpCodeError(opcodes::Error::Truncated(_))) => {
+                return Err(ValidationError::Truncated { offset, op })
+            }
+            Err(_) => return Err(ValidationError::InvalidOperands { offset, op }),
+        }
+    }
+
+    Ok(())
+}
+
+/// every address an instruction reads, writes or jumps to, relative to the program start
+fn addresses(inst: &Instruction) -> Vec<Address> {
+    use Instruction::*;
+
+    match *inst {
+        MovRegMem(_, addr) | MovMemReg(addr, _) | MovMemNum(addr, _) => vec![addr],
+        AddRegMem(_, addr) | AddMemReg(addr, _) => vec![addr],
+        IncMem(addr) | PushMem(addr) => vec![addr],
+        Jump(addr) | JumpGe(addr) | JumpGte(addr) | JumpLt(addr) | JumpLte(addr) => vec![addr],
+        JumpEq(addr) | JumpNe(addr) | Call(addr) | CallSaving(addr, _) => vec![addr],
+        Load(_, addr) | StoreReg(addr, _) | StoreVal(addr, _) => vec![addr],
+        StoreRegIndexed(base, _, _) | MemSet(base, _, _) => vec![base],
+        MemCopy(dest, src, _) => vec![dest, src],
+        _ => Vec::new(),
+    }
+}
+
+/// statically checks `program` before it is run: every address it uses must fit within
+/// `memory_capacity`, every jump must land on an instruction and every path from the first
+/// instruction must be able to end in a halt or ret
+pub fn verify(program: &[Instruction], memory_capacity: usize) -> Result<(), Vec<VerifyError>> {
+    let mut errors = Vec::new();
+
+    let mut offsets = Vec::with_capacity(program.len());
+    let mut offset = 0;
+
+    for (index, inst) in program.iter().enumerate() {
+        offsets.push(offset);
+        offset += inst.size() as u32;
+
+        for address in addresses(inst) {
+            if address.0 as usize >= memory_capacity {
+                errors.push(VerifyError::AddressOutOfRange { index, address });
+            }
+        }
+    }
+
+    // where a jump lands has to be the start of one of the program's instructions
+    let target = |addr: Address| offsets.binary_search(&addr.0).ok();
+
+    // the instructions each reachable instruction can go on to, an index of `program.len()`
+    // is running off the end
+    let mut successors = BTreeMap::new();
+    let mut pending = vec![0];
+    let mut runs_off_end = program.is_empty();
+
+    while let Some(index) = pending.pop() {
+        if successors.contains_key(&index) {
+            continue;
+        }
+
+        let Some(inst) = program.get(index) else {
+            runs_off_end = true;
+            continue;
+        };
+
+        let mut next = Vec::new();
+        match *inst {
+            Instruction::Halt | Instruction::Ret | Instruction::IRet => {}
+            // where a computed jump lands is only known once it runs
+            Instruction::JumpReg(_) => {}
+            Instruction::Jump(addr)
+            | Instruction::JumpGe(addr)
+            | Instruction::JumpGte(addr)
+            | Instruction::JumpLt(addr)
+            | Instruction::JumpLte(addr)
+            | Instruction::JumpEq(addr)
+            | Instruction::JumpNe(addr)
+            | Instruction::Call(addr)
+            | Instruction::CallSaving(addr, _) => {
+                match target(addr) {
+                    Some(target) => next.push(target),
+                    None => errors.push(VerifyError::InvalidJumpTarget {
+                        index,
+                        address: addr,
+                    }),
+                }
+                // only an unconditional jump never falls through
+                if !matches!(inst, Instruction::Jump(_)) {
+                    next.push(index + 1);
+                }
+            }
+            _ => next.push(index + 1),
+        }
+
+        pending.extend(&next);
+        successors.insert(index, next);
+    }
+
+    let mut predecessors = BTreeMap::<usize, Vec<usize>>::new();
+    for (index, next) in &successors {
+        for next in next {
+            predecessors.entry(*next).or_default().push(*index);
+        }
+    }
+
+    // walks back from every instruction that ends the program, anything reachable that is not
+    // found this way is stuck in a loop with no way out
+    let mut terminates = BTreeSet::new();
+    let mut pending = successors
+        .iter()
+        .filter(|(_, next)| next.is_empty())
+        .map(|(index, _)| *index)
+        .collect::<Vec<_>>();
+    while let Some(index) = pending.pop() {
+        if terminates.insert(index) {
+            pending.extend(predecessors.get(&index).into_iter().flatten());
+        }
+    }
+
+    if runs_off_end || successors.keys().any(|index| !terminates.contains(index)) {
+        errors.push(VerifyError::MissingTerminator);
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use alloc::vec::Vec;
+
+    use crate::{
+        memory::Address,
+        opcodes::{Instruction, OpCode, Value},
+        registers::Register,
+    };
+
+    use super::{validate_program, verify, ValidationError, VerifyError};
+
+    #[test]
+    fn valid_program() {
+        let program = [
+            Instruction::MovRegNum(Register::R1, Value::U8(1)),
+            Instruction::JumpEq(Address::from(0)),
+            Instruction::Halt,
+        ];
+
+        assert_eq!(verify(&program, 100), Ok(()));
+    }
+
+    #[test]
+    fn jump_out_of_range() {
+        let program = [Instruction::JumpEq(Address::from(200)), Instruction::Halt];
+
+        assert_eq!(
+            verify(&program, 100),
+            Err(vec![
+                VerifyError::AddressOutOfRange {
+                    index: 0,
+                    address: Address::from(200)
+                },
+                VerifyError::InvalidJumpTarget {
+                    index: 0,
+                    address: Address::from(200)
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn mem_copy_source_out_of_range() {
+        let program = [
+            Instruction::MemCopy(Address::from(10), Address::from(300), Register::R1),
+            Instruction::Halt,
+        ];
+
+        assert_eq!(
+            verify(&program, 100),
+            Err(vec![VerifyError::AddressOutOfRange {
+                index: 0,
+                address: Address::from(300)
+            }])
+        );
+    }
+
+    #[test]
+    fn jump_into_instruction() {
+        // 1 is the register operand of the mov
+        let program = [
+            Instruction::MovRegNum(Register::R1, Value::U8(1)),
+            Instruction::JumpNe(Address::from(1)),
+            Instruction::Halt,
+        ];
+
+        assert_eq!(
+            verify(&program, 100),
+            Err(vec![VerifyError::InvalidJumpTarget {
+                index: 1,
+                address: Address::from(1)
+            }])
+        );
+    }
+
+    #[test]
+    fn loop_without_exit() {
+        // the halt is reachable, but once the loop is entered it never gets there
+        let program = [
+            Instruction::JumpEq(Address::from(6)),
+            Instruction::Halt,
+            Instruction::IncReg(Register::R1),
+            Instruction::Jump(Address::from(6)),
+        ];
+
+        assert_eq!(
+            verify(&program, 100),
+            Err(vec![VerifyError::MissingTerminator])
+        );
+    }
+
+    #[test]
+    fn no_terminator() {
+        let program = [
+            Instruction::MovRegNum(Register::R1, Value::U8(1)),
+            Instruction::AddRegNum(Register::R1, Value::U8(1)),
+        ];
+
+        assert_eq!(
+            verify(&program, 100),
+            Err(vec![VerifyError::MissingTerminator])
+        );
+    }
+
+    #[test]
+    fn terminator_on_one_branch() {
+        // the jump skips the halt, so the taken branch runs off the end
+        let program = [
+            Instruction::JumpEq(Address::from(6)),
+            Instruction::Halt,
+            Instruction::IncReg(Register::R1),
+        ];
+
+        assert_eq!(
+            verify(&program, 100),
+            Err(vec![VerifyError::MissingTerminator])
+        );
+    }
+
+    fn encode(program: &[Instruction]) -> Vec<u8> {
+        program.iter().flat_map(Instruction::encode).collect()
+    }
+
+    #[test]
+    fn valid_bytecode() {
+        let bytes = encode(&[
+            Instruction::MovRegNum(Register::R1, Value::U32(70000)),
+            Instruction::AddRegReg(Register::R1, Register::R2),
+            Instruction::Halt,
+        ]);
+
+        assert_eq!(validate_program(&bytes), Ok(()));
+        assert_eq!(validate_program(&[]), Ok(()));
+    }
+
+    #[test]
+    fn truncated_instruction() {
+        let mut bytes = encode(&[Instruction::IncReg(Register::R1), Instruction::Nop]);
+        let offset = bytes.len();
+        bytes.extend(Instruction::Jump(Address::from(0)).encode());
+        bytes.pop();
+
+        assert_eq!(
+            validate_program(&bytes),
+            Err(ValidationError::Truncated {
+                offset,
+                op: OpCode::Jump
+            })
+        );
+    }
+
+    #[test]
+    fn bad_register() {
+        let mut bytes = encode(&[Instruction::Nop, Instruction::Nop]);
+        bytes.extend([OpCode::PushReg as u8, 200]);
+
+        assert_eq!(
+            validate_program(&bytes),
+            Err(ValidationError::InvalidRegister {
+                offset: 2,
+                byte: 200
+            })
+        );
+    }
+
+    #[test]
+    fn bad_opcode() {
+        let mut bytes = encode(&[Instruction::Halt]);
+        bytes.push(0xff);
+
+        assert_eq!(
+            validate_program(&bytes),
+            Err(ValidationError::InvalidOpCode {
+                offset: 1,
+                byte: 0xff
+            })
+        );
+    }
+}
@@ -0,0 +1,258 @@
+use std::{collections::HashMap, fmt::Display};
+
+use vm_cpu::{
+    memory::Address,
+    opcodes::{Instruction, Value},
+    registers::Register,
+};
+
+use crate::parser::{Expr, Stmt};
+
+#[derive(Debug, PartialEq)]
+pub enum CodegenError {
+    UnknownIdent(String),
+    /// the expression has no lowering yet, comparisons can only be evaluated
+    Unsupported(String),
+}
+
+impl Display for CodegenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CodegenError::UnknownIdent(ident) => write!(f, "unknown identifier {ident}"),
+            CodegenError::Unsupported(expr) => write!(f, "can not compile {expr} yet"),
+        }
+    }
+}
+
+impl std::error::Error for CodegenError {}
+
+/// where each variable lives. every variable gets a 4 byte slot, the slots are laid out after
+/// the code in the order the variables are first assigned
+#[derive(Debug, Default, PartialEq)]
+pub struct Symbols<'a> {
+    slots: HashMap<&'a str, u32>,
+    /// where the first slot starts, relative to the program start
+    base: u32,
+}
+
+impl<'a> Symbols<'a> {
+    /// the slot of `name`, giving it a new one if it has none yet
+    fn slot_or_insert(&mut self, name: &'a str) -> u32 {
+        let next = self.slots.len() as u32;
+        *self.slots.entry(name).or_insert(next)
+    }
+
+    /// the address of the variable's slot, relative to the program start
+    pub fn address(&self, name: &str) -> Option<Address> {
+        self.slots
+            .get(name)
+            .map(|slot| Address::from(self.base + slot * 4))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&'a str, Address)> + '_ {
+        self.slots
+            .keys()
+            .filter_map(|name| Some((*name, self.address(name)?)))
+    }
+}
+
+/// a compiled program and the variables it assigns
+#[derive(Debug)]
+pub struct Program<'a> {
+    pub insts: Vec<Instruction>,
+    pub symbols: Symbols<'a>,
+}
+
+/// pushes the value of `expr`, every operator pops its two operands into R1 and R2. variables
+/// are read from the slot index they are given in `symbols` and moved to their address once
+/// the size of the code is known
+fn emit(expr: &Expr, symbols: &Symbols, insts: &mut Vec<Instruction>) -> Result<(), CodegenError> {
+    let (op, lhs, rhs) = match expr {
+        Expr::Num(val) => {
+            insts.push(Instruction::PushVal(Value::U32(*val)));
+            return Ok(());
+        }
+        Expr::Ident(ident) => {
+            let Some(slot) = symbols.slots.get(ident) else {
+                return Err(CodegenError::UnknownIdent(ident.to_string()));
+            };
+            insts.push(Instruction::PushMem(Address::from(slot * 4)));
+            return Ok(());
+        }
+        Expr::Add(lhs, rhs) => (Instruction::AddRegReg(Register::R1, Register::R2), lhs, rhs),
+        Expr::Sub(lhs, rhs) => (Instruction::SubRegReg(Register::R1, Register::R2), lhs, rhs),
+        Expr::Mul(lhs, rhs) => (Instruction::MulRegReg(Register::R1, Register::R2), lhs, rhs),
+        Expr::Div(lhs, rhs) => (Instruction::DivRegReg(Register::R1, Register::R2), lhs, rhs),
+        Expr::Eq(..) | Expr::Gt(..) | Expr::Gte(..) | Expr::Lt(..) | Expr::Lte(..) => {
+            return Err(CodegenError::Unsupported(expr.to_string()));
+        }
+    };
+
+    emit(lhs, symbols, insts)?;
+    emit(rhs, symbols, insts)?;
+
+    insts.extend([
+        Instruction::PopReg(Register::R2),
+        Instruction::PopReg(Register::R1),
+        op,
+        Instruction::PushReg(Register::R1),
+    ]);
+
+    Ok(())
+}
+
+/// compiles an arithmetic expression into a stack machine program that leaves its result in
+/// R1 and halts
+pub fn compile(expr: &Expr) -> Result<Vec<Instruction>, CodegenError> {
+    let mut insts = Vec::new();
+
+    emit(expr, &Symbols::default(), &mut insts)?;
+    insts.extend([Instruction::PopReg(Register::R1), Instruction::Halt]);
+
+    Ok(insts)
+}
+
+/// compiles every statement in order. an assignment stores its value in the variable's slot,
+/// a bare expression leaves its value in R1. a variable has to be assigned before it is read
+pub fn compile_program<'a>(stmts: &[Stmt<'a>]) -> Result<Program<'a>, CodegenError> {
+    let mut insts = Vec::new();
+    let mut symbols = Symbols::default();
+
+    for stmt in stmts {
+        match stmt {
+            Stmt::Assign { name, expr } => {
+                emit(expr, &symbols, &mut insts)?;
+                let slot = symbols.slot_or_insert(name);
+                insts.extend([
+                    Instruction::PopReg(Register::R1),
+                    Instruction::MovRegMem(Register::R1, Address::from(slot * 4)),
+                ]);
+            }
+            Stmt::Expr(expr) => {
+                emit(expr, &symbols, &mut insts)?;
+                insts.push(Instruction::PopReg(Register::R1));
+            }
+        }
+    }
+    insts.push(Instruction::Halt);
+
+    // every address so far is a slot offset, the slots start right after the code
+    symbols.base = insts.iter().map(|inst| inst.size() as u32).sum();
+    for inst in insts.iter_mut() {
+        match inst {
+            Instruction::PushMem(addr) | Instruction::MovRegMem(_, addr) => {
+                *addr = Address::from(symbols.base + addr.0)
+            }
+            _ => {}
+        }
+    }
+
+    Ok(Program { insts, symbols })
+}
+
+#[cfg(test)]
+mod test {
+    use vm_cpu::{
+        cpu::Cpu,
+        memory::{Address, CpuMemory, Memory},
+        registers::Register,
+    };
+
+    use crate::{lexer::Lexer, parser::Parser};
+
+    use super::{compile, compile_program, CodegenError, Program};
+
+    fn run(src: &str) -> Result<u32, CodegenError> {
+        let tokens = Lexer::new(src).lex().unwrap();
+        let ast = Parser::default().parse(tokens).unwrap();
+
+        let bytes = compile(&ast.expr().unwrap())?
+            .iter()
+            .flat_map(|inst| inst.encode())
+            .collect::<Vec<_>>();
+
+        let mut memory = CpuMemory::default();
+        memory.write_bytes(Address::from(0), &bytes).unwrap();
+
+        let mut cpu = Cpu::new(memory, 0, u16::MAX as u32, 0.into());
+        cpu.execute();
+
+        Ok(cpu.registers().get(Register::R1))
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(run("1 + 2 * 3"), Ok(7));
+    }
+
+    #[test]
+    fn parens() {
+        assert_eq!(run("(1 + 2) * 3"), Ok(9));
+        assert_eq!(run("20 / (6 - 1)"), Ok(4));
+    }
+
+    fn run_program(src: &str) -> Result<(Cpu, Program<'_>), CodegenError> {
+        let tokens = Lexer::new(src).lex().unwrap();
+        let ast = Parser::default().parse(tokens).unwrap();
+        let program = compile_program(&ast.statements().unwrap())?;
+
+        let bytes = program
+            .insts
+            .iter()
+            .flat_map(|inst| inst.encode())
+            .collect::<Vec<_>>();
+
+        let mut cpu = Cpu::new(CpuMemory::default(), 16, u16::MAX as u32, 0.into());
+        cpu.load_program(&bytes).unwrap();
+        cpu.execute();
+
+        Ok((cpu, program))
+    }
+
+    fn variable(cpu: &Cpu, program: &Program, name: &str) -> u32 {
+        let addr = program.symbols.address(name).unwrap();
+        cpu.memory()
+            .read_u32((Address::from(16) + addr).unwrap())
+            .unwrap()
+    }
+
+    #[test]
+    fn assignment() {
+        let (cpu, program) = run_program("x = 1 + 2").unwrap();
+
+        assert_eq!(variable(&cpu, &program, "x"), 3);
+    }
+
+    #[test]
+    fn variables() {
+        let (cpu, program) = run_program("x = 2\ny = x * 4\nx = y - x\nx + y").unwrap();
+
+        assert_eq!(variable(&cpu, &program, "x"), 6);
+        assert_eq!(variable(&cpu, &program, "y"), 8);
+        assert_eq!(cpu.registers().get(Register::R1), 14);
+    }
+
+    #[test]
+    fn read_before_assignment() {
+        assert_eq!(
+            run_program("y = x").unwrap_err(),
+            CodegenError::UnknownIdent("x".to_string())
+        );
+    }
+
+    #[test]
+    fn comparison() {
+        assert_eq!(
+            run("1 < 2"),
+            Err(CodegenError::Unsupported("(1 < 2)".to_string()))
+        );
+    }
+
+    #[test]
+    fn unknown_ident() {
+        assert_eq!(
+            run("x + 1"),
+            Err(CodegenError::UnknownIdent("x".to_string()))
+        );
+    }
+}
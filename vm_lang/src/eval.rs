@@ -0,0 +1,114 @@
+use std::fmt::Display;
+
+use crate::parser::{Expr, Types};
+
+#[derive(Debug, PartialEq)]
+pub enum EvalError {
+    UnknownIdent(String),
+    DivideByZero,
+    /// an operator that works on numbers was given a bool
+    ExpectedNumber(String),
+}
+
+impl Display for EvalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EvalError::UnknownIdent(ident) => write!(f, "unknown identifier {ident}"),
+            EvalError::DivideByZero => write!(f, "divide by zero"),
+            EvalError::ExpectedNumber(expr) => write!(f, "expected a number, found {expr}"),
+        }
+    }
+}
+
+impl std::error::Error for EvalError {}
+
+/// evaluates `expr` without compiling it. arithmetic wraps like it does on the cpu and
+/// comparisons give a [`Types::Bool`]
+pub fn eval(expr: &Expr) -> Result<Types, EvalError> {
+    let (lhs, rhs) = match expr {
+        Expr::Num(val) => return Ok(Types::U32(*val)),
+        Expr::Ident(ident) => return Err(EvalError::UnknownIdent(ident.to_string())),
+        Expr::Add(lhs, rhs)
+        | Expr::Sub(lhs, rhs)
+        | Expr::Mul(lhs, rhs)
+        | Expr::Div(lhs, rhs)
+        | Expr::Eq(lhs, rhs)
+        | Expr::Gt(lhs, rhs)
+        | Expr::Gte(lhs, rhs)
+        | Expr::Lt(lhs, rhs)
+        | Expr::Lte(lhs, rhs) => (number(lhs)?, number(rhs)?),
+    };
+
+    Ok(match expr {
+        Expr::Add(..) => Types::U32(lhs.wrapping_add(rhs)),
+        Expr::Sub(..) => Types::U32(lhs.wrapping_sub(rhs)),
+        Expr::Mul(..) => Types::U32(lhs.wrapping_mul(rhs)),
+        Expr::Div(..) => Types::U32(lhs.checked_div(rhs).ok_or(EvalError::DivideByZero)?),
+        Expr::Eq(..) => Types::Bool(lhs == rhs),
+        Expr::Gt(..) => Types::Bool(lhs > rhs),
+        Expr::Gte(..) => Types::Bool(lhs >= rhs),
+        Expr::Lt(..) => Types::Bool(lhs < rhs),
+        Expr::Lte(..) => Types::Bool(lhs <= rhs),
+        Expr::Num(_) | Expr::Ident(_) => unreachable!("leaves are returned above"),
+    })
+}
+
+fn number(expr: &Expr) -> Result<u32, EvalError> {
+    match eval(expr)? {
+        Types::U32(val) => Ok(val),
+        Types::Bool(_) => Err(EvalError::ExpectedNumber(expr.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        lexer::Lexer,
+        parser::{Parser, Types},
+    };
+
+    use super::{eval, EvalError};
+
+    fn run(src: &str) -> Result<Types, EvalError> {
+        let tokens = Lexer::new(src).lex().unwrap();
+        let ast = Parser::default().parse(tokens).unwrap();
+
+        eval(&ast.expr().unwrap())
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(run("1 + 2 * 3"), Ok(Types::U32(7)));
+        assert_eq!(run("0 - 1"), Ok(Types::U32(u32::MAX)));
+        assert_eq!(run("1 / 0"), Err(EvalError::DivideByZero));
+    }
+
+    #[test]
+    fn comparisons() {
+        let cases = [
+            ("3 > 2", true),
+            ("2 > 3", false),
+            ("2 >= 2", true),
+            ("1 >= 2", false),
+            ("1 < 2", true),
+            ("2 < 2", false),
+            ("2 <= 2", true),
+            ("3 <= 2", false),
+            ("4 == 2 + 2", true),
+            ("4 == 5", false),
+        ];
+
+        for (src, expected) in cases {
+            assert_eq!(run(src), Ok(Types::Bool(expected)), "{src}");
+        }
+    }
+
+    #[test]
+    fn bool_operand() {
+        assert_eq!(
+            run("(1 < 2) + 1"),
+            Err(EvalError::ExpectedNumber("(1 < 2)".to_string()))
+        );
+        assert_eq!(run("x > 1"), Err(EvalError::UnknownIdent("x".to_string())));
+    }
+}
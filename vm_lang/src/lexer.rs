@@ -1,10 +1,31 @@
 use std::fmt::Display;
 
-use tracing::info;
+use tracing::trace;
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum LexError {
-    InvalidToken(String),
+    /// `offset` is in bytes, `line` and `column` count from 1
+    InvalidToken {
+        token: char,
+        offset: usize,
+        line: usize,
+        column: usize,
+    },
+}
+
+impl LexError {
+    fn invalid_token(data: &str, token: char, offset: usize) -> Self {
+        let before = &data[..offset];
+        let line = before.matches('\n').count() + 1;
+        let column = before.chars().rev().take_while(|c| *c != '\n').count() + 1;
+
+        LexError::InvalidToken {
+            token,
+            offset,
+            line,
+            column,
+        }
+    }
 }
 
 impl std::error::Error for LexError {}
@@ -12,7 +33,15 @@ impl std::error::Error for LexError {}
 impl Display for LexError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            LexError::InvalidToken(tok) => write!(f, "{tok}"),
+            LexError::InvalidToken {
+                token,
+                offset,
+                line,
+                column,
+            } => write!(
+                f,
+                "invalid token '{token}' at offset {offset} (line {line}, column {column})"
+            ),
         }
     }
 }
@@ -23,6 +52,8 @@ pub enum Token<'a> {
     RCarrot,
     EqSign,
     Space,
+    /// ends a statement, written as a line break or `;`
+    Newline,
     Number(&'a str),
     Lparen,
     Rparen,
@@ -39,9 +70,9 @@ pub struct Lexer<'a> {
 }
 
 #[tracing::instrument]
-fn lex_number(src: &str) -> (Token, usize) {
+fn lex_number(src: &str) -> (Token<'_>, usize) {
     let mut start = None;
-    for (i, ch) in src.chars().enumerate() {
+    for (i, ch) in src.char_indices() {
         match ch {
             '0'..='9' => {
                 if start.is_none() {
@@ -57,27 +88,18 @@ fn lex_number(src: &str) -> (Token, usize) {
     (Token::Number(&src[start.unwrap()..]), src.len())
 }
 
+/// an identifier starts with a letter and may contain digits after it
 #[tracing::instrument]
-fn lex_ident(src: &str) -> &str {
-    let mut start = None;
-    for (i, ch) in src.chars().enumerate() {
-        match ch {
-            'a'..='z' | 'A'..='Z' => {
-                if start.is_none() {
-                    start = Some(i)
-                }
-            }
-            '0'..='9' => {
-                if start.is_none() {
-                    panic!()
-                }
-            }
-            _ => {
-                return &src[start.unwrap()..i];
-            }
-        }
+fn lex_ident(src: &str) -> Option<&str> {
+    if !src.starts_with(|ch: char| ch.is_ascii_alphabetic()) {
+        return None;
     }
-    src
+
+    let end = src
+        .find(|ch: char| !ch.is_ascii_alphanumeric())
+        .unwrap_or(src.len());
+
+    Some(&src[..end])
 }
 
 impl<'a> Lexer<'a> {
@@ -91,13 +113,13 @@ impl<'a> Lexer<'a> {
 
         let mut idx = 0;
 
-        let chars = self.data.chars().collect::<Vec<_>>();
+        let chars = self.data.char_indices().collect::<Vec<_>>();
 
-        loop {
-            let Some(ch) = chars.get(idx) else { break };
-            info!(?ch);
+        while let Some((offset, ch)) = chars.get(idx) {
+            trace!(?ch);
             let tok = match ch {
                 ' ' => Token::Space,
+                '\n' | ';' => Token::Newline,
                 '>' => Token::RCarrot,
                 '<' => Token::LCarrot,
                 '=' => Token::EqSign,
@@ -108,24 +130,34 @@ impl<'a> Lexer<'a> {
                 '*' => Token::Mult,
                 '/' => Token::Div,
                 '0'..='9' => {
-                    let (num, amount) = lex_number(&self.data[idx..]);
+                    let (num, amount) = lex_number(&self.data[*offset..]);
+
+                    // a letter straight after a number would otherwise start an identifier
+                    if let Some(next) = self.data[offset + amount..].chars().next() {
+                        if next.is_ascii_alphabetic() {
+                            return Err(LexError::invalid_token(self.data, next, offset + amount));
+                        }
+                    }
+
                     idx += amount.saturating_sub(1);
                     num
                 }
                 'a'..='z' | 'A'..='Z' => {
-                    let ident = lex_ident(&self.data[idx..]);
+                    let Some(ident) = lex_ident(&self.data[*offset..]) else {
+                        return Err(LexError::invalid_token(self.data, *ch, *offset));
+                    };
                     idx += ident.len().saturating_sub(1);
                     Token::Ident(ident)
                 }
 
-                _ => return Err(LexError::InvalidToken(ch.to_string())),
+                _ => return Err(LexError::invalid_token(self.data, *ch, *offset)),
             };
             idx += 1;
 
             tokens.push(tok);
         }
 
-        info!(?tokens);
+        trace!(?tokens);
 
         Ok(tokens)
     }
@@ -133,9 +165,9 @@ impl<'a> Lexer<'a> {
 
 #[cfg(test)]
 mod test {
-    use super::{Lexer, Token};
+    use super::{LexError, Lexer, Token};
 
-    fn lex(data: &str) -> Vec<Token> {
+    fn lex(data: &str) -> Vec<Token<'_>> {
         Lexer::new(data).lex().unwrap()
     }
 
@@ -184,13 +216,74 @@ mod test {
         assert_eq!(lex("="), [Token::EqSign])
     }
 
+    #[test]
+    fn newline() {
+        assert_eq!(
+            lex("a\nb;c"),
+            [
+                Token::Ident("a"),
+                Token::Newline,
+                Token::Ident("b"),
+                Token::Newline,
+                Token::Ident("c")
+            ]
+        )
+    }
+
     #[test]
     fn number() {
         assert_eq!(lex("123456"), [Token::Number("123456")])
     }
 
+    #[test]
+    fn invalid_token_offset() {
+        let err = Lexer::new("12 # 3").lex().unwrap_err();
+
+        assert_eq!(
+            err,
+            LexError::InvalidToken {
+                token: '#',
+                offset: 3,
+                line: 1,
+                column: 4
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "invalid token '#' at offset 3 (line 1, column 4)"
+        );
+    }
+
     #[test]
     fn ident() {
         assert_eq!(lex("hello"), [Token::Ident("hello")])
     }
+
+    #[test]
+    fn ident_with_digits() {
+        assert_eq!(lex("abc123"), [Token::Ident("abc123")]);
+        assert_eq!(
+            lex("r2d2 + 1"),
+            [
+                Token::Ident("r2d2"),
+                Token::Space,
+                Token::Plus,
+                Token::Space,
+                Token::Number("1")
+            ]
+        );
+    }
+
+    #[test]
+    fn number_then_letters() {
+        assert_eq!(
+            Lexer::new("1abc").lex().unwrap_err(),
+            LexError::InvalidToken {
+                token: 'a',
+                offset: 1,
+                line: 1,
+                column: 2
+            }
+        );
+    }
 }
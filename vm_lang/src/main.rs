@@ -1,28 +1,161 @@
-use std::path::PathBuf;
+use std::{error::Error, path::PathBuf};
 
 use clap::Parser;
 use lexer::Lexer;
-use tracing::info;
+use tracing::level_filters::LevelFilter;
+use vm_cpu::{
+    cpu::{Cpu, DEFAULT_STACK_START},
+    memory::{CpuMemory, Memory},
+    opcodes::Instruction,
+    registers::Register,
+};
 
+mod codegen;
+mod eval;
 mod lexer;
 mod parser;
+mod repl;
 
 #[derive(clap::Parser, Debug)]
 struct Args {
-    input: PathBuf,
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// the file to compile and run, the repl is started when it is left out
+    input: Option<PathBuf>,
+    /// write the compiled bytecode to this file instead of running it
+    #[arg(short, long, requires = "input")]
+    output: Option<PathBuf>,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum Command {
+    /// evaluates expressions read from stdin, one per line
+    Repl,
+    /// compiles a single expression, runs it on the cpu and prints R1
+    Expr { expr: String },
+}
+
+/// encodes `insts`, loads them at address 0 and runs them until they halt
+fn run(insts: &[Instruction]) -> Result<Cpu, Box<dyn Error>> {
+    let bytes = insts
+        .iter()
+        .flat_map(Instruction::encode)
+        .collect::<Vec<_>>();
+
+    let mut cpu = Cpu::new(CpuMemory::default(), 0, DEFAULT_STACK_START, 0.into());
+    cpu.load_program(&bytes)?;
+    while cpu.step()?.is_continue() {}
+
+    Ok(cpu)
+}
+
+/// runs a program and lists R1 followed by every variable, ordered by name
+fn run_file(src: &str) -> Result<String, Box<dyn Error>> {
+    let tokens = Lexer::new(src).lex()?;
+    let ast = parser::Parser::default().parse(tokens)?;
+    let program = codegen::compile_program(&ast.statements()?)?;
+
+    let cpu = run(&program.insts)?;
+
+    let mut variables = program.symbols.iter().collect::<Vec<_>>();
+    variables.sort();
+
+    let mut out = format!("r1 = {}", cpu.registers().get(Register::R1));
+    for (name, addr) in variables {
+        let val = cpu
+            .memory()
+            .read_u32(addr)
+            .map_err(vm_cpu::error::Error::from)?;
+        out.push_str(&format!("\n{name} = {val}"));
+    }
+
+    Ok(out)
+}
+
+/// the value of a single expression, computed on the cpu
+fn run_expr(src: &str) -> Result<u32, Box<dyn Error>> {
+    let tokens = Lexer::new(src).lex()?;
+    let ast = parser::Parser::default().parse(tokens)?;
+    let cpu = run(&codegen::compile(&ast.expr()?)?)?;
+
+    Ok(cpu.registers().get(Register::R1))
+}
+
+/// the bytecode of a program, ready for `vm_cpu run`
+fn compile_file(src: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    let tokens = Lexer::new(src).lex()?;
+    let ast = parser::Parser::default().parse(tokens)?;
+    let program = codegen::compile_program(&ast.statements()?)?;
+
+    Ok(program.insts.iter().flat_map(Instruction::encode).collect())
 }
 
 fn main() {
-    // let args = Args::parse();
+    // logs go to stderr so they never mix with the repl's results
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .with_max_level(LevelFilter::WARN)
+        .init();
+
+    let args = Args::parse();
+
+    let res = match (args.command, args.input) {
+        (Some(Command::Expr { expr }), _) => run_expr(&expr).map(|val| val.to_string()),
+        (None, Some(input)) => std::fs::read_to_string(&input)
+            .map_err(|e| format!("failed to read {}: {e}", input.display()).into())
+            .and_then(|src| match &args.output {
+                Some(output) => compile_file(&src).and_then(|bytes| {
+                    std::fs::write(output, bytes)?;
+                    Ok(String::new())
+                }),
+                None => run_file(&src),
+            }),
+        _ => {
+            repl::repl(std::io::stdin().lock(), std::io::stdout()).expect("failed to run the repl");
+            return;
+        }
+    };
+
+    match res {
+        Ok(out) if out.is_empty() => {}
+        Ok(out) => println!("{out}"),
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use vm_cpu::{cpu::Cpu, memory::CpuMemory, registers::Register};
+
+    use super::{compile_file, run_expr, run_file};
+
+    #[test]
+    fn runs_file() {
+        let out = run_file("x = 2\ny = x * 4\nx + y").unwrap();
+
+        assert_eq!(out, "r1 = 10\nx = 2\ny = 8");
+    }
 
-    // let file = std::fs::read_to_string(args.input).expect("failed to read input file");
-    let data = r#"= >= <= == > < 12345"#;
+    #[test]
+    fn runs_expr() {
+        assert_eq!(run_expr("1 + 2 * 3").unwrap(), 7);
+        assert_eq!(
+            run_expr("y + 1").unwrap_err().to_string(),
+            "unknown identifier y"
+        );
+    }
 
-    let tokens = Lexer::new(data).lex().unwrap();
+    #[test]
+    fn compiles_file() {
+        let bytes = compile_file("x = 1 + 2\nx * 3").unwrap();
 
-    let ast = parser::Parser::default()
-        .parse(tokens)
-        .expect("failed to parse input file");
+        let mut cpu = Cpu::new(CpuMemory::default(), 0, u16::MAX as u32, 0.into());
+        cpu.load_program(&bytes).unwrap();
+        cpu.execute();
 
-    info!(?ast);
+        assert_eq!(cpu.registers().get(Register::R1), 9);
+    }
 }
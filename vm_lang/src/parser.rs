@@ -1,19 +1,25 @@
 use std::fmt::Display;
 
-use tracing::{info, level_filters::LevelFilter};
-use tracing_subscriber::util::SubscriberInitExt;
+use tracing::trace;
 
 use crate::lexer::{LexError, Token};
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum ParserError {
     InvalidToken(String),
+    UnexpectedEnd,
+    UnbalancedParens,
+    /// a number literal that does not fit in a u32, the widest value the vm has
+    NumberTooLarge(String),
 }
 
 impl Display for ParserError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             ParserError::InvalidToken(tok) => write!(f, "{tok:?}"),
+            ParserError::UnexpectedEnd => write!(f, "unexpected end of input"),
+            ParserError::UnbalancedParens => write!(f, "unbalanced parentheses"),
+            ParserError::NumberTooLarge(num) => write!(f, "number {num} does not fit in a u32"),
         }
     }
 }
@@ -22,9 +28,7 @@ impl std::error::Error for ParserError {}
 
 impl From<LexError> for ParserError {
     fn from(value: LexError) -> Self {
-        match value {
-            LexError::InvalidToken(tok) => Self::InvalidToken(tok),
-        }
+        Self::InvalidToken(value.to_string())
     }
 }
 
@@ -36,6 +40,7 @@ pub struct Parser<'a> {
 #[derive(PartialEq, Debug)]
 pub enum Node<'a> {
     Assign,
+    Newline,
     Eq,
     Gt,
     Gte,
@@ -48,7 +53,6 @@ pub enum Node<'a> {
     Lparen,
     Rparen,
     Ident(&'a str),
-    Types(Types),
     Add,
     Sub,
     Mult,
@@ -57,12 +61,19 @@ pub enum Node<'a> {
 
 #[derive(PartialEq, Debug)]
 pub enum Types {
-    U8(u8),
-    U16(u16),
     U32(u32),
     Bool(bool),
 }
 
+impl Display for Types {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Types::U32(val) => write!(f, "{val}"),
+            Types::Bool(val) => write!(f, "{val}"),
+        }
+    }
+}
+
 #[derive(Default, Debug)]
 pub struct Ast<'a> {
     nodes: Vec<Node<'a>>,
@@ -72,28 +83,189 @@ impl<'a> Ast<'a> {
     pub fn push(&mut self, node: Node<'a>) {
         self.nodes.push(node);
     }
+
+    /// groups the nodes into an expression tree, `*` and `/` bind tighter than `+` and `-`
+    pub fn expr(&self) -> Result<Expr<'a>, ParserError> {
+        parse_expr(
+            self.nodes
+                .iter()
+                .filter(|node| **node != Node::Space)
+                .collect(),
+        )
+    }
+
+    /// splits the nodes into one statement per line, skipping empty lines
+    pub fn statements(&self) -> Result<Vec<Stmt<'a>>, ParserError> {
+        self.nodes
+            .split(|node| *node == Node::Newline)
+            .map(|line| {
+                line.iter()
+                    .filter(|node| **node != Node::Space)
+                    .collect::<Vec<_>>()
+            })
+            .filter(|line| !line.is_empty())
+            .map(statement)
+            .collect()
+    }
 }
 
-impl<'a> Parser<'a> {
-    pub fn parse(self, tokens: Vec<Token<'a>>) -> Result<Ast<'a>, ParserError> {
-        let _ = tracing_subscriber::FmtSubscriber::builder()
-            .with_ansi(true)
-            .with_max_level(LevelFilter::INFO)
-            .finish()
-            .try_init();
+/// a line is either `name = expr` or a bare expression
+fn statement<'a>(line: Vec<&Node<'a>>) -> Result<Stmt<'a>, ParserError> {
+    match line.as_slice() {
+        [Node::Ident(name), Node::Assign, ..] => Ok(Stmt::Assign {
+            name,
+            expr: parse_expr(line[2..].to_vec())?,
+        }),
+        _ => Ok(Stmt::Expr(parse_expr(line)?)),
+    }
+}
 
-        let mut ast = Ast::default();
+fn parse_expr<'a>(nodes: Vec<&Node<'a>>) -> Result<Expr<'a>, ParserError> {
+    let mut parser = ExprParser { nodes, idx: 0 };
 
-        let mut idx = 0;
+    let expr = parser.expr(0)?;
+
+    match parser.next() {
+        None => Ok(expr),
+        Some(Node::Rparen) => Err(ParserError::UnbalancedParens),
+        Some(node) => Err(ParserError::InvalidToken(format!("{node:?}"))),
+    }
+}
 
-        loop {
-            let Some(token) = tokens.get(idx) else {
+#[derive(PartialEq, Debug)]
+pub enum Stmt<'a> {
+    Assign { name: &'a str, expr: Expr<'a> },
+    Expr(Expr<'a>),
+}
+
+#[derive(PartialEq, Debug)]
+pub enum Expr<'a> {
+    Num(u32),
+    Ident(&'a str),
+    Add(Box<Expr<'a>>, Box<Expr<'a>>),
+    Sub(Box<Expr<'a>>, Box<Expr<'a>>),
+    Mul(Box<Expr<'a>>, Box<Expr<'a>>),
+    Div(Box<Expr<'a>>, Box<Expr<'a>>),
+    Eq(Box<Expr<'a>>, Box<Expr<'a>>),
+    Gt(Box<Expr<'a>>, Box<Expr<'a>>),
+    Gte(Box<Expr<'a>>, Box<Expr<'a>>),
+    Lt(Box<Expr<'a>>, Box<Expr<'a>>),
+    Lte(Box<Expr<'a>>, Box<Expr<'a>>),
+}
+
+impl Display for Expr<'_> {
+    /// every operation is wrapped in parentheses, so the output shows how it was grouped
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Expr::Num(val) => write!(f, "{val}"),
+            Expr::Ident(ident) => write!(f, "{ident}"),
+            Expr::Add(lhs, rhs) => write!(f, "({lhs} + {rhs})"),
+            Expr::Sub(lhs, rhs) => write!(f, "({lhs} - {rhs})"),
+            Expr::Mul(lhs, rhs) => write!(f, "({lhs} * {rhs})"),
+            Expr::Div(lhs, rhs) => write!(f, "({lhs} / {rhs})"),
+            Expr::Eq(lhs, rhs) => write!(f, "({lhs} == {rhs})"),
+            Expr::Gt(lhs, rhs) => write!(f, "({lhs} > {rhs})"),
+            Expr::Gte(lhs, rhs) => write!(f, "({lhs} >= {rhs})"),
+            Expr::Lt(lhs, rhs) => write!(f, "({lhs} < {rhs})"),
+            Expr::Lte(lhs, rhs) => write!(f, "({lhs} <= {rhs})"),
+        }
+    }
+}
+
+impl Display for Stmt<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Stmt::Assign { name, expr } => write!(f, "{name} = {expr}"),
+            Stmt::Expr(expr) => write!(f, "{expr}"),
+        }
+    }
+}
+
+/// a pratt parser over the nodes of an [`Ast`]
+struct ExprParser<'n, 'a> {
+    nodes: Vec<&'n Node<'a>>,
+    idx: usize,
+}
+
+impl<'n, 'a> ExprParser<'n, 'a> {
+    fn next(&mut self) -> Option<&'n Node<'a>> {
+        let node = self.nodes.get(self.idx).copied();
+        self.idx += 1;
+        node
+    }
+
+    fn peek(&self) -> Option<&'n Node<'a>> {
+        self.nodes.get(self.idx).copied()
+    }
+
+    /// comparisons bind looser than arithmetic, so `1 + 2 > 2` compares the sum
+    fn binding_power(node: &Node) -> Option<u8> {
+        match node {
+            Node::Eq | Node::Gt | Node::Gte | Node::Lt | Node::Lte => Some(1),
+            Node::Add | Node::Sub => Some(2),
+            Node::Mult | Node::Div => Some(3),
+            _ => None,
+        }
+    }
+
+    fn expr(&mut self, min_bp: u8) -> Result<Expr<'a>, ParserError> {
+        let mut lhs = match self.next() {
+            Some(Node::U8(val)) => Expr::Num(*val as u32),
+            Some(Node::U16(val)) => Expr::Num(*val as u32),
+            Some(Node::U32(val)) => Expr::Num(*val),
+            Some(Node::Ident(ident)) => Expr::Ident(ident),
+            Some(Node::Lparen) => {
+                let expr = self.expr(0)?;
+                match self.next() {
+                    Some(Node::Rparen) => expr,
+                    _ => return Err(ParserError::UnbalancedParens),
+                }
+            }
+            Some(node) => return Err(ParserError::InvalidToken(format!("{node:?}"))),
+            None => return Err(ParserError::UnexpectedEnd),
+        };
+
+        while let Some(op) = self.peek() {
+            let Some(bp) = Self::binding_power(op) else {
                 break;
             };
+            if bp < min_bp {
+                break;
+            }
+            self.next();
+
+            // operators are left associative, so the right hand side only takes tighter ones
+            let rhs = Box::new(self.expr(bp + 1)?);
+            let lhs_box = Box::new(lhs);
+
+            lhs = match op {
+                Node::Add => Expr::Add(lhs_box, rhs),
+                Node::Sub => Expr::Sub(lhs_box, rhs),
+                Node::Mult => Expr::Mul(lhs_box, rhs),
+                Node::Div => Expr::Div(lhs_box, rhs),
+                Node::Eq => Expr::Eq(lhs_box, rhs),
+                Node::Gt => Expr::Gt(lhs_box, rhs),
+                Node::Gte => Expr::Gte(lhs_box, rhs),
+                Node::Lt => Expr::Lt(lhs_box, rhs),
+                Node::Lte => Expr::Lte(lhs_box, rhs),
+                _ => unreachable!("only operators have a binding power"),
+            };
+        }
+
+        Ok(lhs)
+    }
+}
 
+impl<'a> Parser<'a> {
+    pub fn parse(self, tokens: Vec<Token<'a>>) -> Result<Ast<'a>, ParserError> {
+        let mut ast = Ast::default();
+
+        let mut idx = 0;
+
+        while let Some(token) = tokens.get(idx) {
             if let Some(next_token) = tokens.get(idx + 1) {
-                info!(?token, ?next_token);
-                let peeked_node = peeked_tokens(token, Some(next_token));
+                trace!(?token, ?next_token);
+                let peeked_node = peeked_tokens(token, Some(next_token))?;
                 match peeked_node {
                     Node::Eq => idx += 1,
                     Node::Gte => idx += 1,
@@ -101,10 +273,10 @@ impl<'a> Parser<'a> {
                     _ => {}
                 }
 
-                info!(?peeked_node);
+                trace!(?peeked_node);
                 ast.push(peeked_node);
             } else {
-                let node = peeked_tokens(token, None);
+                let node = peeked_tokens(token, None)?;
 
                 ast.push(node);
             }
@@ -112,14 +284,32 @@ impl<'a> Parser<'a> {
             idx += 1;
         }
 
-        info!(?ast);
+        trace!(?ast);
 
         Ok(ast)
     }
 }
 
-fn peeked_tokens<'a>(token: &Token<'a>, peeked_token: Option<&Token<'a>>) -> Node<'a> {
-    match (token, peeked_token) {
+/// numbers get the smallest width they fit in
+fn number<'a>(val: &str) -> Result<Node<'a>, ParserError> {
+    let val = val
+        .parse::<u32>()
+        .map_err(|_| ParserError::NumberTooLarge(val.to_string()))?;
+
+    Ok(if let Ok(val) = u8::try_from(val) {
+        Node::U8(val)
+    } else if let Ok(val) = u16::try_from(val) {
+        Node::U16(val)
+    } else {
+        Node::U32(val)
+    })
+}
+
+fn peeked_tokens<'a>(
+    token: &Token<'a>,
+    peeked_token: Option<&Token<'a>>,
+) -> Result<Node<'a>, ParserError> {
+    let node = match (token, peeked_token) {
         (Token::LCarrot, Some(Token::EqSign)) => Node::Lte,
         (Token::RCarrot, Some(Token::EqSign)) => Node::Gte,
         (Token::EqSign, Some(Token::EqSign)) => Node::Eq,
@@ -128,19 +318,8 @@ fn peeked_tokens<'a>(token: &Token<'a>, peeked_token: Option<&Token<'a>>) -> Nod
             Token::RCarrot => Node::Gt,
             Token::EqSign => Node::Assign,
             Token::Space => Node::Space,
-            Token::Number(val) => {
-                if let Ok(val) = val.parse::<u8>() {
-                    return Node::U8(val);
-                }
-                if let Ok(val) = val.parse::<u16>() {
-                    return Node::U16(val);
-                }
-                if let Ok(val) = val.parse::<u32>() {
-                    Node::U32(val)
-                } else {
-                    unreachable!()
-                }
-            }
+            Token::Newline => Node::Newline,
+            Token::Number(val) => number(val)?,
             Token::Lparen => Node::Lparen,
             Token::Rparen => Node::Rparen,
             Token::Ident(ident) => Node::Ident(ident),
@@ -149,7 +328,9 @@ fn peeked_tokens<'a>(token: &Token<'a>, peeked_token: Option<&Token<'a>>) -> Nod
             Token::Mult => Node::Mult,
             Token::Div => Node::Div,
         },
-    }
+    };
+
+    Ok(node)
 }
 
 #[cfg(test)]
@@ -158,18 +339,19 @@ mod test {
     use tracing::{info, level_filters::LevelFilter};
     use tracing_subscriber::util::SubscriberInitExt;
 
-    use crate::{
-        lexer::Lexer,
-        parser::{Node, Types},
-    };
+    use crate::{lexer::Lexer, parser::Node};
+
+    use super::{Ast, Expr, Parser, ParserError, Stmt};
 
-    use super::{Ast, Parser, ParserError};
+    fn num(val: u32) -> Box<Expr<'static>> {
+        Box::new(Expr::Num(val))
+    }
 
     #[derive(Debug, Default)]
     struct TestRunner {}
 
     impl TestRunner {
-        pub fn run(src: &str) -> Result<Ast, ParserError> {
+        pub fn run(src: &str) -> Result<Ast<'_>, ParserError> {
             let _ = tracing_subscriber::FmtSubscriber::builder()
                 .with_ansi(true)
                 .with_max_level(LevelFilter::INFO)
@@ -252,6 +434,28 @@ mod test {
         assert_eq!(TestRunner::run("12345").unwrap().nodes, [Node::U16(12345)]);
     }
 
+    #[test]
+    pub fn number_widths() {
+        assert_eq!(
+            TestRunner::run("255 256 65536").unwrap().nodes,
+            [
+                Node::U8(255),
+                Node::Space,
+                Node::U16(256),
+                Node::Space,
+                Node::U32(65536)
+            ]
+        );
+    }
+
+    #[test]
+    pub fn number_too_large() {
+        assert_eq!(
+            TestRunner::run("99999999999999999999").unwrap_err(),
+            ParserError::NumberTooLarge("99999999999999999999".to_string())
+        );
+    }
+
     #[test]
     pub fn idents() {
         assert_eq!(
@@ -311,6 +515,102 @@ mod test {
             ]
         )
     }
+    #[test]
+    fn precedence() {
+        assert_eq!(
+            TestRunner::run("1 + 2 * 3").unwrap().expr().unwrap(),
+            Expr::Add(num(1), Box::new(Expr::Mul(num(2), num(3))))
+        );
+    }
+
+    #[test]
+    fn parens() {
+        assert_eq!(
+            TestRunner::run("(1 + 2) * 3").unwrap().expr().unwrap(),
+            Expr::Mul(Box::new(Expr::Add(num(1), num(2))), num(3))
+        );
+    }
+
+    #[test]
+    fn left_associative() {
+        assert_eq!(
+            TestRunner::run("8 - 4 - 2").unwrap().expr().unwrap(),
+            Expr::Sub(Box::new(Expr::Sub(num(8), num(4))), num(2))
+        );
+    }
+
+    #[test]
+    fn unbalanced_parens() {
+        assert_eq!(
+            TestRunner::run("(1 + 2").unwrap().expr(),
+            Err(ParserError::UnbalancedParens)
+        );
+        assert_eq!(
+            TestRunner::run("1 + 2)").unwrap().expr(),
+            Err(ParserError::UnbalancedParens)
+        );
+    }
+
+    #[test]
+    fn comparison_precedence() {
+        assert_eq!(
+            TestRunner::run("1 + 2 > 2 * 1").unwrap().expr().unwrap(),
+            Expr::Gt(
+                Box::new(Expr::Add(num(1), num(2))),
+                Box::new(Expr::Mul(num(2), num(1)))
+            )
+        );
+    }
+
+    #[test]
+    fn display() {
+        let ast = TestRunner::run("1 + 2 * 3").unwrap();
+        assert_eq!(ast.expr().unwrap().to_string(), "(1 + (2 * 3))");
+
+        let ast = TestRunner::run("x = (a - 1) / 2 - b\n8 - 4 - 2").unwrap();
+        let printed = ast
+            .statements()
+            .unwrap()
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(printed, ["x = (((a - 1) / 2) - b)", "((8 - 4) - 2)"]);
+    }
+
+    #[test]
+    fn statements() {
+        assert_eq!(
+            TestRunner::run("a = 1\nb = 2")
+                .unwrap()
+                .statements()
+                .unwrap(),
+            [
+                Stmt::Assign {
+                    name: "a",
+                    expr: Expr::Num(1)
+                },
+                Stmt::Assign {
+                    name: "b",
+                    expr: Expr::Num(2)
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn semicolons_and_blank_lines() {
+        assert_eq!(
+            TestRunner::run("1 + 2;\n\n3")
+                .unwrap()
+                .statements()
+                .unwrap(),
+            [
+                Stmt::Expr(Expr::Add(num(1), num(2))),
+                Stmt::Expr(Expr::Num(3))
+            ]
+        );
+    }
+
     #[test]
     fn div() {
         let nodes = TestRunner::run("1/2").unwrap().nodes;
@@ -0,0 +1,54 @@
+use std::io::{BufRead, Write};
+
+use crate::{eval::eval, lexer::Lexer, parser::Parser};
+
+/// evaluates `input` one line at a time and writes each result to `output` until `input` ends.
+/// a line that fails to lex, parse or evaluate prints its error and the next line is read
+pub fn repl(input: impl BufRead, mut output: impl Write) -> std::io::Result<()> {
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        match run_line(&line) {
+            Ok(result) => writeln!(output, "{result}")?,
+            Err(err) => writeln!(output, "error: {err}")?,
+        }
+    }
+
+    Ok(())
+}
+
+fn run_line(line: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let tokens = Lexer::new(line).lex()?;
+    let ast = Parser::default().parse(tokens)?;
+    let result = eval(&ast.expr()?)?;
+
+    Ok(result.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use super::repl;
+
+    #[test]
+    fn evaluates_lines() {
+        let input = Cursor::new("1 + 2 * 3\n\n1 / 0\n4 > 2\n1 $ 2\n(1 + 2\n10 - 3\n");
+        let mut output = Vec::new();
+
+        repl(input, &mut output).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        let lines = output.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 6);
+        assert_eq!(lines[0], "7");
+        assert_eq!(lines[1], "error: divide by zero");
+        assert_eq!(lines[2], "true");
+        assert!(lines[3].starts_with("error: "));
+        assert_eq!(lines[4], "error: unbalanced parentheses");
+        assert_eq!(lines[5], "7");
+    }
+}